@@ -0,0 +1,255 @@
+//! Spawns a number of bots against a local server that walk, fight and chat,
+//! then reports ping latency, packet rates and disconnects.
+//!
+//! The server limits connections per IP, so raise `max_connections_per_ip`,
+//! `ip_connection_rate` and `ip_connection_burst` in settings.toml before running
+//! more than a handful of bots. Each bot uses two connections.
+//!
+//! Bots that were still in combat when the test ended stay on the servers
+//! disconnected list for a minute. Wait or use another `--prefix` before running again.
+
+use ascending_bot::*;
+use rand::Rng;
+use std::{
+    env,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+struct Options {
+    addr: String,
+    bots: usize,
+    secs: u64,
+    prefix: String,
+    password: String,
+    spawn_ms: u64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            addr: "127.0.0.1:7010".into(),
+            bots: 10,
+            secs: 60,
+            prefix: "bot".into(),
+            password: "Bot_password1".into(),
+            spawn_ms: 250,
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "Usage: loadtest [--addr 127.0.0.1:7010] [--bots 10] [--secs 60] [--prefix bot] [--password Bot_password1] [--spawn-ms 250]"
+    );
+    std::process::exit(1);
+}
+
+fn parse_options() -> Options {
+    let mut options = Options::default();
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        let Some(value) = args.next() else { usage() };
+
+        match arg.as_str() {
+            "--addr" => options.addr = value,
+            "--bots" => options.bots = value.parse().unwrap_or_else(|_| usage()),
+            "--secs" => options.secs = value.parse().unwrap_or_else(|_| usage()),
+            "--prefix" => options.prefix = value,
+            "--password" => options.password = value,
+            "--spawn-ms" => options.spawn_ms = value.parse().unwrap_or_else(|_| usage()),
+            _ => usage(),
+        }
+    }
+
+    options
+}
+
+#[derive(Default)]
+struct Stats {
+    joined: AtomicU64,
+    failed_joins: AtomicU64,
+    disconnects: AtomicU64,
+    sent: AtomicU64,
+    received: AtomicU64,
+    latencies: Mutex<Vec<Duration>>,
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+fn report(stats: &Stats, elapsed: Duration, sent: u64, received: u64) {
+    let mut latencies = stats.latencies.lock().unwrap().clone();
+    latencies.sort();
+
+    let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+
+    println!(
+        "joined: {}, failed joins: {}, disconnects: {}, sent/sec: {:.1}, received/sec: {:.1}, pings: {}, p50: {:?}, p90: {:?}, p99: {:?}, max: {:?}",
+        stats.joined.load(Ordering::Relaxed),
+        stats.failed_joins.load(Ordering::Relaxed),
+        stats.disconnects.load(Ordering::Relaxed),
+        sent as f64 / secs,
+        received as f64 / secs,
+        latencies.len(),
+        percentile(&latencies, 0.5),
+        percentile(&latencies, 0.9),
+        percentile(&latencies, 0.99),
+        latencies.last().copied().unwrap_or_default(),
+    );
+}
+
+/// Walks, fights and chats until `running` is cleared.
+fn run_bot(options: &Options, index: usize, stats: &Stats, running: &AtomicBool) {
+    let username = format!("{}{}", options.prefix, index);
+    let mut bot = match BotClient::join(
+        &options.addr,
+        &username,
+        &options.password,
+        Duration::from_secs(10),
+    ) {
+        Ok(bot) => bot,
+        Err(e) => {
+            eprintln!("{} failed to join: {}", username, e);
+            stats.failed_joins.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+    };
+
+    stats.joined.fetch_add(1, Ordering::Relaxed);
+
+    let mut rng = rand::rng();
+    let mut sent = bot.sent_packets();
+    let mut received = bot.received_packets();
+    let mut next_ping = Instant::now();
+    let mut next_action = Instant::now();
+    let mut next_chat = Instant::now() + Duration::from_secs(rng.random_range(5..15));
+
+    let result = (|| -> Result<()> {
+        while running.load(Ordering::Relaxed) {
+            let now = Instant::now();
+
+            if now >= next_ping {
+                bot.ping()?;
+                next_ping = now + Duration::from_secs(1);
+            }
+
+            if now >= next_action {
+                let dir = rng.random_range(0..4);
+
+                match rng.random_range(0..4) {
+                    0 => bot.game.send_move(None)?,
+                    1 => bot.game.send_attack(dir, None)?,
+                    _ => bot.game.send_move(Some(dir))?,
+                }
+
+                next_action = now + Duration::from_millis(rng.random_range(200..800));
+            }
+
+            if now >= next_chat {
+                bot.game.send_message(
+                    MessageChannel::Map,
+                    &format!("Hello from {}", username),
+                    "",
+                )?;
+                next_chat = now + Duration::from_secs(rng.random_range(5..15));
+            }
+
+            bot.poll()?;
+
+            if !bot.latencies.is_empty() {
+                stats.latencies.lock().unwrap().append(&mut bot.latencies);
+            }
+
+            stats
+                .sent
+                .fetch_add(bot.sent_packets() - sent, Ordering::Relaxed);
+            stats
+                .received
+                .fetch_add(bot.received_packets() - received, Ordering::Relaxed);
+            sent = bot.sent_packets();
+            received = bot.received_packets();
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        bot.game.send_disconnect()
+    })();
+
+    if let Err(e) = result
+        && running.load(Ordering::Relaxed)
+    {
+        eprintln!("{} was disconnected: {}", username, e);
+        stats.disconnects.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn main() {
+    let options = Arc::new(parse_options());
+    let stats = Arc::new(Stats::default());
+    let running = Arc::new(AtomicBool::new(true));
+    let mut handles = Vec::with_capacity(options.bots);
+
+    println!(
+        "Starting {} bots against {} for {} seconds.",
+        options.bots, options.addr, options.secs
+    );
+
+    let start = Instant::now();
+
+    for index in 0..options.bots {
+        let (bot_options, bot_stats, bot_running) =
+            (options.clone(), stats.clone(), running.clone());
+
+        handles.push(thread::spawn(move || {
+            run_bot(&bot_options, index, &bot_stats, &bot_running)
+        }));
+
+        thread::sleep(Duration::from_millis(options.spawn_ms));
+    }
+
+    let end = start + Duration::from_secs(options.secs);
+    let mut last = Instant::now();
+    let (mut last_sent, mut last_received) = (0, 0);
+
+    while Instant::now() < end {
+        thread::sleep(Duration::from_secs(10).min(end.saturating_duration_since(Instant::now())));
+
+        let (sent, received) = (
+            stats.sent.load(Ordering::Relaxed),
+            stats.received.load(Ordering::Relaxed),
+        );
+
+        report(
+            &stats,
+            last.elapsed(),
+            sent - last_sent,
+            received - last_received,
+        );
+        (last, last_sent, last_received) = (Instant::now(), sent, received);
+    }
+
+    running.store(false, Ordering::Relaxed);
+
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    println!("Final results over {:?}:", start.elapsed());
+    report(
+        &stats,
+        start.elapsed(),
+        stats.sent.load(Ordering::Relaxed),
+        stats.received.load(Ordering::Relaxed),
+    );
+}
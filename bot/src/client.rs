@@ -0,0 +1,231 @@
+use crate::{BotError, Connection, Packet, Result, ServerPackets};
+use std::{
+    collections::VecDeque,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A Character as listed on the character select screen.
+#[derive(Clone, Debug)]
+pub struct CharacterSummary {
+    pub slot: u8,
+    pub name: String,
+    pub level: i32,
+    pub sprite: u16,
+    pub deleted: bool,
+    pub restore_seconds_left: i64,
+}
+
+/// A player logged into the game. Like the real client it keeps the login
+/// connection open next to the game connection.
+pub struct BotClient {
+    pub login: Connection,
+    pub game: Connection,
+    /// Our entity key as sent in MyIndex.
+    pub entity: u64,
+    /// Reconnect code to send with Login if the connection drops.
+    pub code: String,
+    /// Round trip times of answered pings that have not been taken yet.
+    pub latencies: Vec<Duration>,
+    pings: VecDeque<Instant>,
+}
+
+impl BotClient {
+    /// Registers the account, or logs into it if it already exists, and joins the game
+    /// with the first Character on it.
+    pub fn join(addr: &str, username: &str, password: &str, timeout: Duration) -> Result<Self> {
+        let mut login = Connection::connect(addr)?;
+
+        login.send_protocol_version(false)?;
+        wait_for(&mut login, timeout, "ProtocolVersion", |mut packet| {
+            if packet.id != ServerPackets::ProtocolVersion {
+                return Ok(None);
+            }
+
+            let _version = packet.data.read::<u16>()?;
+
+            if packet.data.read::<bool>()? {
+                Ok(Some(()))
+            } else {
+                Err(BotError::ProtocolRejected)
+            }
+        })?;
+
+        let email = format!("{}@bots.invalid", username);
+
+        login.send_register(username, password, &email, 0)?;
+
+        let mut entity = None;
+        let registered = wait_for(&mut login, timeout, "Register", |mut packet| {
+            match packet.id {
+                ServerPackets::MyIndex => entity = Some(packet.data.read::<u64>()?),
+                ServerPackets::HandShake => return Ok(Some(Some(read_codes(&mut packet)?))),
+                ServerPackets::AlertMsg => {
+                    let msg = packet.data.read::<String>()?;
+
+                    return if msg.starts_with("Username Exists") {
+                        Ok(Some(None))
+                    } else {
+                        Err(BotError::Alert(msg))
+                    };
+                }
+                _ => {}
+            }
+
+            Ok(None)
+        })?;
+
+        let (code, handshake) = match registered {
+            Some(codes) => codes,
+            None => {
+                login.send_login(username, password, "")?;
+
+                let mut characters = wait_for_characters(&mut login, timeout)?;
+
+                if !characters.iter().any(|character| !character.deleted) {
+                    login.send_create_character(username, 0)?;
+                    characters = wait_for_characters(&mut login, timeout)?;
+                }
+
+                let slot = characters
+                    .iter()
+                    .find(|character| !character.deleted)
+                    .map(|character| character.slot)
+                    .ok_or(BotError::Alert("No Character to select".into()))?;
+
+                login.send_select_character(slot)?;
+                wait_for(&mut login, timeout, "HandShake", |mut packet| {
+                    match packet.id {
+                        ServerPackets::MyIndex => entity = Some(packet.data.read::<u64>()?),
+                        ServerPackets::HandShake => return Ok(Some(read_codes(&mut packet)?)),
+                        ServerPackets::AlertMsg => {
+                            return Err(BotError::Alert(packet.data.read::<String>()?));
+                        }
+                        _ => {}
+                    }
+
+                    Ok(None)
+                })?
+            }
+        };
+
+        let mut game = Connection::connect(addr)?;
+
+        game.send_handshake(&handshake)?;
+        wait_for(&mut game, timeout, "LoginOk", |mut packet| {
+            match packet.id {
+                ServerPackets::LoginOk => Ok(Some(())),
+                ServerPackets::AlertMsg => Err(BotError::Alert(packet.data.read::<String>()?)),
+                _ => Ok(None),
+            }
+        })?;
+        game.send_login_ok(&code)?;
+
+        Ok(Self {
+            login,
+            game,
+            entity: entity.unwrap_or_default(),
+            code,
+            latencies: Vec::new(),
+            pings: VecDeque::new(),
+        })
+    }
+
+    /// Sends a Ping. The round trip time is added to `latencies` once it is answered.
+    pub fn ping(&mut self) -> Result<()> {
+        self.game.send_ping()?;
+        self.pings.push_back(Instant::now());
+        Ok(())
+    }
+
+    /// Reads both connections, answers online checks and pings, and returns the game packets.
+    pub fn poll(&mut self) -> Result<Vec<Packet>> {
+        for mut packet in self.login.receive()? {
+            if packet.id == ServerPackets::AlertMsg {
+                return Err(BotError::Alert(packet.data.read::<String>()?));
+            }
+        }
+
+        let packets = self.game.receive()?;
+
+        for packet in &packets {
+            match packet.id {
+                ServerPackets::Ping => {
+                    if let Some(sent) = self.pings.pop_front() {
+                        self.latencies.push(sent.elapsed());
+                    }
+                }
+                ServerPackets::OnlineCheck => self.game.send_online_check()?,
+                _ => {}
+            }
+        }
+
+        Ok(packets)
+    }
+
+    pub fn sent_packets(&self) -> u64 {
+        self.login.sent_packets + self.game.sent_packets
+    }
+
+    pub fn received_packets(&self) -> u64 {
+        self.login.received_packets + self.game.received_packets
+    }
+}
+
+fn read_codes(packet: &mut Packet) -> Result<(String, String)> {
+    let code = packet.data.read::<String>()?;
+    let handshake = packet.data.read::<String>()?;
+
+    Ok((code, handshake))
+}
+
+fn wait_for_characters(login: &mut Connection, timeout: Duration) -> Result<Vec<CharacterSummary>> {
+    wait_for(login, timeout, "CharacterList", |mut packet| {
+        match packet.id {
+            ServerPackets::CharacterList => {
+                let _max = packet.data.read::<u8>()?;
+                let count = packet.data.read::<u8>()?;
+                let mut characters = Vec::with_capacity(count as usize);
+
+                for _ in 0..count {
+                    characters.push(CharacterSummary {
+                        slot: packet.data.read()?,
+                        name: packet.data.read()?,
+                        level: packet.data.read()?,
+                        sprite: packet.data.read()?,
+                        deleted: packet.data.read()?,
+                        restore_seconds_left: packet.data.read()?,
+                    });
+                }
+
+                Ok(Some(characters))
+            }
+            ServerPackets::AlertMsg => Err(BotError::Alert(packet.data.read::<String>()?)),
+            _ => Ok(None),
+        }
+    })
+}
+
+/// Reads packets until `check` returns a value. Packets it returns None for are dropped.
+pub fn wait_for<T>(
+    conn: &mut Connection,
+    timeout: Duration,
+    waiting_on: &'static str,
+    mut check: impl FnMut(Packet) -> Result<Option<T>>,
+) -> Result<T> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        for packet in conn.receive()? {
+            if let Some(value) = check(packet)? {
+                return Ok(value);
+            }
+        }
+
+        if Instant::now() > deadline {
+            return Err(BotError::Timeout(waiting_on));
+        }
+
+        thread::sleep(Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{BotError, ClientPacket, Result, ServerPackets};
+use bytey::ByteBuffer;
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+/// Set on the high bit of the length header when the server compressed the packet.
+const COMPRESSED_FLAG: u64 = 1 << 63;
+/// Largest packet the server will send, matching its own receive limit.
+const MAX_PACKET_SIZE: u64 = 8192;
+
+/// A packet from the server with the cursor left just after the packet id.
+pub struct Packet {
+    pub id: ServerPackets,
+    pub data: ByteBuffer,
+}
+
+/// A non blocking socket to one of the servers plain TCP listeners.
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    pub sent_packets: u64,
+    pub received_packets: u64,
+}
+
+impl Connection {
+    pub fn connect(addr: &str) -> Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+
+        Ok(Self {
+            stream,
+            incoming: Vec::with_capacity(8192),
+            sent_packets: 0,
+            received_packets: 0,
+        })
+    }
+
+    /// Creates a packet with room for the length and the packet id written.
+    pub fn new_packet(id: ClientPacket) -> Result<ByteBuffer> {
+        let mut buf = ByteBuffer::with_capacity(64)?;
+
+        buf.write(0u64)?;
+        buf.write(id)?;
+        Ok(buf)
+    }
+
+    /// Fills in the length and sends the packet.
+    pub fn send(&mut self, mut buf: ByteBuffer) -> Result<()> {
+        let length = buf.length();
+
+        buf.move_cursor(0)?;
+        buf.write((length - 8) as u64)?;
+
+        let mut bytes = buf.as_slice();
+
+        while !bytes.is_empty() {
+            match self.stream.write(bytes) {
+                Ok(0) => return Err(BotError::Disconnected),
+                Ok(n) => bytes = &bytes[n..],
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.sent_packets += 1;
+        Ok(())
+    }
+
+    /// Reads everything waiting on the socket and returns the packets that are complete.
+    pub fn receive(&mut self) -> Result<Vec<Packet>> {
+        let mut chunk = [0u8; 4096];
+
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(BotError::Disconnected),
+                Ok(n) => self.incoming.extend_from_slice(&chunk[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut packets = Vec::new();
+        let mut pos = 0;
+
+        while self.incoming.len() - pos >= 8 {
+            let mut length = [0u8; 8];
+            length.copy_from_slice(&self.incoming[pos..pos + 8]);
+            let length = u64::from_ne_bytes(length);
+
+            if length & COMPRESSED_FLAG != 0 {
+                return Err(BotError::Compressed);
+            }
+
+            if !(2..=MAX_PACKET_SIZE).contains(&length) {
+                return Err(BotError::InvalidLength(length));
+            }
+
+            let end = pos + 8 + length as usize;
+
+            if self.incoming.len() < end {
+                break;
+            }
+
+            let mut data = ByteBuffer::with_capacity(length as usize)?;
+            data.write_slice(&self.incoming[pos + 8..end])?;
+            data.move_cursor(0)?;
+
+            let id = data.read::<ServerPackets>()?;
+
+            packets.push(Packet { id, data });
+            pos = end;
+        }
+
+        self.incoming.drain(..pos);
+        self.received_packets += packets.len() as u64;
+        Ok(packets)
+    }
+}
@@ -0,0 +1,23 @@
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, BotError>;
+
+#[derive(Error, Debug)]
+pub enum BotError {
+    #[error("Server closed the connection")]
+    Disconnected,
+    #[error("Server sent an alert: {0}")]
+    Alert(String),
+    #[error("Server rejected the protocol version")]
+    ProtocolRejected,
+    #[error("Server sent a compressed packet, which bots do not ask for")]
+    Compressed,
+    #[error("Packet length {0} is not valid")]
+    InvalidLength(u64),
+    #[error("Timed out waiting for {0}")]
+    Timeout(&'static str),
+    #[error("Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Error: {0}")]
+    Bytey(#[from] bytey::ByteBufferError),
+}
@@ -0,0 +1,16 @@
+//! Headless client for driving AscendingServer from tests and load tests.
+//! Speaks the same length prefixed packets as the real client over plain TCP.
+
+mod client;
+mod connection;
+mod error;
+// Shared with the server so packet ids can never drift apart.
+#[path = "../../src/socket/packet_ids.rs"]
+mod packet_ids;
+mod sends;
+
+pub use client::*;
+pub use connection::*;
+pub use error::*;
+pub use packet_ids::*;
+pub use sends::*;
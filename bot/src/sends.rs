@@ -0,0 +1,154 @@
+use crate::{ClientPacket, Connection, Result};
+
+/// Highest protocol version the bots speak.
+pub const PROTOCOL_VERSION: u16 = 2;
+
+/// Chat channels in the order the server defines them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u16)]
+pub enum MessageChannel {
+    Map = 1,
+    Global,
+    Trade,
+    Party,
+    Private,
+    Guild,
+    Help,
+    Quest,
+    Npc,
+}
+
+impl Connection {
+    pub fn send_protocol_version(&mut self, wants_compression: bool) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::ProtocolVersion)?;
+
+        buf.write(PROTOCOL_VERSION)?;
+        buf.write(wants_compression)?;
+        self.send(buf)
+    }
+
+    pub fn send_online_check(&mut self) -> Result<()> {
+        let buf = Connection::new_packet(ClientPacket::OnlineCheck)?;
+
+        self.send(buf)
+    }
+
+    pub fn send_register(
+        &mut self,
+        username: &str,
+        password: &str,
+        email: &str,
+        sprite_id: u8,
+    ) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::Register)?;
+
+        buf.write(username)?;
+        buf.write(password)?;
+        buf.write(email)?;
+        buf.write(sprite_id)?;
+        self.send(buf)
+    }
+
+    pub fn send_login(
+        &mut self,
+        username: &str,
+        password: &str,
+        reconnect_code: &str,
+    ) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::Login)?;
+
+        buf.write(username)?;
+        buf.write(password)?;
+        buf.write(reconnect_code)?;
+        self.send(buf)
+    }
+
+    pub fn send_select_character(&mut self, slot: u8) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::SelectCharacter)?;
+
+        buf.write(slot)?;
+        self.send(buf)
+    }
+
+    pub fn send_create_character(&mut self, name: &str, sprite_id: u8) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::CreateCharacter)?;
+
+        buf.write(name)?;
+        buf.write(sprite_id)?;
+        self.send(buf)
+    }
+
+    pub fn send_handshake(&mut self, handshake: &str) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::HandShake)?;
+
+        buf.write(handshake)?;
+        self.send(buf)
+    }
+
+    pub fn send_login_ok(&mut self, code: &str) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::LoginOk)?;
+
+        buf.write(code)?;
+        self.send(buf)
+    }
+
+    /// Starts walking in `dir` or stops walking with None.
+    pub fn send_move(&mut self, dir: Option<u8>) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::Move)?;
+
+        buf.write(dir)?;
+        self.send(buf)
+    }
+
+    pub fn send_dir(&mut self, dir: u8) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::Dir)?;
+
+        buf.write(dir)?;
+        self.send(buf)
+    }
+
+    /// Attacks the `target` entity or whatever is in front when None.
+    pub fn send_attack(&mut self, dir: u8, target: Option<u64>) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::Attack)?;
+
+        buf.write(dir)?;
+        buf.write(target)?;
+        self.send(buf)
+    }
+
+    pub fn send_set_target(&mut self, target: Option<u64>) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::SetTarget)?;
+
+        buf.write(target)?;
+        self.send(buf)
+    }
+
+    pub fn send_pickup(&mut self) -> Result<()> {
+        let buf = Connection::new_packet(ClientPacket::PickUp)?;
+
+        self.send(buf)
+    }
+
+    /// `name` is only used by the Private channel.
+    pub fn send_message(&mut self, channel: MessageChannel, msg: &str, name: &str) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::Message)?;
+
+        buf.write(channel as u16)?;
+        buf.write(msg)?;
+        buf.write(name)?;
+        self.send(buf)
+    }
+
+    pub fn send_ping(&mut self) -> Result<()> {
+        let buf = Connection::new_packet(ClientPacket::Ping)?;
+
+        self.send(buf)
+    }
+
+    pub fn send_disconnect(&mut self) -> Result<()> {
+        let mut buf = Connection::new_packet(ClientPacket::Disconnect)?;
+
+        buf.write(0u32)?;
+        self.send(buf)
+    }
+}
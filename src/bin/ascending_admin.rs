@@ -0,0 +1,752 @@
+//! Offline tool for support staff to look at and fix a Character without raw SQL. It
+//! reads `settings.toml` from the working directory like the server does, and refuses to
+//! edit a Character that is online since the server would save over the edit.
+
+use ascending_server::{
+    containers::{Config, UserAccess, establish_connection, read_config},
+    gametypes::*,
+    items::{Item, ItemData, get_item},
+    maps::get_maps,
+    sql::*,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use std::{
+    collections::{BTreeMap, BTreeSet, btree_map::Entry},
+    env, fs,
+    process::exit,
+};
+use tokio::{runtime::Runtime, task::LocalSet};
+use uuid::Uuid;
+
+const USAGE: &str = "Usage: ascending_admin <command>
+
+A <character> is a Character name or id.
+
+  dump <character>                           Print the Character as JSON.
+  import <file>                              Write a Character printed by dump back.
+  give <character> <item> <amount>           Put items into the inventory.
+  remove <character> <item> <amount>         Take items out of the inventory.
+  level <character> <level>                  Set the level.
+  money <character> <amount>                 Set the money carried.
+  move <character> <x> <y> <map x> <map y> <group>
+                                             Move the Character.
+  access <character> <None|Monitor|Admin>    Set the UserAccess of the Account.
+  snapshots <character>                      List the newest snapshots.
+  snapshot <character> <id>                  Print a snapshot as JSON.
+  restore <character> <id>                   Put the Character back to a snapshot.
+  item-history <item> [limit]                Print the item ledger of an item number.
+  character-items <character> [limit]        Print the item ledger of a Character.
+  trade-items <trade>                        Print the item ledger of a trade id.
+  item-instance <instance>                   Print the item ledger of an item instance id.
+  duplicate-items                            List item instances with more than one copy
+                                             saved. Exits with 2 if any are found.
+
+Every change first saves what the Character held as an Admin snapshot. Ledger entries
+are printed newest first, up to 100 unless a limit is given.";
+
+/// How many snapshots `snapshots` lists.
+const SNAPSHOT_LIST_LIMIT: i64 = 50;
+/// How many ledger entries are printed when no limit is given.
+const ITEM_HISTORY_LIMIT: i64 = 100;
+
+/// A Character as `dump` prints it and `import` reads it back. The names and dates are
+/// shown for reference and are not imported.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct CharacterDump {
+    cid: Uuid,
+    uid: Uuid,
+    name: String,
+    username: String,
+    access: UserAccess,
+    deleted_on: Option<DateTime<Utc>>,
+    online_on: Option<DateTime<Utc>>,
+    state: CharacterState,
+}
+
+/// What is read for a Character, kept so only what an edit changed is written back.
+struct CharacterRows {
+    character: PGCharacterAdmin,
+    general: PGGeneral,
+    combat: PGCombat,
+    location: PGLocation,
+    inventory: Vec<PGInventorySlot>,
+    equipment: Vec<PGEquipmentSlot>,
+    storage: Vec<PGStorageSlot>,
+}
+
+impl CharacterRows {
+    async fn load(conn: &mut PgConnection, character: PGCharacterAdmin) -> Result<Self> {
+        let cid = character.cid;
+
+        Ok(Self {
+            general: pg_select(&mut *conn, cid).await?,
+            combat: pg_select(&mut *conn, cid).await?,
+            location: pg_select(&mut *conn, cid).await?,
+            inventory: pg_select_all(&mut *conn, cid).await?,
+            equipment: pg_select_all(&mut *conn, cid).await?,
+            storage: pg_select_all(&mut *conn, cid).await?,
+            character,
+        })
+    }
+
+    fn dump(&self) -> CharacterDump {
+        let character = &self.character;
+
+        CharacterDump {
+            cid: character.cid,
+            uid: character.uid,
+            name: character.name.clone(),
+            username: character.username.clone(),
+            access: character.useraccess,
+            deleted_on: character.deleted_on,
+            online_on: character.online_on,
+            state: CharacterState::from_rows(
+                &self.general,
+                &self.combat,
+                &self.location,
+                &self.inventory,
+                &self.equipment,
+                &self.storage,
+            ),
+        }
+    }
+
+    /// Writes what differs between `dump` and what was loaded, and records every item
+    /// that was added or taken in the item ledger.
+    async fn write(&self, conn: &mut PgConnection, dump: &CharacterDump) -> Result<()> {
+        let cid = self.character.cid;
+        let loaded = self.dump();
+        let inventory: Vec<i16> = self.inventory.iter().map(|slot| slot.id).collect();
+        let equipment: Vec<i16> = self.equipment.iter().map(|slot| slot.id).collect();
+        let storage: Vec<i16> = self.storage.iter().map(|slot| slot.id).collect();
+
+        check_slots("inventory", &dump.state.inventory, &inventory)?;
+        check_slots("equipment", &dump.state.equipment, &equipment)?;
+        check_slots("storage", &dump.state.storage, &storage)?;
+
+        if dump.access != loaded.access {
+            sql_lock_account_characters(&mut *conn, self.character.uid).await?;
+            sql_update_account(conn, self.character.uid, dump.access).await?;
+        }
+
+        let (dump, loaded) = (&dump.state, &loaded.state);
+
+        if (dump.sprite, dump.money, dump.resetcount)
+            != (loaded.sprite, loaded.money, loaded.resetcount)
+        {
+            let data = PGGeneral {
+                sprite: i16::unshift_signed(&dump.sprite),
+                money: i64::unshift_signed(&dump.money),
+                resetcount: dump.resetcount,
+                itemtimer: self.general.itemtimer,
+                deathtimer: self.general.deathtimer,
+            };
+
+            sql_update_general(conn, cid, &data).await?;
+        }
+
+        if (
+            dump.level,
+            dump.levelexp,
+            dump.pk,
+            dump.indeath,
+            dump.vital,
+            dump.vital_max,
+        ) != (
+            loaded.level,
+            loaded.levelexp,
+            loaded.pk,
+            loaded.indeath,
+            loaded.vital,
+            loaded.vital_max,
+        ) {
+            let data = PGCombat {
+                indeath: dump.indeath,
+                level: dump.level,
+                levelexp: i64::unshift_signed(&dump.levelexp),
+                pk: dump.pk,
+                vital: dump.vital,
+                vital_max: dump.vital_max,
+            };
+
+            sql_update_combat(conn, cid, &data).await?;
+        }
+
+        if (dump.pos, dump.spawn, dump.dir) != (loaded.pos, loaded.spawn, loaded.dir) {
+            let data = PGLocation {
+                spawn: dump.spawn,
+                pos: dump.pos,
+                dir: dump.dir as i16,
+            };
+
+            sql_update_location(conn, cid, &data).await?;
+        }
+
+        for (id, old, new) in changed_slots(&loaded.inventory, &dump.inventory) {
+            let data = PGInventorySlot::new(id as usize, &new);
+
+            sql_update_inventory_slot(conn, cid, &data).await?;
+            record_slot(conn, ItemPlace::Inventory, cid, &old, &new).await?;
+        }
+
+        for (id, old, new) in changed_slots(&loaded.equipment, &dump.equipment) {
+            let data = PGEquipmentSlot::new(id as usize, &new);
+
+            sql_update_equipment_slot(conn, cid, &data).await?;
+            record_slot(conn, ItemPlace::Equipment, cid, &old, &new).await?;
+        }
+
+        for (id, old, new) in changed_slots(&loaded.storage, &dump.storage) {
+            let data = PGStorageSlot::new(id as usize, &new);
+
+            sql_update_storage_slot(conn, cid, &data).await?;
+            record_slot(conn, ItemPlace::Storage, cid, &old, &new).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Fails if `items` names a slot the Character does not have.
+fn check_slots(place: &str, items: &BTreeMap<i16, Item>, ids: &[i16]) -> Result<()> {
+    match items.keys().find(|id| !ids.contains(id)) {
+        Some(id) => Err(AscendingError::AdminEdit(format!(
+            "the Character has no {} slot {}",
+            place, id
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Slots whose item differs, as the slot id with the old and new item.
+fn changed_slots(old: &BTreeMap<i16, Item>, new: &BTreeMap<i16, Item>) -> Vec<(i16, Item, Item)> {
+    let empty = Item {
+        val: 0,
+        level: 0,
+        ..Default::default()
+    };
+
+    old.keys()
+        .chain(new.keys())
+        .copied()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|id| {
+            let old = old.get(&id).copied().unwrap_or(empty);
+            let new = new.get(&id).copied().unwrap_or(empty);
+
+            (old != new).then_some((id, old, new))
+        })
+        .collect()
+}
+
+/// Records an admin change of one slot in the item ledger. A stack that only changed in
+/// size is recorded as the difference.
+async fn record_slot(
+    conn: &mut PgConnection,
+    place: ItemPlace,
+    cid: Uuid,
+    old: &Item,
+    new: &Item,
+) -> Result<()> {
+    let same = Item {
+        val: old.val,
+        ..*new
+    } == *old;
+    let mut events = Vec::with_capacity(2);
+
+    if same && new.val < old.val {
+        events.push(
+            PGItemEvent::new(ItemReason::Admin, new, old.val - new.val)
+                .from(place, Some(cid))
+                .to(ItemPlace::Void, None),
+        );
+    } else if same {
+        events.push(
+            PGItemEvent::new(ItemReason::Admin, new, new.val - old.val)
+                .from(ItemPlace::Void, None)
+                .to(place, Some(cid)),
+        );
+    } else {
+        if old.val > 0 {
+            events.push(
+                PGItemEvent::new(ItemReason::Admin, old, old.val)
+                    .from(place, Some(cid))
+                    .to(ItemPlace::Void, None),
+            );
+        }
+
+        if new.val > 0 {
+            events.push(
+                PGItemEvent::new(ItemReason::Admin, new, new.val)
+                    .from(ItemPlace::Void, None)
+                    .to(place, Some(cid)),
+            );
+        }
+    }
+
+    for event in events {
+        pg_insert(&mut *conn, Uuid::nil(), &event).await?;
+    }
+
+    Ok(())
+}
+
+/// Puts `amount` of item `num` into the first inventory slots that can take it. Fails
+/// without changing anything if they do not all fit.
+fn give_items(
+    inventory: &mut BTreeMap<i16, Item>,
+    slots: usize,
+    base: &ItemData,
+    num: u32,
+    amount: u16,
+) -> Result<()> {
+    let mut items = inventory.clone();
+    let mut left = amount;
+    let stack = if base.stackable {
+        base.stacklimit.max(1)
+    } else {
+        1
+    };
+
+    if base.stackable {
+        for item in items.values_mut().filter(|item| item.num == num) {
+            let add = left.min(stack.saturating_sub(item.val));
+
+            item.val += add;
+            left -= add;
+        }
+    }
+
+    for id in 0..slots as i16 {
+        if left == 0 {
+            break;
+        }
+
+        if let Entry::Vacant(slot) = items.entry(id) {
+            let val = left.min(stack);
+            let item = Item {
+                num,
+                val,
+                ..Default::default()
+            };
+
+            slot.insert(item.with_instance(base));
+            left -= val;
+        }
+    }
+
+    if left > 0 {
+        return Err(AscendingError::AdminEdit(format!(
+            "only {} of {} fit in the inventory",
+            amount - left,
+            amount
+        )));
+    }
+
+    *inventory = items;
+    Ok(())
+}
+
+/// Takes `amount` of item `num` out of the inventory, last slots first. Fails without
+/// changing anything if there are not that many.
+fn remove_items(inventory: &mut BTreeMap<i16, Item>, num: u32, amount: u16) -> Result<()> {
+    let held: u32 = inventory
+        .values()
+        .filter(|item| item.num == num)
+        .map(|item| item.val as u32)
+        .sum();
+
+    if held < amount as u32 {
+        return Err(AscendingError::AdminEdit(format!(
+            "the inventory only holds {} of item {}",
+            held, num
+        )));
+    }
+
+    let mut left = amount;
+
+    for item in inventory.values_mut().rev().filter(|item| item.num == num) {
+        let take = left.min(item.val);
+
+        item.val -= take;
+        left -= take;
+    }
+
+    inventory.retain(|_, item| item.val > 0);
+    Ok(())
+}
+
+fn parse<T: std::str::FromStr>(args: &[String], index: usize, what: &str) -> Result<T> {
+    let value = args
+        .get(index)
+        .ok_or_else(|| AscendingError::AdminEdit(USAGE.into()))?;
+
+    value
+        .parse()
+        .map_err(|_| AscendingError::AdminEdit(format!("{} {} is not valid", what, value)))
+}
+
+fn parse_access(value: &str) -> Result<UserAccess> {
+    match value {
+        "None" => Ok(UserAccess::None),
+        "Monitor" => Ok(UserAccess::Monitor),
+        "Admin" => Ok(UserAccess::Admin),
+        _ => Err(AscendingError::AdminEdit(format!(
+            "{} is not a UserAccess",
+            value
+        ))),
+    }
+}
+
+async fn find_character(
+    conn: &mut PgConnection,
+    character: &str,
+    lock: bool,
+) -> Result<PGCharacterAdmin> {
+    sql_admin_character(conn, character, lock)
+        .await?
+        .ok_or_else(|| AscendingError::AdminEdit(format!("no Character {}", character)))
+}
+
+/// Applies `change` to the Character and writes it in one transaction. The Character row
+/// stays locked until the edit is written, so it can not log in halfway through. What it
+/// held before is saved as a snapshot when `character_snapshots` is on.
+async fn edit(
+    conn: &PgPool,
+    config: &Config,
+    character: &str,
+    change: impl FnOnce(&mut CharacterDump, &CharacterRows) -> Result<()>,
+) -> Result<()> {
+    let mut tx = conn.begin().await?;
+    let character = find_character(&mut tx, character, true).await?;
+
+    character.check_offline()?;
+
+    let rows = CharacterRows::load(&mut tx, character).await?;
+    let mut dump = rows.dump();
+
+    change(&mut dump, &rows)?;
+
+    if dump == rows.dump() {
+        println!("Nothing to change for {}.", rows.character.name);
+        return Ok(());
+    }
+
+    if config.character_snapshots {
+        let before = PGCharacterSnapshot::new(SnapshotReason::Admin, rows.dump().state);
+
+        pg_insert(&mut *tx, rows.character.cid, &before).await?;
+    }
+
+    rows.write(&mut tx, &dump).await?;
+    tx.commit().await?;
+
+    println!("Updated {}.", rows.character.name);
+    Ok(())
+}
+
+async fn find_snapshot(
+    conn: &PgPool,
+    character: &str,
+    id: i64,
+) -> Result<PGCharacterSnapshotRecord> {
+    let mut conn = conn.acquire().await?;
+    let character = find_character(&mut conn, character, false).await?;
+
+    sql_character_snapshot(&mut *conn, character.cid, id)
+        .await?
+        .ok_or_else(|| {
+            AscendingError::AdminEdit(format!("{} has no snapshot {}", character.name, id))
+        })
+}
+
+fn print_item_events(records: &[PGItemEventRecord]) {
+    if records.is_empty() {
+        println!("No item events found.");
+    }
+
+    let owner = |uid: Option<Uuid>| uid.map(|uid| format!(" {}", uid)).unwrap_or_default();
+
+    for record in records {
+        let event = &record.event;
+
+        println!(
+            "{} #{} {:?} by {}: {} of item {}{} (val {}, level {}, data {:?}) from {:?}{} to {:?}{}{}{}",
+            event.created_on,
+            record.id,
+            event.reason,
+            record.uid,
+            event.quantity,
+            event.num,
+            event
+                .instance
+                .map(|id| format!(" instance {}", id))
+                .unwrap_or_default(),
+            event.val,
+            event.level,
+            event.data,
+            event.source,
+            owner(event.source_uid),
+            event.destination,
+            owner(event.destination_uid),
+            event
+                .pos
+                .map(|pos| format!(
+                    " at {},{} on map {},{},{}",
+                    pos.x, pos.y, pos.map.x, pos.map.y, pos.map.group
+                ))
+                .unwrap_or_default(),
+            event
+                .correlation
+                .map(|id| format!(" in trade {}", id))
+                .unwrap_or_default(),
+        );
+    }
+}
+
+async fn run(conn: &PgPool, config: &Config, args: &[String]) -> Result<()> {
+    let character = args.get(1).map(String::as_str).unwrap_or_default();
+
+    match args.first().map(String::as_str) {
+        Some("dump") if args.len() == 2 => {
+            let mut conn = conn.acquire().await?;
+            let character = find_character(&mut conn, character, false).await?;
+            let rows = CharacterRows::load(&mut conn, character).await?;
+
+            println!("{}", serde_json::to_string_pretty(&rows.dump())?);
+            Ok(())
+        }
+        Some("import") if args.len() == 2 => {
+            let import: CharacterDump = serde_json::from_str(&fs::read_to_string(character)?)?;
+            let items = get_item().len();
+
+            if let Some(item) = [
+                &import.state.inventory,
+                &import.state.equipment,
+                &import.state.storage,
+            ]
+            .into_iter()
+            .flat_map(|slots| slots.values())
+            .find(|item| item.num as usize >= items)
+            {
+                return Err(AscendingError::AdminEdit(format!(
+                    "there is no item {}",
+                    item.num
+                )));
+            }
+
+            edit(conn, config, &import.cid.to_string(), |dump, _| {
+                dump.access = import.access;
+                dump.state = import.state;
+                Ok(())
+            })
+            .await
+        }
+        Some(command @ ("give" | "remove")) if args.len() == 4 => {
+            let num: u32 = parse(args, 2, "item")?;
+            let amount: u16 = parse(args, 3, "amount")?;
+            let bases = get_item();
+            let base = bases
+                .get(num as usize)
+                .ok_or_else(|| AscendingError::AdminEdit(format!("there is no item {}", num)))?;
+
+            edit(conn, config, character, |dump, rows| {
+                if command == "give" {
+                    give_items(
+                        &mut dump.state.inventory,
+                        rows.inventory.len(),
+                        base,
+                        num,
+                        amount,
+                    )
+                } else {
+                    remove_items(&mut dump.state.inventory, num, amount)
+                }
+            })
+            .await
+        }
+        Some("level") if args.len() == 3 => {
+            let level: i32 = parse(args, 2, "level")?;
+
+            if !(1..=MAX_LVL as i32).contains(&level) {
+                return Err(AscendingError::AdminEdit(format!(
+                    "level must be 1 to {}",
+                    MAX_LVL
+                )));
+            }
+
+            edit(conn, config, character, |dump, _| {
+                dump.state.level = level;
+                Ok(())
+            })
+            .await
+        }
+        Some("money") if args.len() == 3 => {
+            let money: u64 = parse(args, 2, "amount")?;
+
+            edit(conn, config, character, |dump, _| {
+                dump.state.money = money;
+                Ok(())
+            })
+            .await
+        }
+        Some("move") if args.len() == 7 => {
+            let map = MapPosition::new(
+                parse(args, 4, "map x")?,
+                parse(args, 5, "map y")?,
+                parse(args, 6, "group")?,
+            );
+            let pos = Position::new(parse(args, 2, "x")?, parse(args, 3, "y")?, map);
+
+            if pos.left_map() {
+                return Err(AscendingError::AdminEdit(format!(
+                    "{},{} is not on a map",
+                    pos.x, pos.y
+                )));
+            }
+
+            if !get_maps().iter().any(|data| {
+                MapPosition::new(data.position.x, data.position.y, data.position.group) == map
+            }) {
+                return Err(AscendingError::MapNotFound(map));
+            }
+
+            edit(conn, config, character, |dump, _| {
+                dump.state.pos = pos;
+                Ok(())
+            })
+            .await
+        }
+        Some("access") if args.len() == 3 => {
+            let access = parse_access(&args[2])?;
+
+            edit(conn, config, character, |dump, _| {
+                dump.access = access;
+                Ok(())
+            })
+            .await
+        }
+        Some("snapshots") if args.len() == 2 => {
+            let mut conn = conn.acquire().await?;
+            let character = find_character(&mut conn, character, false).await?;
+            let records =
+                sql_character_snapshots(&mut *conn, character.cid, SNAPSHOT_LIST_LIMIT).await?;
+
+            if records.is_empty() {
+                println!("{} has no snapshots.", character.name);
+            }
+
+            for record in records {
+                let state = &record.snapshot.data;
+
+                println!(
+                    "{} #{} {:?}: level {}, money {}, {} inventory, {} equipment and {} storage items",
+                    record.snapshot.created_on,
+                    record.id,
+                    record.snapshot.reason,
+                    state.level,
+                    state.money,
+                    state.inventory.len(),
+                    state.equipment.len(),
+                    state.storage.len(),
+                );
+            }
+
+            Ok(())
+        }
+        Some("snapshot") if args.len() == 3 => {
+            let record = find_snapshot(conn, character, parse(args, 2, "snapshot")?).await?;
+
+            println!("{}", serde_json::to_string_pretty(&record.snapshot.data)?);
+            Ok(())
+        }
+        Some("restore") if args.len() == 3 => {
+            let record = find_snapshot(conn, character, parse(args, 2, "snapshot")?).await?;
+
+            edit(conn, config, &record.uid.to_string(), |dump, _| {
+                dump.state = record.snapshot.data.0;
+                Ok(())
+            })
+            .await
+        }
+        Some("item-history") if (2..=3).contains(&args.len()) => {
+            let num: u32 = parse(args, 1, "item")?;
+            let limit = match args.len() {
+                3 => parse(args, 2, "limit")?,
+                _ => ITEM_HISTORY_LIMIT,
+            };
+
+            print_item_events(&sql_item_history(conn, num, limit).await?);
+            Ok(())
+        }
+        Some("character-items") if (2..=3).contains(&args.len()) => {
+            let limit = match args.len() {
+                3 => parse(args, 2, "limit")?,
+                _ => ITEM_HISTORY_LIMIT,
+            };
+            let mut conn = conn.acquire().await?;
+            let character = find_character(&mut conn, character, false).await?;
+
+            print_item_events(&sql_character_item_history(&mut *conn, character.cid, limit).await?);
+            Ok(())
+        }
+        Some("trade-items") if args.len() == 2 => {
+            let correlation: Uuid = parse(args, 1, "trade")?;
+
+            print_item_events(&sql_correlated_item_events(conn, correlation).await?);
+            Ok(())
+        }
+        Some("item-instance") if args.len() == 2 => {
+            let instance: Uuid = parse(args, 1, "instance")?;
+
+            print_item_events(&sql_item_instance_events(conn, instance).await?);
+            Ok(())
+        }
+        Some("duplicate-items") if args.len() == 1 => {
+            let slots = sql_duplicate_item_instances(conn).await?;
+
+            if slots.is_empty() {
+                println!("No duplicate item instances found.");
+                return Ok(());
+            }
+
+            for slot in &slots {
+                println!(
+                    "instance {} of item {}: {} in {:?} slot {} of {}",
+                    slot.instance,
+                    slot.item_num(),
+                    slot.copies,
+                    slot.place,
+                    slot.id,
+                    slot.uid
+                );
+            }
+
+            exit(2);
+        }
+        _ => Err(AscendingError::AdminEdit(USAGE.into())),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    if args.is_empty() {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let config = read_config("settings.toml");
+    let mut rt = Runtime::new().unwrap();
+    let local = LocalSet::new();
+    let result = establish_connection(&config, &mut rt, &local)
+        .and_then(|conn| local.block_on(&rt, run(&conn, &config, &args)));
+
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
@@ -1,12 +1,16 @@
 mod bases;
 mod entity;
+mod login_guard;
 mod storage;
 mod world;
+mod world_snapshot;
 
 pub use bases::*;
 pub use entity::*;
+pub use login_guard::*;
 pub use storage::*;
 pub use world::*;
+pub use world_snapshot::*;
 
 //We redefine these here so it is easier to update the hash style later if we need too.
 pub type AHashBuildHasher = std::hash::BuildHasherDefault<ahash::AHasher>;
@@ -17,6 +17,7 @@ use super::{CombatData, MovementData};
 #[derive(Debug, Clone, Default)]
 pub struct PlayerEntity {
     pub account: Account,
+    pub character: Character,
 
     // Connection
     pub socket: Socket,
@@ -43,6 +44,8 @@ pub struct PlayerEntity {
     pub inventory: Inventory,
     pub equipment: Equipment,
     pub storage: PlayerStorage,
+    pub stash: AccountStash,
+    pub bank_money: Money,
 
     pub trade_item: TradeItem,
     pub trade_money: TradeMoney,
@@ -61,6 +64,14 @@ pub struct Account {
     pub id: Uuid,
 }
 
+/// The Character of an Account that is currently being played.
+#[derive(Clone, Debug, Default)]
+pub struct Character {
+    pub name: String,
+    pub id: Uuid,
+    pub slot: u8,
+}
+
 #[derive(Copy, Clone, Debug, Educe)]
 #[educe(Default)]
 pub struct PlayerConnection {
@@ -169,7 +180,33 @@ pub struct TradeRequestEntity {
 )]
 #[educe(Default)]
 pub struct PlayerStorage {
-    #[educe(Default = (0..MAX_STORAGE).map(|_| Item::default()).collect())]
+    #[educe(Default = (0..BASE_STORAGE).map(|_| Item::default()).collect())]
+    pub items: Vec<Item>,
+}
+
+impl PlayerStorage {
+    /// Number of tabs currently unlocked. Each tab holds STORAGE_TAB_SIZE slots.
+    pub fn tabs(&self) -> usize {
+        self.items.len().div_ceil(STORAGE_TAB_SIZE)
+    }
+
+    /// Grows the storage to hold `slots` slots. Never shrinks it.
+    pub fn resize(&mut self, slots: usize) {
+        let slots = slots.min(MAX_STORAGE);
+
+        if slots > self.items.len() {
+            self.items.resize(slots, Item::default());
+        }
+    }
+}
+
+/// Storage shared by every Character on the Account.
+#[derive(
+    PartialEq, Eq, Clone, Debug, Educe, Deserialize, Serialize, MByteBufferRead, MByteBufferWrite,
+)]
+#[educe(Default)]
+pub struct AccountStash {
+    #[educe(Default = (0..MAX_STASH).map(|_| Item::default()).collect())]
     pub items: Vec<Item>,
 }
 
@@ -0,0 +1,274 @@
+use crate::{containers::Config, time_ext::MyInstant};
+use chrono::Duration;
+use std::net::SocketAddr;
+
+use super::HashMap;
+
+/// Failed Login tracking for a single Account or IP.
+#[derive(Copy, Clone, Debug)]
+pub struct LoginAttempts {
+    pub failures: u32,
+    pub last_failure: MyInstant,
+    pub locked_until: MyInstant,
+}
+
+impl LoginAttempts {
+    fn new(tick: MyInstant) -> Self {
+        Self {
+            failures: 0,
+            last_failure: tick,
+            locked_until: tick,
+        }
+    }
+
+    /// Adds a failure and locks once the limit is reached. Each failure past the limit
+    /// doubles the lock time up to the max. Returns the lock time if it got locked.
+    fn add_failure(&mut self, config: &Config, limit: u32, tick: MyInstant) -> Option<Duration> {
+        self.failures = self.failures.saturating_add(1);
+        self.last_failure = tick;
+
+        if self.failures < limit {
+            return None;
+        }
+
+        let doublings = (self.failures - limit).min(31);
+        let secs = config
+            .login_lockout_secs
+            .saturating_mul(1u64 << doublings)
+            .min(config.login_lockout_max_secs);
+        let lockout = Duration::try_seconds(secs as i64).unwrap_or_default();
+
+        self.locked_until = tick + lockout;
+        Some(lockout)
+    }
+}
+
+/// Keeps track of failed Logins per Account and per IP so they can be locked out.
+#[derive(Debug, Default)]
+pub struct LoginGuard {
+    pub accounts: HashMap<String, LoginAttempts>,
+    pub ips: HashMap<String, LoginAttempts>,
+}
+
+impl LoginGuard {
+    /// Returns how long is left on the lockout if either the Account or IP is locked.
+    pub fn lockout_left(&self, account: &str, ip: &str, tick: MyInstant) -> Option<Duration> {
+        [self.accounts.get(account), self.ips.get(ip)]
+            .into_iter()
+            .flatten()
+            .filter(|attempts| attempts.locked_until > tick)
+            .map(|attempts| {
+                Duration::from_std(attempts.locked_until.0 - tick.0).unwrap_or_default()
+            })
+            .max()
+    }
+
+    /// Adds a failed Login. Returns the lockout if this failure caused one.
+    pub fn add_failure(
+        &mut self,
+        config: &Config,
+        account: &str,
+        ip: &str,
+        tick: MyInstant,
+    ) -> Option<Duration> {
+        let account_lock = self
+            .accounts
+            .entry(account.to_owned())
+            .or_insert_with(|| LoginAttempts::new(tick))
+            .add_failure(config, config.login_max_account_failures, tick);
+        let ip_lock = self
+            .ips
+            .entry(ip.to_owned())
+            .or_insert_with(|| LoginAttempts::new(tick))
+            .add_failure(config, config.login_max_ip_failures, tick);
+
+        account_lock.max(ip_lock)
+    }
+
+    /// Clears the Accounts failures after a successful Login.
+    pub fn clear_account(&mut self, account: &str) {
+        let _ = self.accounts.remove(account);
+    }
+
+    /// Forgets anything that is not locked and has not failed within the reset time.
+    pub fn clear_expired(&mut self, config: &Config, tick: MyInstant) {
+        let reset =
+            Duration::try_seconds(config.login_failure_reset_secs as i64).unwrap_or_default();
+        let keep = |attempts: &mut LoginAttempts| {
+            attempts.locked_until > tick || attempts.last_failure + reset > tick
+        };
+
+        self.accounts.retain(|_, attempts| keep(attempts));
+        self.ips.retain(|_, attempts| keep(attempts));
+    }
+}
+
+/// Gets the IP from a Socket address, dropping the port.
+pub fn addr_ip(addr: &str) -> String {
+    addr.parse::<SocketAddr>()
+        .map(|addr| addr.ip().to_string())
+        .unwrap_or_else(|_| addr.to_owned())
+}
+
+/// Makes a readable wait time for Lockout messages.
+pub fn format_wait(wait: Duration) -> String {
+    let secs = wait.num_seconds().max(1);
+
+    if secs >= 60 {
+        format!("{} minutes {} seconds", secs / 60, secs % 60)
+    } else {
+        format!("{} seconds", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::containers::test_config;
+
+    fn config() -> Config {
+        let mut config = test_config();
+
+        config.login_max_account_failures = 3;
+        config.login_max_ip_failures = 10;
+        config.login_lockout_secs = 30;
+        config.login_lockout_max_secs = 200;
+        config.login_failure_reset_secs = 600;
+        config
+    }
+
+    fn after(tick: MyInstant, secs: i64) -> MyInstant {
+        tick + Duration::try_seconds(secs).unwrap_or_default()
+    }
+
+    fn seconds(lockout: Option<Duration>) -> Option<i64> {
+        lockout.map(|lockout| lockout.num_seconds())
+    }
+
+    #[test]
+    fn lockout_doubles_up_to_the_max() {
+        let config = config();
+        let tick = MyInstant::now();
+        let mut guard = LoginGuard::default();
+        let locks: Vec<_> = (0..7)
+            .map(|_| seconds(guard.add_failure(&config, "bob", "1.1.1.1", tick)))
+            .collect();
+
+        assert_eq!(
+            locks,
+            [
+                None,
+                None,
+                Some(30),
+                Some(60),
+                Some(120),
+                Some(200),
+                Some(200)
+            ]
+        );
+    }
+
+    #[test]
+    fn lockout_does_not_overflow_after_many_failures() {
+        let mut config = config();
+        let tick = MyInstant::now();
+        let mut guard = LoginGuard::default();
+
+        config.login_lockout_max_secs = u64::MAX;
+
+        for _ in 0..100 {
+            assert!(
+                guard
+                    .add_failure(&config, "bob", "1.1.1.1", tick)
+                    .is_none_or(|lock| lock > Duration::zero())
+            );
+        }
+    }
+
+    #[test]
+    fn lockout_expires() {
+        let config = config();
+        let tick = MyInstant::now();
+        let mut guard = LoginGuard::default();
+
+        for _ in 0..3 {
+            guard.add_failure(&config, "bob", "1.1.1.1", tick);
+        }
+
+        assert_eq!(
+            seconds(guard.lockout_left("bob", "2.2.2.2", after(tick, 10))),
+            Some(20)
+        );
+        assert!(
+            guard
+                .lockout_left("bob", "2.2.2.2", after(tick, 30))
+                .is_none()
+        );
+        assert!(guard.lockout_left("alice", "2.2.2.2", tick).is_none());
+    }
+
+    #[test]
+    fn ip_is_locked_on_its_own_limit() {
+        let config = config();
+        let tick = MyInstant::now();
+        let mut guard = LoginGuard::default();
+
+        for i in 0..10 {
+            guard.add_failure(&config, &format!("user{}", i), "1.1.1.1", tick);
+        }
+
+        assert_eq!(
+            seconds(guard.lockout_left("someone", "1.1.1.1", tick)),
+            Some(30)
+        );
+        assert!(guard.lockout_left("someone", "2.2.2.2", tick).is_none());
+    }
+
+    #[test]
+    fn success_resets_the_account_schedule() {
+        let config = config();
+        let tick = MyInstant::now();
+        let mut guard = LoginGuard::default();
+
+        for _ in 0..2 {
+            guard.add_failure(&config, "bob", "1.1.1.1", tick);
+        }
+
+        guard.clear_account("bob");
+
+        assert!(guard.add_failure(&config, "bob", "1.1.1.1", tick).is_none());
+        assert!(guard.add_failure(&config, "bob", "1.1.1.1", tick).is_none());
+        assert_eq!(
+            seconds(guard.add_failure(&config, "bob", "1.1.1.1", tick)),
+            Some(30)
+        );
+    }
+
+    #[test]
+    fn clear_expired_keeps_locked_and_recent_failures() {
+        let config = config();
+        let tick = MyInstant::now();
+        let mut guard = LoginGuard::default();
+
+        guard.add_failure(&config, "old", "1.1.1.1", tick);
+        guard.add_failure(&config, "recent", "2.2.2.2", after(tick, 500));
+
+        guard.clear_expired(&config, after(tick, 700));
+        assert!(!guard.accounts.contains_key("old"));
+        assert!(!guard.ips.contains_key("1.1.1.1"));
+        assert!(guard.accounts.contains_key("recent"));
+        assert!(guard.ips.contains_key("2.2.2.2"));
+
+        let mut config = config;
+        config.login_lockout_secs = 10_000;
+        config.login_lockout_max_secs = 10_000;
+
+        for _ in 0..3 {
+            guard.add_failure(&config, "locked", "3.3.3.3", tick);
+        }
+
+        guard.clear_expired(&config, after(tick, 5_000));
+        assert!(guard.accounts.contains_key("locked"));
+        assert!(!guard.accounts.contains_key("recent"));
+    }
+}
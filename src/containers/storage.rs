@@ -1,9 +1,11 @@
 use crate::{
     containers::{Bases, HashMap, IndexMap, IndexSet},
     gametypes::*,
+    mailer::{Mailer, MailerKind, create_mailer},
     maps::*,
     npcs::*,
     socket::*,
+    sql::Persistence,
     tasks::{DataTaskToken, MapSwitchTasks},
     time_ext::MyInstant,
 };
@@ -11,15 +13,16 @@ use chrono::Duration;
 use log::{LevelFilter, error, info, trace, warn};
 use mio::{Poll, Token};
 use rustls::{
-    ServerConfig,
+    RootCertStore, ServerConfig,
     crypto::{CryptoProvider, ring as provider},
     pki_types::{CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
 };
 use serde::{Deserialize, Serialize};
 use slotmap::SecondaryMap;
 use sqlx::{
     ConnectOptions, PgPool,
-    postgres::{PgConnectOptions, PgPoolOptions},
+    postgres::{PgConnectOptions, PgPoolOptions, PgSslMode},
 };
 use std::{
     cell::RefCell,
@@ -27,14 +30,16 @@ use std::{
     fs,
     io::BufReader,
     sync::{Arc, Mutex},
+    time::SystemTime,
 };
 use tokio::runtime::Runtime;
 use tokio::task;
+use uuid::Uuid;
 
 use super::{
-    CombatData, Entity, EntityKind, GlobalKey, HashSet, LoginHandShake, MovementData, NpcEntity,
-    NpcMode, NpcTimer, PlayerConnectionTimer, PlayerEntity, ReloginCode, Socket, Spawn, Vitals,
-    World,
+    CombatData, Entity, EntityKind, GlobalKey, HashSet, LoginGuard, LoginHandShake, MovementData,
+    NpcEntity, NpcMode, NpcTimer, PlayerConnectionTimer, PlayerEntity, ReloginCode, Socket, Spawn,
+    Vitals, World, WorldSnapshot, load_world_snapshot,
 };
 
 #[derive(Hash, PartialEq, Eq, Clone)]
@@ -54,6 +59,10 @@ pub struct Storage {
     pub player_timeout: RefCell<SecondaryMap<GlobalKey, PlayerConnectionTimer>>,
     pub hand_shakes: RefCell<HashMap<String, GlobalKey>>,
     pub player_code: RefCell<IndexMap<String, GlobalKey>>,
+    //Accounts that have logged in but have not picked a Character yet, and when they got there.
+    pub char_select: RefCell<HashMap<Token, (Uuid, MyInstant)>>,
+    pub login_guard: RefCell<LoginGuard>,
+    pub compression_metrics: RefCell<CompressionMetrics>,
     //Keep track of older relogin codes so we can remove them after a set period of time.
     pub clear_code: RefCell<IndexSet<ClearCodeData>>,
     //This is for buffering the specific packets needing to send.
@@ -65,6 +74,8 @@ pub struct Storage {
     pub server: RefCell<Server>,
     pub gettick: RefCell<MyInstant>,
     pub pgconn: PgPool,
+    //Writes player saves off the game loop. Dropped before the runtime it uses.
+    pub persistence: Persistence,
     pub time: RefCell<GameTime>,
     pub map_switch_tasks: RefCell<IndexMap<GlobalKey, Vec<MapSwitchTasks>>>, //Data Tasks For dealing with Player Warp and MapSwitch
     pub bases: Bases,
@@ -72,25 +83,41 @@ pub struct Storage {
     pub local: RefCell<task::LocalSet>,
     pub config: Config,
     pub unload_npc: RefCell<Vec<GlobalKey>>,
+    pub mailer: Box<dyn Mailer>,
+    //Map items and NPCs read from the world snapshot until the World is made to put them in.
+    pub world_snapshot: RefCell<Option<WorldSnapshot>>,
 }
 
-fn establish_connection(
+pub fn establish_connection(
     config: &Config,
     rt: &mut Runtime,
     local: &task::LocalSet,
 ) -> Result<PgPool> {
     let mut connect_opts = PgConnectOptions::new();
-    connect_opts = connect_opts.log_statements(log::LevelFilter::Debug);
+    connect_opts = connect_opts.log_statements(config.db_statement_log_level.parse_enum());
     connect_opts = connect_opts.database(&config.database);
     connect_opts = connect_opts.username(&config.username);
     connect_opts = connect_opts.password(&config.password);
     connect_opts = connect_opts.host(&config.host);
     connect_opts = connect_opts.port(config.port);
+    connect_opts = connect_opts.ssl_mode(config.db_ssl_mode.into());
+
+    if !config.db_ssl_root_cert.is_empty() {
+        connect_opts = connect_opts.ssl_root_cert(&config.db_ssl_root_cert);
+    }
+
+    let idle_timeout = (config.db_idle_timeout_secs > 0)
+        .then(|| std::time::Duration::from_secs(config.db_idle_timeout_secs));
 
     let pool = local.block_on(
         rt,
         PgPoolOptions::new()
-            .max_connections(5)
+            .max_connections(config.db_max_connections.max(1))
+            .min_connections(config.db_min_connections)
+            .acquire_timeout(std::time::Duration::from_secs(
+                config.db_acquire_timeout_secs.max(1),
+            ))
+            .idle_timeout(idle_timeout)
             .connect_with(connect_opts),
     )?;
 
@@ -128,8 +155,14 @@ impl ServerLevelFilter {
 
 #[derive(Deserialize)]
 pub struct Config {
-    pub listen: String,
-    pub tls_listen: String,
+    #[serde(deserialize_with = "one_or_many")]
+    pub listen: Vec<String>,
+    #[serde(deserialize_with = "one_or_many")]
+    pub tls_listen: Vec<String>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub ws_listen: Vec<String>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub wss_listen: Vec<String>,
     pub server_cert: String,
     pub server_key: String,
     pub ca_root: String,
@@ -141,6 +174,338 @@ pub struct Config {
     pub port: u16,
     pub enable_backtrace: bool,
     pub level_filter: ServerLevelFilter,
+    #[serde(default = "default_storage_tab_price")]
+    pub storage_tab_price: u64,
+    #[serde(default = "default_rename_cooldown_days")]
+    pub rename_cooldown_days: u32,
+    #[serde(default = "default_character_restore_days")]
+    pub character_restore_days: u32,
+    #[serde(default)]
+    pub require_email_verification: bool,
+    #[serde(default = "default_email_verification_ttl_mins")]
+    pub email_verification_ttl_mins: u32,
+    #[serde(default = "default_email_verification_resend_secs")]
+    pub email_verification_resend_secs: u32,
+    #[serde(default)]
+    pub mailer: MailerKind,
+    #[serde(default = "default_mail_file")]
+    pub mail_file: String,
+    #[serde(default)]
+    pub server_id: i16,
+    #[serde(default = "default_login_max_account_failures")]
+    pub login_max_account_failures: u32,
+    #[serde(default = "default_login_max_ip_failures")]
+    pub login_max_ip_failures: u32,
+    #[serde(default = "default_login_lockout_secs")]
+    pub login_lockout_secs: u64,
+    #[serde(default = "default_login_lockout_max_secs")]
+    pub login_lockout_max_secs: u64,
+    #[serde(default = "default_login_failure_reset_secs")]
+    pub login_failure_reset_secs: u64,
+    #[serde(default = "default_packet_abuse_limit")]
+    pub packet_abuse_limit: u32,
+    #[serde(default = "default_packet_limits")]
+    pub packet_limits: HashMap<ClientPacket, PacketLimit>,
+    #[serde(default = "default_max_connections_per_ip")]
+    pub max_connections_per_ip: usize,
+    #[serde(default = "default_ip_connection_rate")]
+    pub ip_connection_rate: f32,
+    #[serde(default = "default_ip_connection_burst")]
+    pub ip_connection_burst: f32,
+    #[serde(default = "default_login_deadline_secs")]
+    pub login_deadline_secs: u64,
+    #[serde(default = "default_char_select_deadline_secs")]
+    pub char_select_deadline_secs: u64,
+    #[serde(default)]
+    pub ip_allow: Vec<String>,
+    #[serde(default)]
+    pub ip_deny: Vec<String>,
+    #[serde(default = "default_compression")]
+    pub compression: bool,
+    #[serde(default = "default_compression_threshold")]
+    pub compression_threshold: usize,
+    #[serde(default = "default_send_queue_soft_count")]
+    pub send_queue_soft_count: usize,
+    #[serde(default = "default_send_queue_soft_bytes")]
+    pub send_queue_soft_bytes: usize,
+    #[serde(default = "default_send_queue_hard_count")]
+    pub send_queue_hard_count: usize,
+    #[serde(default = "default_send_queue_hard_bytes")]
+    pub send_queue_hard_bytes: usize,
+    #[serde(default = "default_send_queue_hard_secs")]
+    pub send_queue_hard_secs: u64,
+    #[serde(default)]
+    pub tls_client_auth: TlsClientAuth,
+    #[serde(default = "default_tls_watch")]
+    pub tls_watch: bool,
+    #[serde(default = "default_persist_queue_capacity")]
+    pub persist_queue_capacity: usize,
+    #[serde(default = "default_persist_flush_ms")]
+    pub persist_flush_ms: u64,
+    #[serde(default = "default_persist_batch_size")]
+    pub persist_batch_size: usize,
+    #[serde(default = "default_item_ledger")]
+    pub item_ledger: bool,
+    #[serde(default)]
+    pub world_snapshot: bool,
+    #[serde(default = "default_world_snapshot_file")]
+    pub world_snapshot_file: String,
+    #[serde(default = "default_world_snapshot_secs")]
+    pub world_snapshot_secs: u64,
+    #[serde(default)]
+    pub world_snapshot_npcs: bool,
+    #[serde(default = "default_db_max_connections")]
+    pub db_max_connections: u32,
+    #[serde(default)]
+    pub db_min_connections: u32,
+    #[serde(default = "default_db_acquire_timeout_secs")]
+    pub db_acquire_timeout_secs: u64,
+    #[serde(default = "default_db_idle_timeout_secs")]
+    pub db_idle_timeout_secs: u64,
+    #[serde(default)]
+    pub db_ssl_mode: DbSslMode,
+    #[serde(default)]
+    pub db_ssl_root_cert: String,
+    #[serde(default = "default_db_statement_log_level")]
+    pub db_statement_log_level: ServerLevelFilter,
+    #[serde(default = "default_db_retry_min_ms")]
+    pub db_retry_min_ms: u64,
+    #[serde(default = "default_db_retry_max_ms")]
+    pub db_retry_max_ms: u64,
+    #[serde(default = "default_db_health_check_secs")]
+    pub db_health_check_secs: u64,
+    #[serde(default = "default_character_snapshots")]
+    pub character_snapshots: bool,
+    #[serde(default = "default_character_snapshot_secs")]
+    pub character_snapshot_secs: u64,
+    #[serde(default = "default_character_snapshot_keep_days")]
+    pub character_snapshot_keep_days: u32,
+    #[serde(default = "default_character_snapshot_keep_count")]
+    pub character_snapshot_keep_count: u32,
+}
+
+/// How the connection to the database uses TLS. Matches the libpq `sslmode` values.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DbSslMode {
+    Disable,
+    Allow,
+    #[default]
+    Prefer,
+    Require,
+    /// Require TLS and check the certificate against `db_ssl_root_cert`.
+    VerifyCa,
+    /// Like `VerifyCa` and also check the host name matches the certificate.
+    VerifyFull,
+}
+
+impl From<DbSslMode> for PgSslMode {
+    fn from(mode: DbSslMode) -> Self {
+        match mode {
+            DbSslMode::Disable => PgSslMode::Disable,
+            DbSslMode::Allow => PgSslMode::Allow,
+            DbSslMode::Prefer => PgSslMode::Prefer,
+            DbSslMode::Require => PgSslMode::Require,
+            DbSslMode::VerifyCa => PgSslMode::VerifyCa,
+            DbSslMode::VerifyFull => PgSslMode::VerifyFull,
+        }
+    }
+}
+
+/// Whether TLS Clients must show a certificate signed by `ca_root`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TlsClientAuth {
+    #[default]
+    Off,
+    /// Clients may show a certificate. Those that do are marked as trusted.
+    Optional,
+    /// Every TLS Client must show a certificate.
+    Required,
+}
+
+/// Lets listen addresses be set as a single string or a list of strings.
+fn one_or_many<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(addr) => vec![addr],
+        OneOrMany::Many(addrs) => addrs,
+    })
+}
+
+fn default_storage_tab_price() -> u64 {
+    10_000
+}
+
+fn default_rename_cooldown_days() -> u32 {
+    30
+}
+
+fn default_character_restore_days() -> u32 {
+    7
+}
+
+fn default_email_verification_ttl_mins() -> u32 {
+    60
+}
+
+fn default_email_verification_resend_secs() -> u32 {
+    300
+}
+
+fn default_mail_file() -> String {
+    "Mail.txt".into()
+}
+
+fn default_login_max_account_failures() -> u32 {
+    5
+}
+
+fn default_login_max_ip_failures() -> u32 {
+    20
+}
+
+fn default_login_lockout_secs() -> u64 {
+    30
+}
+
+fn default_login_lockout_max_secs() -> u64 {
+    3600
+}
+
+fn default_login_failure_reset_secs() -> u64 {
+    900
+}
+
+fn default_packet_abuse_limit() -> u32 {
+    20
+}
+
+fn default_max_connections_per_ip() -> usize {
+    8
+}
+
+fn default_ip_connection_rate() -> f32 {
+    1.0
+}
+
+fn default_ip_connection_burst() -> f32 {
+    5.0
+}
+
+fn default_login_deadline_secs() -> u64 {
+    60
+}
+
+fn default_char_select_deadline_secs() -> u64 {
+    300
+}
+
+fn default_compression() -> bool {
+    true
+}
+
+fn default_compression_threshold() -> usize {
+    256
+}
+
+fn default_send_queue_soft_count() -> usize {
+    256
+}
+
+fn default_send_queue_soft_bytes() -> usize {
+    256 * 1024
+}
+
+fn default_send_queue_hard_count() -> usize {
+    2048
+}
+
+fn default_send_queue_hard_bytes() -> usize {
+    2 * 1024 * 1024
+}
+
+fn default_send_queue_hard_secs() -> u64 {
+    10
+}
+
+fn default_tls_watch() -> bool {
+    true
+}
+
+fn default_persist_queue_capacity() -> usize {
+    65536
+}
+
+fn default_persist_flush_ms() -> u64 {
+    500
+}
+
+fn default_persist_batch_size() -> usize {
+    256
+}
+
+fn default_item_ledger() -> bool {
+    true
+}
+
+fn default_world_snapshot_file() -> String {
+    "world_snapshot.json".into()
+}
+
+fn default_world_snapshot_secs() -> u64 {
+    300
+}
+
+fn default_db_max_connections() -> u32 {
+    5
+}
+
+fn default_db_acquire_timeout_secs() -> u64 {
+    5
+}
+
+fn default_db_idle_timeout_secs() -> u64 {
+    600
+}
+
+fn default_db_statement_log_level() -> ServerLevelFilter {
+    ServerLevelFilter::Debug
+}
+
+fn default_db_retry_min_ms() -> u64 {
+    500
+}
+
+fn default_db_retry_max_ms() -> u64 {
+    30000
+}
+
+fn default_db_health_check_secs() -> u64 {
+    5
+}
+
+fn default_character_snapshots() -> bool {
+    true
+}
+
+fn default_character_snapshot_secs() -> u64 {
+    1800
+}
+
+fn default_character_snapshot_keep_days() -> u32 {
+    30
+}
+
+fn default_character_snapshot_keep_count() -> u32 {
+    100
 }
 
 pub fn read_config(path: &str) -> Config {
@@ -148,75 +513,132 @@ pub fn read_config(path: &str) -> Config {
     toml::from_str(&data).unwrap()
 }
 
-fn load_certs(filename: &str) -> Vec<CertificateDer<'static>> {
-    let certfile = fs::File::open(filename).expect("cannot open certificate file");
+/// The shipped settings.toml.default, for tests that need a Config.
+#[cfg(test)]
+pub fn test_config() -> Config {
+    toml::from_str(include_str!("../../settings.toml.default")).expect("default settings parse")
+}
+
+fn load_certs(filename: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let certfile = fs::File::open(filename)?;
     let mut reader = BufReader::new(certfile);
-    rustls_pemfile::certs(&mut reader)
-        .map(|result| result.unwrap())
-        .collect()
+    let certs = rustls_pemfile::certs(&mut reader).collect::<std::io::Result<Vec<_>>>()?;
+
+    if certs.is_empty() {
+        return Err(
+            rustls::Error::General(format!("no certificates found in {:?}", filename)).into(),
+        );
+    }
+
+    Ok(certs)
 }
 
-fn load_private_key(filename: &str) -> PrivateKeyDer<'static> {
-    let keyfile = fs::File::open(filename).expect("cannot open private key file");
+fn load_private_key(filename: &str) -> Result<PrivateKeyDer<'static>> {
+    let keyfile = fs::File::open(filename)?;
     let mut reader = BufReader::new(keyfile);
 
     loop {
-        match rustls_pemfile::read_one(&mut reader).expect("cannot parse private key .pem file") {
-            Some(rustls_pemfile::Item::Pkcs1Key(key)) => return key.into(),
-            Some(rustls_pemfile::Item::Pkcs8Key(key)) => return key.into(),
-            Some(rustls_pemfile::Item::Sec1Key(key)) => return key.into(),
+        match rustls_pemfile::read_one(&mut reader)? {
+            Some(rustls_pemfile::Item::Pkcs1Key(key)) => return Ok(key.into()),
+            Some(rustls_pemfile::Item::Pkcs8Key(key)) => return Ok(key.into()),
+            Some(rustls_pemfile::Item::Sec1Key(key)) => return Ok(key.into()),
             None => break,
             _ => {}
         }
     }
 
-    panic!(
+    Err(rustls::Error::General(format!(
         "no keys found in {:?} (encrypted keys not supported)",
         filename
-    );
+    ))
+    .into())
 }
 
-fn build_tls_config(
-    server_certs_path: &str,
-    server_key_path: &str,
-    _ca_root_path: &str,
-) -> Result<Arc<rustls::ServerConfig>> {
-    let certs = load_certs(server_certs_path);
-    let private_key = load_private_key(server_key_path);
+/// Builds the TLS config from the cert files in Config. Called again when the certs are reloaded.
+pub fn build_tls_config(config: &Config) -> Result<Arc<rustls::ServerConfig>> {
+    let certs = load_certs(&config.server_cert)?;
+    let private_key = load_private_key(&config.server_key)?;
+    let provider: Arc<CryptoProvider> = CryptoProvider {
+        cipher_suites: provider::ALL_CIPHER_SUITES.to_vec(),
+        ..provider::default_provider()
+    }
+    .into();
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(rustls::ALL_VERSIONS)?;
+
+    let builder = match config.tls_client_auth {
+        TlsClientAuth::Off => builder.with_no_client_auth(),
+        TlsClientAuth::Optional | TlsClientAuth::Required => {
+            let mut roots = RootCertStore::empty();
+
+            for cert in load_certs(&config.ca_root)? {
+                roots.add(cert)?;
+            }
+
+            let verifier = WebPkiClientVerifier::builder_with_provider(roots.into(), provider);
+            let verifier = if config.tls_client_auth == TlsClientAuth::Optional {
+                verifier.allow_unauthenticated().build()?
+            } else {
+                verifier.build()?
+            };
 
-    let config = ServerConfig::builder_with_provider(
-        CryptoProvider {
-            cipher_suites: provider::ALL_CIPHER_SUITES.to_vec(),
-            ..provider::default_provider()
+            builder.with_client_cert_verifier(verifier)
         }
-        .into(),
-    )
-    .with_protocol_versions(rustls::ALL_VERSIONS)
-    .unwrap()
-    .with_no_client_auth()
-    .with_single_cert(certs, private_key)?;
+    };
+
+    Ok(Arc::new(builder.with_single_cert(certs, private_key)?))
+}
 
-    Ok(Arc::new(config))
+/// Latest modified time of the files used to build the TLS config.
+pub fn tls_files_modified(config: &Config) -> Option<SystemTime> {
+    let mut files = vec![&config.server_cert, &config.server_key];
+
+    if config.tls_client_auth != TlsClientAuth::Off {
+        files.push(&config.ca_root);
+    }
+
+    files
+        .into_iter()
+        .filter_map(|file| fs::metadata(file).and_then(|meta| meta.modified()).ok())
+        .max()
 }
 
 impl Storage {
     pub fn new(config: Config) -> Option<Self> {
         let mut poll = Poll::new().ok()?;
-        let tls_config =
-            build_tls_config(&config.server_cert, &config.server_key, &config.ca_root).unwrap();
-        let server = Server::new(
-            &mut poll,
-            &config.listen,
-            &config.tls_listen,
-            config.maxconnections,
-            tls_config,
-        )
-        .ok()?;
+        let tls_config = build_tls_config(&config).unwrap();
+        let server = Server::new(&mut poll, &config, tls_config, IpFilter::new(&config)).ok()?;
 
         let mut rt: Runtime = Runtime::new().unwrap();
         let local = task::LocalSet::new();
-        let pgconn = establish_connection(&config, &mut rt, &local).unwrap();
-        crate::sql::initiate(&pgconn, &mut rt, &local).unwrap();
+        let pgconn = match establish_connection(&config, &mut rt, &local) {
+            Ok(pgconn) => pgconn,
+            Err(e) => {
+                error!("Failed to connect to the database: {}", e);
+                return None;
+            }
+        };
+
+        if let Err(e) = crate::sql::migrate(&pgconn, &mut rt, &local, false) {
+            error!("Failed to migrate the database: {}", e);
+            return None;
+        }
+
+        match crate::sql::sql_clear_online_characters(&pgconn, &mut rt, &local, config.server_id) {
+            Ok(0) => {}
+            Ok(count) => warn!("Cleared {} characters left online by the last run.", count),
+            Err(e) => error!("Failed to clear characters left online: {}", e),
+        }
+
+        let persistence = match Persistence::new(&config, pgconn.clone(), rt.handle().clone()) {
+            Ok(persistence) => persistence,
+            Err(e) => {
+                error!("Failed to start the persistence worker: {}", e);
+                return None;
+            }
+        };
+        let world_snapshot = load_world_snapshot(&config);
 
         let mut storage = Self {
             player_ids: RefCell::new(IndexSet::default()),
@@ -231,18 +653,29 @@ impl Storage {
             packet_cache_ids: RefCell::new(IndexSet::default()),
             hand_shakes: RefCell::new(HashMap::default()),
             player_code: RefCell::new(IndexMap::default()),
+            char_select: RefCell::new(HashMap::default()),
+            login_guard: RefCell::new(LoginGuard::default()),
+            compression_metrics: RefCell::new(CompressionMetrics::default()),
             clear_code: RefCell::new(IndexSet::default()),
             poll: RefCell::new(poll),
             server: RefCell::new(server),
             gettick: RefCell::new(MyInstant::now()),
             pgconn,
-            time: RefCell::new(GameTime::default()),
+            persistence,
+            time: RefCell::new(
+                world_snapshot
+                    .as_ref()
+                    .map(|snapshot| snapshot.time)
+                    .unwrap_or_default(),
+            ),
             map_switch_tasks: RefCell::new(IndexMap::default()),
             bases: Bases::new()?,
             rt: RefCell::new(rt),
             local: RefCell::new(local),
+            mailer: create_mailer(&config),
             config,
             unload_npc: RefCell::new(Vec::with_capacity(32)),
+            world_snapshot: RefCell::new(world_snapshot),
         };
 
         let mut map_data_entry = crate::maps::get_maps();
@@ -348,9 +781,9 @@ impl Storage {
 
                     self.player_names
                         .borrow_mut()
-                        .remove(&p_data.account.username);
+                        .remove(&p_data.character.name);
 
-                    info!("Players Disconnected : {}", &p_data.account.username);
+                    info!("Players Disconnected : {}", &p_data.character.name);
                     trace!("Players Disconnected IP: {} ", &p_data.socket.addr);
                 }
 
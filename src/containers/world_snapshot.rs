@@ -0,0 +1,336 @@
+use crate::{
+    containers::{
+        Config, DespawnTimer, Entity, EntityKind, GlobalKey, MapItem, MapItemEntity, Storage, World,
+    },
+    gametypes::*,
+    items::Item,
+    maps::spawn_npc,
+    time_ext::MyInstant,
+};
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+use uuid::Uuid;
+
+/// Bump this when a change to the snapshot can not be read by just defaulting the new
+/// fields, and upgrade the older versions in `WorldSnapshot::upgrade`.
+pub const WORLD_SNAPSHOT_VERSION: u32 = 1;
+
+/// World state that is kept across restarts when `world_snapshot` is on. Timers are
+/// saved as the milliseconds they had left.
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct WorldSnapshot {
+    pub version: u32,
+    pub saved_on: DateTime<Utc>,
+    pub time: GameTime,
+    pub map_items: Vec<MapItemSnapshot>,
+    pub zones: Vec<ZoneSnapshot>,
+    /// Only saved with `world_snapshot_npcs`.
+    pub npcs: Option<Vec<NpcSnapshot>>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct MapItemSnapshot {
+    pub item: Item,
+    pub pos: Position,
+    pub despawn: Option<MyInstant>,
+    pub ownertimer: Option<MyInstant>,
+    /// Character id of the owner.
+    pub owner: Option<Uuid>,
+}
+
+/// How many NPCs were spawned in each zone of a map.
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct ZoneSnapshot {
+    pub map: MapPosition,
+    pub counts: [u64; 5],
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct NpcSnapshot {
+    pub index: u64,
+    pub pos: Position,
+    pub dir: u8,
+    pub zone: Option<usize>,
+    pub vital: [i32; VITALS_MAX],
+}
+
+impl WorldSnapshot {
+    pub fn capture(world: &World, storage: &Storage) -> Result<Self> {
+        let mut map_items = Vec::with_capacity(storage.map_items.borrow().len());
+
+        for id in storage.map_items.borrow().values() {
+            if let Some(Entity::MapItem(mi_data)) = world.get_opt_entity(*id) {
+                let map_item = mi_data.try_lock()?.general;
+                let owner = match map_item.ownerid.and_then(|id| world.get_opt_entity(id)) {
+                    Some(Entity::Player(p_data)) => Some(p_data.try_lock()?.character.id),
+                    _ => None,
+                };
+
+                map_items.push(MapItemSnapshot {
+                    item: map_item.item,
+                    pos: map_item.pos,
+                    despawn: map_item.despawn,
+                    ownertimer: map_item.ownertimer,
+                    owner,
+                });
+            }
+        }
+
+        let zones = storage
+            .maps
+            .iter()
+            .filter_map(|(position, map_data)| {
+                let counts = map_data.borrow().zones;
+
+                counts
+                    .iter()
+                    .any(|count| *count > 0)
+                    .then_some(ZoneSnapshot {
+                        map: *position,
+                        counts,
+                    })
+            })
+            .collect();
+
+        let npcs = if storage.config.world_snapshot_npcs {
+            let mut npcs = Vec::with_capacity(storage.npc_ids.borrow().len());
+
+            for id in storage.npc_ids.borrow().iter() {
+                if let Some(Entity::Npc(n_data)) = world.get_opt_entity(*id) {
+                    let n_data = n_data.try_lock()?;
+
+                    if n_data.combat.death_type.is_alive() {
+                        npcs.push(NpcSnapshot {
+                            index: n_data.index,
+                            pos: n_data.movement.pos,
+                            dir: n_data.movement.dir,
+                            zone: n_data.spawned_zone.0,
+                            vital: n_data.combat.vitals.vital,
+                        });
+                    }
+                }
+            }
+
+            Some(npcs)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            version: WORLD_SNAPSHOT_VERSION,
+            saved_on: Utc::now(),
+            time: *storage.time.borrow(),
+            map_items,
+            zones,
+            npcs,
+        })
+    }
+
+    /// Reads the snapshot in `path`. Returns None if there is no snapshot yet.
+    pub fn load(path: &str) -> Result<Option<Self>> {
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let snapshot: Self = serde_json::from_str(&data)?;
+
+        if snapshot.version > WORLD_SNAPSHOT_VERSION {
+            return Err(AscendingError::SnapshotTooNew {
+                snapshot: snapshot.version,
+                binary: WORLD_SNAPSHOT_VERSION,
+            });
+        }
+
+        Ok(Some(snapshot.upgrade()))
+    }
+
+    /// Brings a snapshot from an older version up to `WORLD_SNAPSHOT_VERSION`.
+    fn upgrade(mut self) -> Self {
+        self.version = WORLD_SNAPSHOT_VERSION;
+        self
+    }
+
+    /// Writes the snapshot to a temporary file next to `path` and then moves it over
+    /// `path`, so a crash while writing never leaves a half written snapshot.
+    pub fn write(&self, path: &str) -> Result<()> {
+        let tmp = format!("{}.tmp", path);
+
+        fs::write(&tmp, serde_json::to_vec(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Puts the saved map items and NPCs back into the World. Items and NPCs whose map
+    /// or data no longer exists are skipped. Zone spawn counts are rebuilt from the NPCs
+    /// that come back, since those are what the counts go back down for.
+    pub fn restore(self, world: &mut World, storage: &Storage) -> Result<()> {
+        let mut items = 0;
+
+        for saved in self.map_items {
+            let Some(map_data) = storage.maps.get(&saved.pos.map) else {
+                continue;
+            };
+
+            if saved.item.num as usize >= storage.bases.items.len()
+                || storage.map_items.borrow().contains_key(&saved.pos)
+            {
+                continue;
+            }
+
+            let id = world.kinds.insert(EntityKind::MapItem);
+
+            world.entities.insert(
+                id,
+                Entity::MapItem(Arc::new(Mutex::new(MapItemEntity {
+                    general: MapItem {
+                        item: saved.item,
+                        despawn: saved.despawn,
+                        ownertimer: saved.ownertimer,
+                        // Owners are never online this early so the items come back unowned.
+                        ownerid: None,
+                        pos: saved.pos,
+                    },
+                    despawn_timer: saved.despawn.map(DespawnTimer).unwrap_or_default(),
+                }))),
+            );
+
+            map_data.borrow_mut().itemids.insert(id);
+            storage.map_items.borrow_mut().insert(saved.pos, id);
+            items += 1;
+        }
+
+        let mut npcs = 0;
+
+        for saved in self.npcs.unwrap_or_default() {
+            if !storage.config.world_snapshot_npcs {
+                break;
+            }
+
+            let Some(map_data) = storage.maps.get(&saved.pos.map) else {
+                continue;
+            };
+
+            let Some(id) = storage.add_npc(world, saved.index)? else {
+                continue;
+            };
+
+            let zone = {
+                let mut data = map_data.borrow_mut();
+                let zone = saved.zone.filter(|zone| *zone < data.zones.len());
+
+                data.add_npc(id);
+
+                if let Some(zone) = zone {
+                    data.zones[zone] = data.zones[zone].saturating_add(1);
+                }
+
+                zone
+            };
+
+            spawn_npc(world, saved.pos, zone, id)?;
+            restore_npc(world, id, &saved)?;
+            npcs += 1;
+        }
+
+        for saved in self.zones {
+            if let Some(map_data) = storage.maps.get(&saved.map)
+                && map_data.borrow().zones != saved.counts
+                && storage.config.world_snapshot_npcs
+            {
+                warn!(
+                    "Zone spawn counts on map {:?} were {:?} when saved but {:?} NPCs came back.",
+                    saved.map,
+                    saved.counts,
+                    map_data.borrow().zones
+                );
+            }
+        }
+
+        info!(
+            "Restored world snapshot from {}: {} map items and {} NPCs.",
+            self.saved_on, items, npcs
+        );
+        Ok(())
+    }
+}
+
+fn restore_npc(world: &mut World, id: GlobalKey, saved: &NpcSnapshot) -> Result<()> {
+    if let Some(Entity::Npc(n_data)) = world.get_opt_entity(id) {
+        let mut n_data = n_data.try_lock()?;
+
+        let vitalmax = n_data.combat.vitals.vitalmax;
+
+        n_data.movement.dir = saved.dir;
+
+        for (i, vital) in n_data.combat.vitals.vital.iter_mut().enumerate() {
+            *vital = saved.vital[i].clamp(1, vitalmax[i].max(1));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the world snapshot for `Storage::new`. A snapshot that can not be read is
+/// logged and moved aside to `<file>.unreadable` so the server still starts without
+/// writing over it.
+pub fn load_world_snapshot(config: &Config) -> Option<WorldSnapshot> {
+    if !config.world_snapshot {
+        return None;
+    }
+
+    match WorldSnapshot::load(&config.world_snapshot_file) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            error!(
+                "Failed to read the world snapshot {}: {}",
+                config.world_snapshot_file, e
+            );
+
+            let unreadable = format!("{}.unreadable", config.world_snapshot_file);
+
+            if let Err(e) = fs::rename(&config.world_snapshot_file, &unreadable) {
+                error!("Failed to move the world snapshot to {}: {}", unreadable, e);
+            }
+
+            None
+        }
+    }
+}
+
+/// Restores what `Storage::new` read from the world snapshot into the new World.
+pub fn restore_world_snapshot(world: &mut World, storage: &Storage) -> Result<()> {
+    match storage.world_snapshot.borrow_mut().take() {
+        Some(snapshot) => snapshot.restore(world, storage),
+        None => Ok(()),
+    }
+}
+
+/// Writes the world snapshot. With `wait` it is written before returning, otherwise it
+/// is written on a blocking thread so the game loop does not wait on the disk.
+pub fn save_world_snapshot(world: &World, storage: &Storage, wait: bool) -> Result<()> {
+    let snapshot = WorldSnapshot::capture(world, storage)?;
+    let path = storage.config.world_snapshot_file.clone();
+
+    if wait {
+        return snapshot.write(&path);
+    }
+
+    storage.rt.borrow().spawn_blocking(move || {
+        if let Err(e) = snapshot.write(&path) {
+            error!("Failed to write the world snapshot {}: {}", path, e);
+        }
+    });
+
+    Ok(())
+}
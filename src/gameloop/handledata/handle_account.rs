@@ -1,24 +1,36 @@
 use std::sync::{Arc, Mutex};
 
 use chrono::Duration;
-use log::info;
+use log::{info, warn};
 use mio::Token;
 use mmap_bytey::MByteBuffer;
 use rand::distr::{Alphanumeric, SampleString};
 use regex::Regex;
+use uuid::Uuid;
 
 use crate::{
     containers::{
-        Entity, EntityKind, GlobalKey, PlayerConnectionTimer, Socket, Storage, World,
-        create_player_entity,
+        Entity, EntityKind, GlobalKey, PlayerConnectionTimer, Socket, Storage, World, addr_ip,
+        create_player_entity, format_wait,
     },
     gametypes::*,
+    mailer::Mail,
     players::{
         is_name_acceptable, is_password_acceptable, joingame, reconnect_player, send_login_info,
         send_reconnect_info,
     },
-    socket::{ClientState, disconnect, send_codes, send_infomsg, send_myindex},
-    sql::{check_existance, find_player, load_player, new_player},
+    socket::{
+        ClientState, disconnect, send_character_list, send_codes, send_infomsg, send_message,
+        send_myindex, send_protocol_version,
+    },
+    sql::{
+        PGCharacter, PGLog, SaveCommand, check_existance, find_player, load_player, new_character,
+        new_player, sql_character_name_taken, sql_email_verification_sent_within,
+        sql_find_account_id, sql_is_email_verified, sql_load_character, sql_load_characters,
+        sql_new_email_verification, sql_new_log, sql_rename_character, sql_restore_character,
+        sql_soft_delete_character, sql_verify_email,
+    },
+    tasks::{DataTaskToken, player_spawn_packet},
 };
 
 use super::SocketID;
@@ -34,14 +46,15 @@ pub fn handle_register(
     let password = data.read::<String>()?;
     let email = data.read::<String>()?;
     let sprite_id = data.read::<u8>()?;
-    let appmajor = data.read::<u16>()? as usize;
-    let appminior = data.read::<u16>()? as usize;
-    let apprevision = data.read::<u16>()? as usize;
 
     if entity.is_some() {
         return Err(AscendingError::InvalidSocket);
     }
 
+    if database_unavailable(storage, socket_id.id)? {
+        return Ok(());
+    }
+
     let socket = if let Some(client) = storage.server.borrow().clients.get(&socket_id.id) {
         let brw_client = client.borrow();
         Socket::new(Token(0), socket_id.id, brw_client.addr.to_string())?
@@ -49,15 +62,6 @@ pub fn handle_register(
         return Err(AscendingError::InvalidSocket);
     };
 
-    if APP_MAJOR > appmajor && APP_MINOR > appminior && APP_REVISION > apprevision {
-        return send_infomsg(
-            storage,
-            socket.tls_id,
-            "Client needs to be updated.".into(),
-            1,
-        );
-    }
-
     let email_regex = Regex::new(r#"^[^\s@]+@([^\s@.,]+\.)+[^\s@.,]{2,}$"#)?;
 
     if !username.chars().all(is_name_acceptable) || !password.chars().all(is_password_acceptable) {
@@ -120,8 +124,30 @@ pub fn handle_register(
         Err(_) => return Err(AscendingError::UserNotFound),
     }
 
-    match new_player(storage, username.clone(), email, password, &socket) {
-        Ok(uid) => {
+    match new_player(
+        storage,
+        username.clone(),
+        email.clone(),
+        password,
+        sprite_id as u16,
+        &socket,
+    ) {
+        Ok((uid, _cid)) if storage.config.require_email_verification => {
+            send_verification_mail(storage, uid, &email)?;
+
+            info!(
+                "New Account {} with IP {}, Awaiting email verification.",
+                username, socket.addr
+            );
+
+            send_infomsg(
+                storage,
+                socket.tls_id,
+                "Account Created. Please enter the verification code sent to your email.".into(),
+                0,
+            )
+        }
+        Ok((uid, cid)) => {
             let code = Alphanumeric.sample_string(&mut rand::rng(), 32);
             let handshake = Alphanumeric.sample_string(&mut rand::rng(), 32);
 
@@ -136,11 +162,16 @@ pub fn handle_register(
             if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
                 let mut p_data = p_data.try_lock()?;
 
-                p_data.account.username.clone_from(&username);
                 p_data.account.id = uid;
+                p_data.account.username.clone_from(&username);
+                p_data.character.id = cid;
+                p_data.character.name.clone_from(&username);
+                p_data.character.slot = 0;
                 p_data.sprite.id = sprite_id as u16;
             }
 
+            world.account_id.insert(uid, entity);
+
             storage
                 .hand_shakes
                 .borrow_mut()
@@ -235,15 +266,16 @@ pub fn handle_login(
 ) -> Result<()> {
     let username = data.read::<String>()?;
     let password = data.read::<String>()?;
-    let appmajor = data.read::<u16>()? as usize;
-    let appminior = data.read::<u16>()? as usize;
-    let apprevision = data.read::<u16>()? as usize;
     let reconnect_code = data.read::<String>()?;
 
     if entity.is_some() {
         return Err(AscendingError::InvalidSocket);
     }
 
+    if database_unavailable(storage, socket_id.id)? {
+        return Ok(());
+    }
+
     let socket = if let Some(client) = storage.server.borrow().clients.get(&socket_id.id) {
         let brw_client = client.borrow();
         Socket::new(Token(0), socket_id.id, brw_client.addr.to_string())?
@@ -251,36 +283,65 @@ pub fn handle_login(
         return Err(AscendingError::InvalidSocket);
     };
 
-    if APP_MAJOR > appmajor && APP_MINOR > appminior && APP_REVISION > apprevision {
+    if username.len() >= 64 || password.len() >= 128 {
         return send_infomsg(
             storage,
             socket.tls_id,
-            "Client needs to be updated.".into(),
-            1,
+            "Account does not Exist or Password is not Correct.".into(),
+            0,
         );
     }
 
-    if username.len() >= 64 || password.len() >= 128 {
+    let login_key = lockout_key(storage, &username)?;
+    let ip = addr_ip(&socket.addr);
+    let tick = *storage.gettick.borrow();
+
+    if let Some(wait) = storage
+        .login_guard
+        .borrow()
+        .lockout_left(&login_key, &ip, tick)
+    {
         return send_infomsg(
             storage,
             socket.tls_id,
-            "Account does not Exist or Password is not Correct.".into(),
-            0,
+            format!(
+                "Too many failed Logins. Please wait {} before trying again.",
+                format_wait(wait)
+            ),
+            1,
         );
     }
 
-    let id = match find_player(storage, &username, &password)? {
-        Some(id) => id,
-        None => {
-            return send_infomsg(
-                storage,
-                socket.tls_id,
-                "Account does not Exist or Password is not Correct.".into(),
-                1,
-            );
+    let (id, email) = match find_player(storage, &username, &password) {
+        Ok(Some(account)) => account,
+        Ok(None) | Err(AscendingError::IncorrectPassword) => {
+            return login_failed(storage, &socket, &username, &login_key, &ip);
         }
+        Err(e) => return Err(e),
     };
 
+    storage.login_guard.borrow_mut().clear_account(&login_key);
+
+    if storage.config.require_email_verification && !sql_is_email_verified(storage, id)? {
+        // Send a fresh code in case the last one never arrived, but not more often than
+        // `email_verification_resend_secs` so the inbox can not be flooded.
+        if !sql_email_verification_sent_within(
+            storage,
+            id,
+            storage.config.email_verification_resend_secs,
+        )? {
+            send_verification_mail(storage, id, &email)?;
+        }
+
+        return send_infomsg(
+            storage,
+            socket.tls_id,
+            "Email has not been verified. Please enter the verification code sent to your email."
+                .into(),
+            0,
+        );
+    }
+
     // we need to Add all the player types creations in a sub function that Creates the Defaults and then adds them to World.
     let code = Alphanumeric.sample_string(&mut rand::rng(), 32);
     let handshake = Alphanumeric.sample_string(&mut rand::rng(), 32);
@@ -294,7 +355,7 @@ pub fn handle_login(
                 // Character is on disconnected list
                 reconnect_player(world, storage, *old_entity, socket.clone())?;
 
-                let name = { p_data.try_lock()?.account.username.clone() };
+                let name = { p_data.try_lock()?.character.name.clone() };
 
                 info!(
                     "Player {} with IP: {}, Reconnecting from disconnected player.",
@@ -313,11 +374,10 @@ pub fn handle_login(
                 // Connected in same code but not disconnected
                 let old_code = { p_data.try_lock()?.relogin_code.clone() };
 
-                // if old code is empty means they did get unloaded just not all the way for some reason.
+                // if old code is empty means they did get unloaded just not all the way for some reason,
+                // so finish removing them or their Account stays marked as logged in.
                 if old_code.code.is_empty() {
-                    let name = { p_data.try_lock()?.account.username.clone() };
-
-                    let _ = storage.player_names.borrow_mut().remove(&name);
+                    storage.remove_player(world, *old_entity)?;
                 } else if !reconnect_code.is_empty() && old_code.code.contains(&reconnect_code) {
                     let p_data = p_data.try_lock()?;
 
@@ -325,7 +385,7 @@ pub fn handle_login(
                         disconnect_player = Some(*old_entity);
                         unload_socket = Some((p_data.socket.tls_id, p_data.socket.id))
                     } else {
-                        let name = p_data.account.username.clone();
+                        let name = p_data.character.name.clone();
 
                         info!(
                             "Player {} with IP: {}, Reconnecting not in disconnected player.",
@@ -375,50 +435,103 @@ pub fn handle_login(
         }
     }
 
-    let user_entity = world.entities.iter().find_map(|(entity, data)| {
-        if let Entity::Player(p_data) = data {
-            if let Ok(player) = p_data.try_lock() {
-                if player.account.username == username {
-                    Some(entity)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    });
-
     // This check is in case the account is connected on different entity
-    if let Some(old_entity) = user_entity {
+    if let Some(old_entity) = world.get_account_id(&id) {
         if let Some(Entity::Player(p_data)) = world.get_opt_entity(old_entity) {
             let old_code = { p_data.try_lock()?.relogin_code.clone() };
 
-            // if old code is empty means they did get unloaded just not all the way for some reason.
+            // if old code is empty means they did get unloaded just not all the way for some reason,
+            // so finish removing them or their Account stays marked as logged in.
             if old_code.code.is_empty() {
-                let name = { p_data.try_lock()?.account.username.clone() };
-
-                let _ = storage.player_names.borrow_mut().remove(&name);
+                storage.remove_player(world, old_entity)?;
             } else if !reconnect_code.is_empty() && old_code.code.contains(&reconnect_code) {
                 disconnect(old_entity, world, storage)?;
             } else {
                 return send_infomsg(storage, socket.tls_id, "Error Loading User.".into(), 1);
             }
+        } else {
+            // The entity is already gone, only the Account mapping was left behind.
+            let _ = world.account_id.remove(&id);
         }
     }
 
+    let characters = match sql_load_characters(storage, id) {
+        Ok(characters) => characters,
+        Err(_) => return send_infomsg(storage, socket.tls_id, "Error Loading User.".into(), 1),
+    };
+
+    info!(
+        "Account {} with IP: {}, Selecting Character.",
+        username, socket.addr
+    );
+
+    enter_char_select(storage, socket.tls_id, id);
+    send_character_list(storage, socket.tls_id, &characters)
+}
+
+/// Puts the socket on the character select screen for the Account. Any other socket still
+/// on it for the same Account is closed, so an Account only ever has one session.
+fn enter_char_select(storage: &Storage, socket_id: Token, account_id: Uuid) {
+    let others: Vec<Token> = storage
+        .char_select
+        .borrow()
+        .iter()
+        .filter(|(token, (uid, _))| **token != socket_id && *uid == account_id)
+        .map(|(token, _)| *token)
+        .collect();
+
+    for token in others {
+        let _ = storage.char_select.borrow_mut().remove(&token);
+
+        if let Some(client) = storage.server.borrow().clients.get(&token) {
+            client.borrow_mut().state = ClientState::Closing;
+        }
+    }
+
+    let tick = *storage.gettick.borrow();
+
+    storage
+        .char_select
+        .borrow_mut()
+        .insert(socket_id, (account_id, tick));
+}
+
+/// Loads the selected Character into the World and sends the codes needed to finish joining.
+fn login_character(
+    world: &mut World,
+    storage: &Storage,
+    socket: Socket,
+    account_id: Uuid,
+    character: &PGCharacter,
+) -> Result<()> {
+    // Two sessions of one Account would each load their own copy of the stash and bank.
+    if world.get_account_id(&account_id).is_some() {
+        return send_infomsg(
+            storage,
+            socket.tls_id,
+            "Account is already logged in.".into(),
+            1,
+        );
+    }
+
+    let code = Alphanumeric.sample_string(&mut rand::rng(), 32);
+    let handshake = Alphanumeric.sample_string(&mut rand::rng(), 32);
+
     let entity = world.kinds.insert(EntityKind::Player);
     let mut player_entity = create_player_entity(code.clone(), handshake.clone(), socket.clone());
 
-    if let Err(_e) = load_player(storage, &mut player_entity, id) {
+    if let Err(_e) = load_player(storage, &mut player_entity, account_id, character) {
+        let _ = world.kinds.remove(entity);
+        let _ = storage
+            .persistence
+            .save(SaveCommand::Offline { cid: character.cid });
         return send_infomsg(storage, socket.tls_id, "Error Loading User.".into(), 1);
     }
 
     world
         .entities
         .insert(entity, Entity::Player(Arc::new(Mutex::new(player_entity))));
+    world.account_id.insert(account_id, entity);
 
     storage.player_ids.borrow_mut().insert(entity);
 
@@ -439,7 +552,7 @@ pub fn handle_login(
         .insert(handshake.clone(), entity);
 
     let name = if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
-        let name = p_data.try_lock()?.account.username.clone();
+        let name = p_data.try_lock()?.character.name.clone();
 
         info!("Player {} with IP: {}, Logging in.", &name, &socket.addr);
 
@@ -450,3 +563,512 @@ pub fn handle_login(
 
     send_login_info(world, storage, entity, code, handshake, socket.tls_id, name)
 }
+
+/// The key failures are counted under for a typed username or email. Known Accounts use
+/// their uid, so the username and email of one Account share a single lockout.
+fn lockout_key(storage: &Storage, login: &str) -> Result<String> {
+    Ok(match sql_find_account_id(storage, login)? {
+        Some(uid) => uid.to_string(),
+        None => login.to_lowercase(),
+    })
+}
+
+/// Counts the failure towards the Account and IP lockouts, logs it and lets the client know.
+fn login_failed(
+    storage: &Storage,
+    socket: &Socket,
+    login: &str,
+    login_key: &str,
+    ip: &str,
+) -> Result<()> {
+    let tick = *storage.gettick.borrow();
+    let lockout =
+        storage
+            .login_guard
+            .borrow_mut()
+            .add_failure(&storage.config, login_key, ip, tick);
+
+    warn!("Failed Login for {} with IP: {}", login, socket.addr);
+
+    sql_new_log(
+        storage,
+        &PGLog::new(
+            storage.config.server_id,
+            Uuid::nil(),
+            LogType::Warning,
+            format!("Failed Login for {}", login),
+            socket.addr.to_string(),
+        ),
+    )?;
+
+    let msg = match lockout {
+        Some(wait) => format!(
+            "Account does not Exist or Password is not Correct. Too many failed Logins, please wait {} before trying again.",
+            format_wait(wait)
+        ),
+        None => "Account does not Exist or Password is not Correct.".into(),
+    };
+
+    send_infomsg(storage, socket.tls_id, msg, 1)
+}
+
+pub fn handle_protocolversion(
+    _world: &mut World,
+    storage: &Storage,
+    data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    socket_id: SocketID,
+) -> Result<()> {
+    let version = data.read::<u16>()?;
+    let wants_compression = version >= 2 && data.read::<bool>()?;
+
+    if entity.is_some() {
+        return Err(AscendingError::InvalidSocket);
+    }
+
+    let accepted = COMPATIBLE_PROTOCOLS.contains(&version);
+    // Version 1 Clients do not know about compression so they get no answer for it.
+    let compression =
+        (version >= 2).then_some(accepted && wants_compression && storage.config.compression);
+
+    if let Some(client) = storage.server.borrow().clients.get(&socket_id.id) {
+        let mut client = client.borrow_mut();
+
+        if accepted {
+            client.protocol_version = version;
+            client.compression = compression.unwrap_or_default();
+        } else {
+            info!(
+                "Client with IP: {} rejected using protocol version {}.",
+                client.addr, version
+            );
+        }
+    } else {
+        return Err(AscendingError::InvalidSocket);
+    }
+
+    send_protocol_version(storage, socket_id.id, accepted, compression)?;
+
+    if accepted {
+        Ok(())
+    } else {
+        send_infomsg(
+            storage,
+            socket_id.id,
+            "Client needs to be updated.".into(),
+            1,
+        )
+    }
+}
+
+/// Creates a new verification code for the Account and mails it out.
+fn send_verification_mail(storage: &Storage, uid: Uuid, email: &str) -> Result<()> {
+    let token = Alphanumeric.sample_string(&mut rand::rng(), 8);
+
+    sql_new_email_verification(storage, uid, &token)?;
+
+    storage.mailer.send(&Mail {
+        to: email.to_owned(),
+        subject: "Verify your Account".into(),
+        body: format!("Your verification code is: {}", token),
+    })
+}
+
+pub fn handle_verifyemail(
+    _world: &mut World,
+    storage: &Storage,
+    data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    socket_id: SocketID,
+) -> Result<()> {
+    let email = data.read::<String>()?;
+    let token = data.read::<String>()?;
+
+    if entity.is_some() {
+        return Err(AscendingError::InvalidSocket);
+    }
+
+    if database_unavailable(storage, socket_id.id)? {
+        return Ok(());
+    }
+
+    let addr = match storage.server.borrow().clients.get(&socket_id.id) {
+        Some(client) => client.borrow().addr.to_string(),
+        None => return Err(AscendingError::InvalidSocket),
+    };
+    let login_key = lockout_key(storage, &email)?;
+    let ip = addr_ip(&addr);
+    let tick = *storage.gettick.borrow();
+
+    if let Some(wait) = storage
+        .login_guard
+        .borrow()
+        .lockout_left(&login_key, &ip, tick)
+    {
+        return send_infomsg(
+            storage,
+            socket_id.id,
+            format!(
+                "Too many failed attempts. Please wait {} before trying again.",
+                format_wait(wait)
+            ),
+            0,
+        );
+    }
+
+    if email.len() >= 128
+        || token.len() >= 64
+        || !sql_verify_email(
+            storage,
+            &email,
+            &token,
+            storage.config.email_verification_ttl_mins,
+        )?
+    {
+        let _ =
+            storage
+                .login_guard
+                .borrow_mut()
+                .add_failure(&storage.config, &login_key, &ip, tick);
+
+        warn!("Failed email verification for {} with IP: {}", email, addr);
+
+        return send_infomsg(
+            storage,
+            socket_id.id,
+            "Verification code is not Correct or has expired.".into(),
+            0,
+        );
+    }
+
+    info!("Email {} verified.", email);
+
+    send_infomsg(
+        storage,
+        socket_id.id,
+        "Email verified. You may now Login.".into(),
+        0,
+    )
+}
+
+/// Tells the client to try again later while the database can not be reached. Returns
+/// true when it did, so the handler stops there.
+fn database_unavailable(storage: &Storage, socket_id: Token) -> Result<bool> {
+    if !storage.persistence.degraded() {
+        return Ok(false);
+    }
+
+    send_infomsg(
+        storage,
+        socket_id,
+        "The server can not reach its database right now. Please try again in a moment.".into(),
+        0,
+    )?;
+    Ok(true)
+}
+
+/// Gets the Account that is currently on the character select screen for this socket.
+fn char_select_account(storage: &Storage, socket_id: &SocketID) -> Result<Uuid> {
+    storage
+        .char_select
+        .borrow()
+        .get(&socket_id.id)
+        .map(|(account_id, _)| *account_id)
+        .ok_or(AscendingError::InvalidSocket)
+}
+
+/// Returns the reason a Character name is not allowed.
+fn check_character_name(name: &str) -> Option<String> {
+    if name.is_empty() || !name.chars().all(is_name_acceptable) {
+        return Some("Name contains unaccepted Characters".into());
+    }
+
+    if name.len() > MAX_NAME_LENGTH {
+        return Some(format!(
+            "Name has too many Characters, {MAX_NAME_LENGTH} Characters Max"
+        ));
+    }
+
+    None
+}
+
+fn send_characters(storage: &Storage, socket_id: Token, account_id: Uuid) -> Result<()> {
+    let characters = sql_load_characters(storage, account_id)?;
+
+    send_character_list(storage, socket_id, &characters)
+}
+
+pub fn handle_selectcharacter(
+    world: &mut World,
+    storage: &Storage,
+    data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    socket_id: SocketID,
+) -> Result<()> {
+    let slot = data.read::<u8>()?;
+
+    if entity.is_some() {
+        return Err(AscendingError::InvalidSocket);
+    }
+
+    if database_unavailable(storage, socket_id.id)? {
+        return Ok(());
+    }
+
+    let account_id = char_select_account(storage, &socket_id)?;
+
+    let socket = if let Some(client) = storage.server.borrow().clients.get(&socket_id.id) {
+        let brw_client = client.borrow();
+        Socket::new(Token(0), socket_id.id, brw_client.addr.to_string())?
+    } else {
+        return Err(AscendingError::InvalidSocket);
+    };
+
+    let character = match sql_load_character(storage, account_id, slot as i16)? {
+        Some(character) => character,
+        None => {
+            return send_infomsg(
+                storage,
+                socket.tls_id,
+                "Character does not Exist.".into(),
+                0,
+            );
+        }
+    };
+
+    let _ = storage.char_select.borrow_mut().remove(&socket.tls_id);
+
+    login_character(world, storage, socket, account_id, &character)
+}
+
+pub fn handle_createcharacter(
+    _world: &mut World,
+    storage: &Storage,
+    data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    socket_id: SocketID,
+) -> Result<()> {
+    let name = data.read::<String>()?;
+    let sprite_id = data.read::<u8>()?;
+
+    if entity.is_some() {
+        return Err(AscendingError::InvalidSocket);
+    }
+
+    if database_unavailable(storage, socket_id.id)? {
+        return Ok(());
+    }
+
+    let account_id = char_select_account(storage, &socket_id)?;
+
+    if let Some(msg) = check_character_name(&name) {
+        return send_infomsg(storage, socket_id.id, msg, 0);
+    }
+
+    if sprite_id >= 6 {
+        return Err(AscendingError::InvalidSocket);
+    }
+
+    let characters = sql_load_characters(storage, account_id)?;
+    let slot = match (0..MAX_CHARACTERS as i16)
+        .find(|slot| !characters.iter().any(|character| character.slot == *slot))
+    {
+        Some(slot) => slot,
+        None => {
+            return send_infomsg(
+                storage,
+                socket_id.id,
+                "No Character slots are left.".into(),
+                0,
+            );
+        }
+    };
+
+    if sql_character_name_taken(storage, account_id, &name)? {
+        return send_infomsg(
+            storage,
+            socket_id.id,
+            "Name Exists. Please try Another.".into(),
+            0,
+        );
+    }
+
+    if new_character(storage, account_id, slot, &name, sprite_id as u16).is_err() {
+        return send_infomsg(
+            storage,
+            socket_id.id,
+            "There was an Issue Creating the Character. Please Contact Support.".into(),
+            0,
+        );
+    }
+
+    info!("Character {} created on slot {}.", name, slot);
+
+    send_characters(storage, socket_id.id, account_id)
+}
+
+pub fn handle_deletecharacter(
+    _world: &mut World,
+    storage: &Storage,
+    data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    socket_id: SocketID,
+) -> Result<()> {
+    let slot = data.read::<u8>()?;
+    let name = data.read::<String>()?;
+
+    if entity.is_some() {
+        return Err(AscendingError::InvalidSocket);
+    }
+
+    if database_unavailable(storage, socket_id.id)? {
+        return Ok(());
+    }
+
+    let account_id = char_select_account(storage, &socket_id)?;
+
+    let character = match sql_load_character(storage, account_id, slot as i16)? {
+        Some(character) => character,
+        None => {
+            return send_infomsg(storage, socket_id.id, "Character does not Exist.".into(), 0);
+        }
+    };
+
+    // The name must be typed in to confirm the deletion.
+    if character.name != name {
+        return send_infomsg(
+            storage,
+            socket_id.id,
+            "Character name does not match.".into(),
+            0,
+        );
+    }
+
+    sql_soft_delete_character(storage, character.cid)?;
+
+    info!("Character {} deleted from slot {}.", character.name, slot);
+
+    send_characters(storage, socket_id.id, account_id)
+}
+
+pub fn handle_restorecharacter(
+    _world: &mut World,
+    storage: &Storage,
+    data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    socket_id: SocketID,
+) -> Result<()> {
+    let slot = data.read::<u8>()?;
+
+    if entity.is_some() {
+        return Err(AscendingError::InvalidSocket);
+    }
+
+    if database_unavailable(storage, socket_id.id)? {
+        return Ok(());
+    }
+
+    let account_id = char_select_account(storage, &socket_id)?;
+
+    if !sql_restore_character(
+        storage,
+        account_id,
+        slot as i16,
+        storage.config.character_restore_days,
+    )? {
+        return send_infomsg(
+            storage,
+            socket_id.id,
+            "Character can no longer be restored.".into(),
+            0,
+        );
+    }
+
+    info!("Character restored on slot {}.", slot);
+
+    send_characters(storage, socket_id.id, account_id)
+}
+
+pub fn handle_renamecharacter(
+    world: &mut World,
+    storage: &Storage,
+    data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    _socket_id: SocketID,
+) -> Result<()> {
+    let entity = match entity {
+        Some(e) => e,
+        None => return Err(AscendingError::InvalidSocket),
+    };
+
+    let name = data.read::<String>()?;
+
+    let (account_id, character_id, old_name, position) =
+        if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+            let p_data = p_data.try_lock()?;
+
+            (
+                p_data.account.id,
+                p_data.character.id,
+                p_data.character.name.clone(),
+                p_data.movement.pos,
+            )
+        } else {
+            return Ok(());
+        };
+
+    let msg = if let Some(msg) = check_character_name(&name) {
+        Some(msg)
+    } else if sql_character_name_taken(storage, account_id, &name)? {
+        Some("Name Exists. Please try Another.".into())
+    } else if !sql_rename_character(
+        storage,
+        character_id,
+        &name,
+        storage.config.rename_cooldown_days,
+    )? {
+        Some(format!(
+            "You can only rename once every {} days.",
+            storage.config.rename_cooldown_days
+        ))
+    } else {
+        None
+    };
+
+    if let Some(msg) = msg {
+        return send_message(
+            world,
+            storage,
+            entity,
+            msg,
+            String::new(),
+            MessageChannel::Private,
+            None,
+        );
+    }
+
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        p_data.try_lock()?.character.name.clone_from(&name);
+    }
+
+    {
+        let mut player_names = storage.player_names.borrow_mut();
+        let _ = player_names.remove(&old_name);
+        player_names.insert(name.clone(), entity);
+    }
+
+    info!("Character {} renamed to {}.", old_name, name);
+
+    DataTaskToken::PlayerSpawn(position.map)
+        .add_task(storage, player_spawn_packet(world, entity, false)?)?;
+
+    send_message(
+        world,
+        storage,
+        entity,
+        format!("You are now known as {}.", name),
+        String::new(),
+        MessageChannel::Private,
+        None,
+    )
+}
@@ -7,7 +7,7 @@ use rand::distr::{Alphanumeric, SampleString};
 use crate::{
     containers::{
         Entity, GlobalKey, IsUsingType, PlayerConnectionTimer, Socket, Storage, TradeRequestEntity,
-        World,
+        UserAccess, World,
     },
     gametypes::*,
     items::Item,
@@ -20,6 +20,7 @@ use crate::{
         MByteBufferExt, send_clear_data, send_clearisusingtype, send_fltalert, send_gameping,
         send_message, send_traderequest,
     },
+    sql::{ItemMove, record_inv_loss},
     time_ext::MyInstant,
 };
 
@@ -56,7 +57,7 @@ pub fn handle_message(
     let (socket_id, p_name) = if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let p_data = p_data.try_lock()?;
 
-        (p_data.socket.id, p_data.account.username.clone())
+        (p_data.socket.id, p_data.character.name.clone())
     } else {
         return Ok(());
     };
@@ -132,6 +133,20 @@ pub fn handle_command(
 
     match command {
         Command::KickPlayer => {}
+        Command::ReloadTls => {
+            let is_admin = match world.get_opt_entity(entity) {
+                Some(Entity::Player(data)) => data.try_lock()?.user_access == UserAccess::Admin,
+                _ => false,
+            };
+
+            if !is_admin {
+                return Err(AscendingError::PacketManipulation {
+                    name: "ReloadTls without Admin access".into(),
+                });
+            }
+
+            storage.server.borrow_mut().reload_tls(&storage.config)?;
+        }
         Command::KickPlayerByName(name) => {
             debug!("Kicking Player {:?}", name);
         }
@@ -316,7 +331,7 @@ pub fn handle_closetrade(
             }
 
             if let IsUsingType::Trading(entity) = p1_data.is_using_type {
-                (entity, p1_data.account.username.clone())
+                (entity, p1_data.character.name.clone())
             } else {
                 return Ok(());
             }
@@ -395,14 +410,22 @@ pub fn handle_buyitem(
             );
         }
 
+        let num = shopdata.item[slot as usize].index as u32;
         let mut item = Item {
-            num: shopdata.item[slot as usize].index as u32,
+            num,
             val: shopdata.item[slot as usize].amount,
             ..Default::default()
-        };
+        }
+        .with_instance(&storage.bases.items[num as usize]);
 
         if check_inv_space(world, storage, entity, &mut item)? {
-            give_inv_item(world, storage, entity, &mut item)?;
+            give_inv_item(
+                world,
+                storage,
+                entity,
+                &mut item,
+                ItemMove::new(ItemReason::ShopBuy, ItemPlace::Shop),
+            )?;
             player_take_vals(world, storage, entity, shopdata.item[slot as usize].price)?;
         } else {
             return send_message(
@@ -477,6 +500,14 @@ pub fn handle_sellitem(
 
         let total_price = price * amount as u64;
         take_inv_itemslot(world, storage, entity, slot, amount)?;
+        record_inv_loss(
+            storage,
+            world,
+            entity,
+            &inv_item,
+            amount,
+            ItemMove::new(ItemReason::ShopSell, ItemPlace::Shop),
+        )?;
         player_give_vals(world, storage, entity, total_price)?;
 
         send_message(
@@ -651,7 +682,7 @@ pub fn handle_reconnect(
 
             reconnect_player(world, storage, old_entity, socket)?;
 
-            let name = { p_data.try_lock()?.account.username.clone() };
+            let name = { p_data.try_lock()?.character.name.clone() };
 
             info!(
                 "Player {} with IP: {}, Reconnecting on handle_reconnect .",
@@ -9,10 +9,13 @@ use crate::{
     maps::{DropItem, get_maps_in_range, try_drop_item},
     players::{
         check_inv_partial_space, check_storage_partial_space, give_inv_item, give_storage_item,
-        player_give_vals, player_unequip, player_use_item, save_inv_item, save_storage_item,
-        set_inv_slot, set_storage_slot, take_inv_itemslot, take_storage_itemslot,
+        item_slot_room, player_buy_storage_tab, player_deposit_vals, player_give_vals,
+        player_unequip, player_use_item, player_withdraw_vals, save_inv_item, save_stash_item,
+        save_storage_item, set_inv_slot, set_storage_slot, storage_tab_price, take_inv_itemslot,
+        take_stash_itemslot, take_storage_itemslot,
     },
     socket::{send_fltalert, send_message},
+    sql::{ItemMove, PGItemEvent, record_inv_gain, record_inv_loss, record_item_event},
     tasks::{DataTaskToken, unload_entity_packet},
 };
 
@@ -286,7 +289,13 @@ pub fn handle_pickup(
 
                             //if passed then we only get partial of the map item.
                             if is_less {
-                                give_inv_item(world, storage, entity, &mut mapitems.item)?;
+                                give_inv_item(
+                                    world,
+                                    storage,
+                                    entity,
+                                    &mut mapitems.item,
+                                    ItemMove::new(ItemReason::Pickup, ItemPlace::Map),
+                                )?;
 
                                 let st = match amount {
                                     0 | 1 => "",
@@ -380,7 +389,7 @@ pub fn handle_dropitem(
     let slot = data.read::<u16>()? as usize;
     let mut amount = data.read::<u16>()?;
 
-    let (pos, item_data, user_access) =
+    let (pos, item_data, user_access, cid) =
         if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
             let p_data = p_data.try_lock()?;
 
@@ -407,6 +416,7 @@ pub fn handle_dropitem(
                 p_data.movement.pos,
                 p_data.inventory.items[slot],
                 p_data.user_access,
+                p_data.character.id,
             )
         } else {
             return Ok(());
@@ -419,6 +429,7 @@ pub fn handle_dropitem(
             index: item_data.num,
             amount,
             pos,
+            instance: item_data.instance,
         },
         match user_access {
             UserAccess::Admin => None,
@@ -428,6 +439,7 @@ pub fn handle_dropitem(
         },
         Some(*storage.gettick.borrow() + Duration::try_milliseconds(5000).unwrap_or_default()),
         Some(entity),
+        ItemMove::new(ItemReason::Drop, ItemPlace::Inventory).owned_by(cid),
     )? {
         take_inv_itemslot(world, storage, entity, slot, amount)?;
     }
@@ -450,7 +462,7 @@ pub fn handle_deleteitem(
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let slot = data.read::<u16>()? as usize;
 
-        let val = {
+        let item = {
             let p_data = p_data.try_lock()?;
 
             if !p_data.combat.death_type.is_alive()
@@ -465,10 +477,18 @@ pub fn handle_deleteitem(
                 return Ok(());
             }
 
-            p_data.inventory.items[slot].val
+            p_data.inventory.items[slot]
         };
 
-        take_inv_itemslot(world, storage, entity, slot, val)?;
+        take_inv_itemslot(world, storage, entity, slot, item.val)?;
+        record_inv_loss(
+            storage,
+            world,
+            entity,
+            &item,
+            item.val,
+            ItemMove::new(ItemReason::Delete, ItemPlace::Void),
+        )?;
     }
     Ok(())
 }
@@ -504,10 +524,17 @@ pub fn handle_switchstorageslot(
 
         let (mut old_slot, new_slot) = {
             let p_data = p_data.try_lock()?;
-            (p_data.storage.items[oldslot], p_data.storage.items[newslot])
+
+            match (
+                p_data.storage.items.get(oldslot),
+                p_data.storage.items.get(newslot),
+            ) {
+                (Some(old_slot), Some(new_slot)) => (*old_slot, *new_slot),
+                _ => return Ok(()),
+            }
         };
 
-        if newslot >= MAX_STORAGE || old_slot.val == 0 {
+        if old_slot.val == 0 {
             return Ok(());
         }
 
@@ -575,7 +602,7 @@ pub fn handle_deletestorageitem(
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let slot = data.read::<u16>()? as usize;
 
-        let val = {
+        let (item, cid, pos) = {
             let p_data = p_data.try_lock()?;
 
             if !p_data.combat.death_type.is_alive()
@@ -586,14 +613,25 @@ pub fn handle_deletestorageitem(
                 return Ok(());
             }
 
-            if slot >= MAX_STORAGE || p_data.storage.items[slot].val == 0 {
+            if slot >= p_data.storage.items.len() || p_data.storage.items[slot].val == 0 {
                 return Ok(());
             }
 
-            p_data.storage.items[slot].val
+            (
+                p_data.storage.items[slot],
+                p_data.character.id,
+                p_data.movement.pos,
+            )
         };
 
-        take_storage_itemslot(world, storage, entity, slot, val)?;
+        take_storage_itemslot(world, storage, entity, slot, item.val)?;
+        record_item_event(
+            storage,
+            cid,
+            PGItemEvent::new(ItemReason::Delete, &item, item.val)
+                .from(ItemPlace::Storage, Some(cid))
+                .at(pos),
+        )?;
     }
     Ok(())
 }
@@ -615,7 +653,7 @@ pub fn handle_deposititem(
         let bank_slot = data.read::<u16>()? as usize;
         let amount = data.read::<u16>()?;
 
-        let (mut item_data, storage_data) = {
+        let (mut item_data, storage_data, cid) = {
             let p_data = p_data.try_lock()?;
 
             if !p_data.combat.death_type.is_alive()
@@ -626,9 +664,9 @@ pub fn handle_deposititem(
                 return Ok(());
             }
 
-            if bank_slot >= MAX_STORAGE
-                || p_data.inventory.items[inv_slot].val == 0
+            if bank_slot >= p_data.storage.items.len()
                 || inv_slot >= MAX_INV
+                || p_data.inventory.items[inv_slot].val == 0
             {
                 return Ok(());
             }
@@ -639,22 +677,32 @@ pub fn handle_deposititem(
                 item_data.val = amount;
             }
 
-            (item_data, p_data.storage.items[bank_slot])
+            (
+                item_data,
+                p_data.storage.items[bank_slot],
+                p_data.character.id,
+            )
         };
 
+        let deposit = ItemMove::new(ItemReason::Deposit, ItemPlace::Storage).owned_by(cid);
+
         if storage_data.val == 0 {
             {
                 p_data.try_lock()?.storage.items[bank_slot] = item_data;
             }
             save_storage_item(world, storage, entity, bank_slot)?;
             take_inv_itemslot(world, storage, entity, inv_slot, amount)?;
+            record_inv_loss(storage, world, entity, &item_data, item_data.val, deposit)?;
         } else {
             let (is_less, amount, _started) =
                 check_storage_partial_space(world, storage, entity, &mut item_data)?;
 
             if is_less {
+                let deposited = item_data;
+
                 give_storage_item(world, storage, entity, &mut item_data)?;
                 take_inv_itemslot(world, storage, entity, inv_slot, amount)?;
+                record_inv_loss(storage, world, entity, &deposited, amount, deposit)?;
             } else {
                 send_message(
                     world,
@@ -689,7 +737,7 @@ pub fn handle_withdrawitem(
         let bank_slot = data.read::<u16>()? as usize;
         let amount = data.read::<u16>()?;
 
-        let (mut item_data, inv_data) = {
+        let (mut item_data, inv_data, owner) = {
             let p_data = p_data.try_lock()?;
 
             if !p_data.combat.death_type.is_alive()
@@ -700,7 +748,7 @@ pub fn handle_withdrawitem(
                 return Ok(());
             }
 
-            if bank_slot >= MAX_STORAGE
+            if bank_slot >= p_data.storage.items.len()
                 || p_data.storage.items[bank_slot].val == 0
                 || inv_slot >= MAX_INV
             {
@@ -713,21 +761,28 @@ pub fn handle_withdrawitem(
                 item_data.val = amount;
             }
 
-            (item_data, p_data.inventory.items[inv_slot])
+            (
+                item_data,
+                p_data.inventory.items[inv_slot],
+                p_data.character.id,
+            )
         };
 
+        let withdraw = ItemMove::new(ItemReason::Withdraw, ItemPlace::Storage).owned_by(owner);
+
         if inv_data.val == 0 {
             {
                 p_data.try_lock()?.inventory.items[inv_slot] = item_data;
             }
             save_inv_item(world, storage, entity, inv_slot)?;
             take_storage_itemslot(world, storage, entity, bank_slot, amount)?;
+            record_inv_gain(storage, world, entity, &item_data, item_data.val, withdraw)?;
         } else {
             let (is_less, amount, _started) =
                 check_inv_partial_space(world, storage, entity, &mut item_data)?;
 
             if is_less {
-                give_inv_item(world, storage, entity, &mut item_data)?;
+                give_inv_item(world, storage, entity, &mut item_data, withdraw)?;
                 take_storage_itemslot(world, storage, entity, bank_slot, amount)?;
             } else {
                 send_message(
@@ -744,3 +799,315 @@ pub fn handle_withdrawitem(
     }
     Ok(())
 }
+
+pub fn handle_depositstashitem(
+    world: &mut World,
+    storage: &Storage,
+    data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    _socket_id: SocketID,
+) -> Result<()> {
+    let entity = match entity {
+        Some(e) => e,
+        None => return Err(AscendingError::InvalidSocket),
+    };
+
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let inv_slot = data.read::<u16>()? as usize;
+        let stash_slot = data.read::<u16>()? as usize;
+        let amount = data.read::<u16>()?;
+
+        let (mut item_data, stash_data, account_id) = {
+            let p_data = p_data.try_lock()?;
+
+            if !p_data.combat.death_type.is_alive()
+                || !p_data.is_using_type.is_bank()
+                || p_data.combat.attacking
+                || p_data.combat.stunned
+            {
+                return Ok(());
+            }
+
+            if stash_slot >= p_data.stash.items.len()
+                || inv_slot >= MAX_INV
+                || p_data.inventory.items[inv_slot].val == 0
+            {
+                return Ok(());
+            }
+
+            let mut item_data = p_data.inventory.items[inv_slot];
+
+            if item_data.val > amount {
+                item_data.val = amount;
+            }
+
+            (item_data, p_data.stash.items[stash_slot], p_data.account.id)
+        };
+
+        let amount = item_slot_room(storage, &stash_data, &item_data);
+
+        if amount == 0 {
+            return send_message(
+                world,
+                storage,
+                entity,
+                "You can not deposit this item into that stash slot!".into(),
+                String::new(),
+                MessageChannel::Private,
+                None,
+            );
+        }
+
+        {
+            let mut p_data = p_data.try_lock()?;
+
+            if stash_data.val == 0 {
+                item_data.val = amount;
+                p_data.stash.items[stash_slot] = item_data;
+            } else {
+                p_data.stash.items[stash_slot].val += amount;
+            }
+        }
+
+        save_stash_item(world, storage, entity, stash_slot)?;
+        take_inv_itemslot(world, storage, entity, inv_slot, amount)?;
+        record_inv_loss(
+            storage,
+            world,
+            entity,
+            &item_data,
+            amount,
+            ItemMove::new(ItemReason::StashDeposit, ItemPlace::Stash).owned_by(account_id),
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn handle_withdrawstashitem(
+    world: &mut World,
+    storage: &Storage,
+    data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    _socket_id: SocketID,
+) -> Result<()> {
+    let entity = match entity {
+        Some(e) => e,
+        None => return Err(AscendingError::InvalidSocket),
+    };
+
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let inv_slot = data.read::<u16>()? as usize;
+        let stash_slot = data.read::<u16>()? as usize;
+        let amount = data.read::<u16>()?;
+
+        let (mut item_data, inv_data, owner) = {
+            let p_data = p_data.try_lock()?;
+
+            if !p_data.combat.death_type.is_alive()
+                || !p_data.is_using_type.is_bank()
+                || p_data.combat.attacking
+                || p_data.combat.stunned
+            {
+                return Ok(());
+            }
+
+            if stash_slot >= p_data.stash.items.len()
+                || p_data.stash.items[stash_slot].val == 0
+                || inv_slot >= MAX_INV
+            {
+                return Ok(());
+            }
+
+            let mut item_data = p_data.stash.items[stash_slot];
+
+            if item_data.val > amount {
+                item_data.val = amount;
+            }
+
+            (
+                item_data,
+                p_data.inventory.items[inv_slot],
+                p_data.account.id,
+            )
+        };
+
+        let amount = item_slot_room(storage, &inv_data, &item_data);
+
+        if amount == 0 {
+            return send_message(
+                world,
+                storage,
+                entity,
+                "You can not withdraw this item into that inventory slot!".into(),
+                String::new(),
+                MessageChannel::Private,
+                None,
+            );
+        }
+
+        {
+            let mut p_data = p_data.try_lock()?;
+
+            if inv_data.val == 0 {
+                item_data.val = amount;
+                p_data.inventory.items[inv_slot] = item_data;
+            } else {
+                p_data.inventory.items[inv_slot].val += amount;
+            }
+        }
+
+        save_inv_item(world, storage, entity, inv_slot)?;
+        take_stash_itemslot(world, storage, entity, stash_slot, amount)?;
+        record_inv_gain(
+            storage,
+            world,
+            entity,
+            &item_data,
+            amount,
+            ItemMove::new(ItemReason::StashWithdraw, ItemPlace::Stash).owned_by(owner),
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn handle_depositmoney(
+    world: &mut World,
+    storage: &Storage,
+    data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    _socket_id: SocketID,
+) -> Result<()> {
+    let entity = match entity {
+        Some(e) => e,
+        None => return Err(AscendingError::InvalidSocket),
+    };
+
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let amount = data.read::<u64>()?;
+
+        {
+            let p_data = p_data.try_lock()?;
+
+            if !p_data.combat.death_type.is_alive()
+                || !p_data.is_using_type.is_bank()
+                || p_data.combat.attacking
+                || p_data.combat.stunned
+                || amount == 0
+            {
+                return Ok(());
+            }
+        }
+
+        if player_deposit_vals(world, storage, entity, amount)? == 0 {
+            send_message(
+                world,
+                storage,
+                entity,
+                "You do not have any vals to deposit!".into(),
+                String::new(),
+                MessageChannel::Private,
+                None,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_withdrawmoney(
+    world: &mut World,
+    storage: &Storage,
+    data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    _socket_id: SocketID,
+) -> Result<()> {
+    let entity = match entity {
+        Some(e) => e,
+        None => return Err(AscendingError::InvalidSocket),
+    };
+
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let amount = data.read::<u64>()?;
+
+        {
+            let p_data = p_data.try_lock()?;
+
+            if !p_data.combat.death_type.is_alive()
+                || !p_data.is_using_type.is_bank()
+                || p_data.combat.attacking
+                || p_data.combat.stunned
+                || amount == 0
+            {
+                return Ok(());
+            }
+        }
+
+        if player_withdraw_vals(world, storage, entity, amount)? == 0 {
+            send_message(
+                world,
+                storage,
+                entity,
+                "You do not have any vals to withdraw!".into(),
+                String::new(),
+                MessageChannel::Private,
+                None,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn handle_buystoragetab(
+    world: &mut World,
+    storage: &Storage,
+    _data: &mut MByteBuffer,
+    entity: Option<GlobalKey>,
+    _socket_id: SocketID,
+) -> Result<()> {
+    let entity = match entity {
+        Some(e) => e,
+        None => return Err(AscendingError::InvalidSocket),
+    };
+
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let tabs = {
+            let p_data = p_data.try_lock()?;
+
+            if !p_data.combat.death_type.is_alive()
+                || !p_data.is_using_type.is_bank()
+                || p_data.combat.attacking
+                || p_data.combat.stunned
+            {
+                return Ok(());
+            }
+
+            p_data.storage.tabs()
+        };
+
+        let msg = if tabs >= MAX_STORAGE_TABS {
+            "Your storage can not be expanded any further!".to_string()
+        } else if player_buy_storage_tab(world, storage, entity)? {
+            "You have unlocked a new storage tab!".to_string()
+        } else {
+            format!(
+                "You need {} vals to unlock a new storage tab!",
+                storage_tab_price(storage, tabs)
+            )
+        };
+
+        send_message(
+            world,
+            storage,
+            entity,
+            msg,
+            String::new(),
+            MessageChannel::Private,
+            None,
+        )?;
+    }
+
+    Ok(())
+}
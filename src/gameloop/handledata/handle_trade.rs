@@ -60,6 +60,20 @@ pub fn handle_addtradeitem(
                 amount = base.stacklimit as u64
             }
 
+            // An item instance can only be offered once and only as much as its slot holds.
+            if let Some(instance) = inv_item.instance {
+                if p1_data
+                    .trade_item
+                    .items
+                    .iter()
+                    .any(|item| item.val > 0 && item.instance == Some(instance))
+                {
+                    return Ok(());
+                }
+
+                amount = amount.min(inv_item.val as u64);
+            }
+
             // Make sure it does not exceed the amount player have
             let inv_count = count_inv_item(inv_item.num, &p1_data.inventory.items);
             let trade_count = count_trade_item(inv_item.num, &p1_data.trade_item.items);
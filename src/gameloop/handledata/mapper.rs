@@ -104,6 +104,54 @@ impl PacketRouter {
             ),
             (ClientPacket::Reconnect, handle_reconnect as PacketFunction),
             (ClientPacket::LoginOk, handle_login_ok as PacketFunction),
+            (
+                ClientPacket::DepositMoney,
+                handle_depositmoney as PacketFunction,
+            ),
+            (
+                ClientPacket::WithdrawMoney,
+                handle_withdrawmoney as PacketFunction,
+            ),
+            (
+                ClientPacket::BuyStorageTab,
+                handle_buystoragetab as PacketFunction,
+            ),
+            (
+                ClientPacket::SelectCharacter,
+                handle_selectcharacter as PacketFunction,
+            ),
+            (
+                ClientPacket::CreateCharacter,
+                handle_createcharacter as PacketFunction,
+            ),
+            (
+                ClientPacket::DeleteCharacter,
+                handle_deletecharacter as PacketFunction,
+            ),
+            (
+                ClientPacket::DepositStashItem,
+                handle_depositstashitem as PacketFunction,
+            ),
+            (
+                ClientPacket::WithdrawStashItem,
+                handle_withdrawstashitem as PacketFunction,
+            ),
+            (
+                ClientPacket::RestoreCharacter,
+                handle_restorecharacter as PacketFunction,
+            ),
+            (
+                ClientPacket::RenameCharacter,
+                handle_renamecharacter as PacketFunction,
+            ),
+            (
+                ClientPacket::VerifyEmail,
+                handle_verifyemail as PacketFunction,
+            ),
+            (
+                ClientPacket::ProtocolVersion,
+                handle_protocolversion as PacketFunction,
+            ),
         ]))
     }
 }
@@ -1,10 +1,13 @@
+use log::warn;
 use mio::Token;
+use uuid::Uuid;
 
 use crate::{
     AscendingError, PacketRouter,
     containers::{GlobalKey, Storage, World},
-    gametypes::Result,
+    gametypes::{LogType, Result},
     socket::*,
+    sql::{PGLog, sql_new_log},
 };
 
 pub struct SocketID {
@@ -24,7 +27,15 @@ pub fn handle_data(
 
     if entity.is_some() {
         match id {
-            ClientPacket::Login | ClientPacket::Register | ClientPacket::HandShake => {
+            ClientPacket::Login
+            | ClientPacket::Register
+            | ClientPacket::HandShake
+            | ClientPacket::SelectCharacter
+            | ClientPacket::CreateCharacter
+            | ClientPacket::DeleteCharacter
+            | ClientPacket::RestoreCharacter
+            | ClientPacket::VerifyEmail
+            | ClientPacket::ProtocolVersion => {
                 return Err(AscendingError::MultiLogin);
             }
             _ => {}
@@ -37,15 +48,82 @@ pub fn handle_data(
             | ClientPacket::HandShake
             | ClientPacket::Ping
             | ClientPacket::TlsHandShake
-            | ClientPacket::TlsReconnect => {}
+            | ClientPacket::TlsReconnect
+            | ClientPacket::SelectCharacter
+            | ClientPacket::CreateCharacter
+            | ClientPacket::DeleteCharacter
+            | ClientPacket::RestoreCharacter
+            | ClientPacket::VerifyEmail
+            | ClientPacket::ProtocolVersion => {}
             _ => return Err(AscendingError::PacketManipulation { name: "".into() }),
         }
+
+        // The protocol version must be agreed on before anything account related is handled.
+        if matches!(
+            id,
+            ClientPacket::Login | ClientPacket::Register | ClientPacket::VerifyEmail
+        ) {
+            let negotiated = storage
+                .server
+                .borrow()
+                .clients
+                .get(&socket_id.id)
+                .is_some_and(|client| client.borrow().protocol_version != 0);
+
+            if !negotiated {
+                return send_infomsg(
+                    storage,
+                    socket_id.id,
+                    "Client needs to be updated.".into(),
+                    1,
+                );
+            }
+        }
     }
 
     if id == ClientPacket::OnlineCheck {
         return Ok(());
     }
 
+    let tick = *storage.gettick.borrow();
+    let (limit, addr) = match storage.server.borrow().clients.get(&socket_id.id) {
+        Some(client) => {
+            let mut client = client.borrow_mut();
+            // Trusted tools and bots are allowed to send as fast as they like.
+            let limit = if client.trusted {
+                RateLimitResult::Allow
+            } else {
+                client.rate_limiter.check(&storage.config, id, tick)
+            };
+
+            (limit, client.addr.clone())
+        }
+        None => return Err(AscendingError::InvalidSocket),
+    };
+
+    match limit {
+        RateLimitResult::Allow => {}
+        RateLimitResult::Drop => return Ok(()),
+        RateLimitResult::Disconnect => {
+            warn!("IP: {} is flooding {:?} packets.", addr, id);
+
+            sql_new_log(
+                storage,
+                &PGLog::new(
+                    storage.config.server_id,
+                    Uuid::nil(),
+                    LogType::Warning,
+                    format!("Packet flooding with {:?}", id),
+                    addr.to_string(),
+                ),
+            )?;
+
+            return Err(AscendingError::PacketManipulation {
+                name: format!("{:?} flood", id),
+            });
+        }
+    }
+
     let fun = match router.0.get(&id) {
         Some(fun) => fun,
         None => {
@@ -1,16 +1,26 @@
 use crate::{
     PacketRouter,
-    containers::{Storage, World},
+    containers::{Entity, Storage, World, save_world_snapshot},
+    gametypes::{AscendingError, SnapshotReason},
     maps::{update_map_items, update_maps},
     npcs::*,
     players::*,
     socket::*,
+    sql::{SaveCommand, save_player, snapshot_player, sql_purge_deleted_characters},
     tasks::{process_data_lists, process_tasks},
     time_ext::MyInstant,
 };
 use chrono::Duration;
+use log::{error, info};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-pub fn game_loop(world: &mut World, storage: &Storage, router: &PacketRouter) {
+/// Runs until `shutdown` is set, then saves everyone still online and the world snapshot.
+pub fn game_loop(
+    world: &mut World,
+    storage: &Storage,
+    router: &PacketRouter,
+    shutdown: &AtomicBool,
+) {
     let mut tick: MyInstant;
     let mut tmr100: MyInstant = MyInstant::now();
     let mut tmr150: MyInstant = MyInstant::now();
@@ -18,13 +28,19 @@ pub fn game_loop(world: &mut World, storage: &Storage, router: &PacketRouter) {
     let mut tmr1000: MyInstant = MyInstant::now();
     let mut tmr60000: MyInstant = MyInstant::now();
     let mut ping_timer: MyInstant = MyInstant::now();
+    let mut purge_timer: MyInstant = MyInstant::now();
+    let mut metrics_timer: MyInstant = MyInstant::now();
+    let mut snapshot_timer: MyInstant = MyInstant::now()
+        + Duration::try_seconds(storage.config.world_snapshot_secs as i64).unwrap_or_default();
+    let mut character_snapshot_timer: MyInstant = MyInstant::now()
+        + Duration::try_seconds(storage.config.character_snapshot_secs as i64).unwrap_or_default();
 
     let mut entity_progress = 0u64;
     let mut npc_progress = 0u64;
     let mut npc_batch = 0usize;
     let mut max_batch = (storage.npc_ids.borrow().len() as f32 / 5.0).ceil() as usize;
 
-    loop {
+    while !shutdown.load(Ordering::Relaxed) {
         let _ = storage.gettick.replace(MyInstant::now());
         tick = *storage.gettick.borrow();
 
@@ -81,6 +97,12 @@ pub fn game_loop(world: &mut World, storage: &Storage, router: &PacketRouter) {
         }
 
         if tick > tmr1000 {
+            if let Err(e) = close_login_timeouts(world, storage) {
+                error!("close_login_timeouts error: {}", e);
+            }
+            if let Err(e) = close_slow_clients(world, storage) {
+                error!("close_slow_clients error: {}", e);
+            }
             tmr1000 = tick + Duration::try_milliseconds(1000).unwrap_or_default();
         }
 
@@ -94,6 +116,15 @@ pub fn game_loop(world: &mut World, storage: &Storage, router: &PacketRouter) {
                     time.hour = 0;
                 }
             }
+            storage
+                .login_guard
+                .borrow_mut()
+                .clear_expired(&storage.config, tick);
+            storage.server.borrow_mut().clear_ip_rates(storage, tick);
+
+            if storage.config.tls_watch {
+                storage.server.borrow_mut().check_tls_files(&storage.config);
+            }
             tmr60000 = tick + Duration::try_milliseconds(60000).unwrap_or_default();
         }
 
@@ -103,10 +134,94 @@ pub fn game_loop(world: &mut World, storage: &Storage, router: &PacketRouter) {
             ping_timer = tick + Duration::try_hours(2).unwrap_or_default();
         }
 
+        //Hard deletes characters once their restore window has passed.
+        if tick > purge_timer && !storage.persistence.degraded() {
+            match sql_purge_deleted_characters(storage, storage.config.character_restore_days) {
+                Ok(0) => {}
+                Ok(count) => info!("Purged {} deleted characters.", count),
+                Err(e) => error!("Failed to purge deleted characters: {}", e),
+            }
+            if let Err(e) = storage.persistence.save(SaveCommand::PruneSnapshots {
+                keep_days: storage.config.character_snapshot_keep_days,
+                keep_count: storage.config.character_snapshot_keep_count,
+            }) {
+                error!("Failed to queue character snapshot pruning: {}", e);
+            }
+            purge_timer = tick + Duration::try_hours(1).unwrap_or_default();
+        }
+
+        if tick > metrics_timer {
+            storage.compression_metrics.borrow().report();
+            storage.persistence.report();
+            metrics_timer = tick + Duration::try_minutes(10).unwrap_or_default();
+        }
+
+        if storage.config.world_snapshot && tick > snapshot_timer {
+            if let Err(e) = save_world_snapshot(world, storage, false) {
+                error!("Failed to save the world snapshot: {}", e);
+            }
+            snapshot_timer = tick
+                + Duration::try_seconds(storage.config.world_snapshot_secs as i64)
+                    .unwrap_or_default();
+        }
+
+        //Snapshots are skipped while the database is down so they do not fill the save queue.
+        if storage.config.character_snapshot_secs > 0
+            && tick > character_snapshot_timer
+            && !storage.persistence.degraded()
+        {
+            for id in storage.player_ids.borrow().iter() {
+                if let Some(Entity::Player(player)) = world.get_opt_entity(*id)
+                    && let Err(e) = player
+                        .try_lock()
+                        .map_err(AscendingError::from)
+                        .and_then(|player| snapshot_player(storage, &player, SnapshotReason::Timer))
+                {
+                    error!("Failed to snapshot a player: {}", e);
+                }
+            }
+            character_snapshot_timer = tick
+                + Duration::try_seconds(storage.config.character_snapshot_secs as i64)
+                    .unwrap_or_default();
+        }
+
         poll_events(world, storage).unwrap();
         process_packets(world, storage, router).unwrap();
         process_data_lists(world, storage).unwrap();
         process_tasks(world, storage).unwrap();
         std::thread::sleep(std::time::Duration::from_millis(1));
     }
+
+    info!(
+        "Saving {} players before shutting down.",
+        storage.player_ids.borrow().len()
+    );
+
+    for id in storage.player_ids.borrow().iter() {
+        if let Some(Entity::Player(player)) = world.get_opt_entity(*id) {
+            let cid = player.try_lock().map(|player| player.character.id).ok();
+
+            if let Err(e) = player
+                .try_lock()
+                .map_err(AscendingError::from)
+                .and_then(|player| snapshot_player(storage, &player, SnapshotReason::Logout))
+            {
+                error!("Failed to snapshot player on shutdown: {}", e);
+            }
+
+            if let Err(e) = save_player(storage, player) {
+                error!("Failed to save player on shutdown: {}", e);
+            } else if let Some(cid) = cid
+                && let Err(e) = storage.persistence.save(SaveCommand::Offline { cid })
+            {
+                error!("Failed to mark player offline on shutdown: {}", e);
+            }
+        }
+    }
+
+    if storage.config.world_snapshot
+        && let Err(e) = save_world_snapshot(world, storage, true)
+    {
+        error!("Failed to save the world snapshot on shutdown: {}", e);
+    }
 }
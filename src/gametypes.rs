@@ -16,9 +16,12 @@ pub const EQUIPMENT_TYPE_MAX: usize = EquipmentType::Count as usize;
 pub const VITALS_MAX: usize = VitalTypes::Count as usize;
 
 pub const MAXCONNECTIONS: usize = 500;
-pub const APP_MAJOR: usize = 1;
-pub const APP_MINOR: usize = 1;
-pub const APP_REVISION: usize = 1;
+/// Protocol version the server speaks. Raise this when packets change.
+/// 2: ProtocolVersion negotiates packet compression.
+pub const PROTOCOL_VERSION: u16 = 2;
+/// Client protocol versions the server accepts. Keep the last version in here
+/// while clients are being rolled over so both can connect.
+pub const COMPATIBLE_PROTOCOLS: &[u16] = &[1, PROTOCOL_VERSION];
 
 ///Map Data Maxs
 pub const MAX_MAPS: usize = 3000;
@@ -39,7 +42,13 @@ pub const NPCS_SPAWNCAP: usize = 10;
 pub const MAX_LVL: usize = 200;
 pub const MAX_INV: usize = 30;
 pub const MAX_TRADE_SLOT: usize = 30;
-pub const MAX_STORAGE: usize = 70;
+pub const STORAGE_TAB_SIZE: usize = 35;
+pub const BASE_STORAGE_TABS: usize = 2;
+pub const MAX_STORAGE_TABS: usize = 8;
+pub const BASE_STORAGE: usize = STORAGE_TAB_SIZE * BASE_STORAGE_TABS;
+pub const MAX_STORAGE: usize = STORAGE_TAB_SIZE * MAX_STORAGE_TABS;
+pub const MAX_STASH: usize = 35;
+pub const MAX_CHARACTERS: usize = 3;
 pub const MAX_EQPT: usize = 5;
 pub const MAX_ITEM_VAL: usize = 999;
 pub const MAX_NAME_LENGTH: usize = 32;
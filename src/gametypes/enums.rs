@@ -255,6 +255,57 @@ pub enum LogType {
     Error,
 }
 
+/// Where an item was or went in the item ledger.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "item_place")]
+pub enum ItemPlace {
+    Inventory,
+    Equipment,
+    Storage,
+    Stash,
+    /// Held by a trade between taking it from one player and giving it to the other.
+    Trade,
+    Map,
+    Shop,
+    Npc,
+    /// Deleted or used up.
+    Void,
+}
+
+/// Why an item moved in the item ledger.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "item_reason")]
+pub enum ItemReason {
+    Drop,
+    NpcDrop,
+    Pickup,
+    Trade,
+    ShopBuy,
+    ShopSell,
+    Deposit,
+    Withdraw,
+    StashDeposit,
+    StashWithdraw,
+    Delete,
+    Use,
+    Equip,
+    Unequip,
+    /// Changed with the admin tool.
+    Admin,
+}
+
+/// Why a character snapshot was taken.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "snapshot_reason")]
+pub enum SnapshotReason {
+    Timer,
+    Trade,
+    LevelUp,
+    Logout,
+    /// Taken by the admin tool before it changes the Character.
+    Admin,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum SlotSpace {
     NoSpace(u16),
@@ -391,13 +442,35 @@ pub enum MessageChannel {
     Npc,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
-pub enum Command {
-    KickPlayer,
-    KickPlayerByName(String),
-    WarpTo(Position),
-    SpawnNpc(i32, Position),
-    Trade,
+/// Creates the Command enum along with a list of its variants and their field names and
+/// types, in tag order, so the protocol schema is built from the enum itself.
+macro_rules! commands {
+    ($(#[$meta:meta])* pub enum $name:ident {
+        $($variant:ident $(($($field:ident: $ty:ty),*))?,)*
+    }) => {
+        $(#[$meta])*
+        pub enum $name {
+            $($variant $(($($ty),*))?,)*
+        }
+
+        impl $name {
+            pub const VARIANTS: &[(&str, &[(&str, &str)])] = &[
+                $((stringify!($variant), &[$($((stringify!($field), stringify!($ty))),*)?]),)*
+            ];
+        }
+    };
+}
+
+commands! {
+    #[derive(Clone, Debug, PartialEq, Eq, MByteBufferRead, MByteBufferWrite)]
+    pub enum Command {
+        KickPlayer,
+        KickPlayerByName(name: String),
+        WarpTo(position: Position),
+        SpawnNpc(npc: i32, position: Position),
+        Trade,
+        ReloadTls,
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
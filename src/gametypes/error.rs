@@ -37,6 +37,20 @@ pub enum AscendingError {
     NpcNotFound(u64),
     #[error("Packet buffer {0:?} not found")]
     PacketCacheNotFound(DataTaskToken),
+    #[error("WebSocket error: {0}")]
+    WebSocket(&'static str),
+    #[error("Protocol schema does not match {name}: {message}")]
+    ProtocolSchema { name: String, message: String },
+    #[error("Persistence error: {0}")]
+    Persistence(&'static str),
+    #[error("Database schema is at version {database} but this server only knows up to {binary}")]
+    SchemaTooNew { database: i32, binary: i32 },
+    #[error("World snapshot is at version {snapshot} but this server only knows up to {binary}")]
+    SnapshotTooNew { snapshot: u32, binary: u32 },
+    #[error("Character {name} is online on server {server}")]
+    CharacterOnline { name: String, server: i16 },
+    #[error("Admin edit refused: {0}")]
+    AdminEdit(String),
     #[error("Error: {error}, BackTrace: {backtrace}")]
     AddrParseError {
         #[from]
@@ -101,6 +115,13 @@ pub enum AscendingError {
         backtrace: Box<Backtrace>,
     },
     #[error("Error: {error}, BackTrace: {backtrace}")]
+    SerdeJson {
+        #[from]
+        error: serde_json::Error,
+        #[backtrace]
+        backtrace: Box<Backtrace>,
+    },
+    #[error("Error: {error}, BackTrace: {backtrace}")]
     TomlDe {
         #[from]
         error: toml::de::Error,
@@ -164,4 +185,23 @@ impl AscendingError {
             backtrace: Box::new(Backtrace::capture()),
         }
     }
+
+    /// Whether the error means the database could not be reached, as opposed to it
+    /// refusing a query. Connection lost, shutting down and too many clients all count.
+    pub fn is_database_unreachable(&self) -> bool {
+        match self {
+            AscendingError::Sqlx { error, .. } => match error {
+                sqlx::Error::Io(_)
+                | sqlx::Error::Tls(_)
+                | sqlx::Error::PoolTimedOut
+                | sqlx::Error::PoolClosed
+                | sqlx::Error::WorkerCrashed => true,
+                sqlx::Error::Database(e) => e.code().is_some_and(|code| {
+                    code.starts_with("08") || code.starts_with("57P") || code == "53300"
+                }),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
 }
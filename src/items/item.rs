@@ -2,6 +2,9 @@ use bytey::{ByteBufferRead, ByteBufferWrite};
 use educe::Educe;
 use mmap_bytey::{MByteBufferRead, MByteBufferWrite};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::ItemData;
 
 #[derive(
     Debug,
@@ -19,12 +22,17 @@ use serde::{Deserialize, Serialize};
 )]
 #[educe(Default)]
 pub struct Item {
-    //17 bytes
+    //17 bytes on the wire
     pub num: u32,
     pub val: u16,
     #[educe(Default = 1)]
     pub level: u8,
     pub data: [i16; 5],
+    /// Set on non-stackable items when they are created so each one can be traced.
+    /// It is never sent to clients.
+    #[bytey(skip)]
+    #[mbytey(skip)]
+    pub instance: Option<Uuid>,
 }
 
 impl Item {
@@ -34,6 +42,15 @@ impl Item {
             ..Default::default()
         }
     }
+
+    /// Gives a newly created non-stackable item its own instance id.
+    pub fn with_instance(mut self, base: &ItemData) -> Self {
+        if !base.stackable && self.instance.is_none() {
+            self.instance = Some(Uuid::now_v7());
+        }
+
+        self
+    }
 }
 
 #[inline]
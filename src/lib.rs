@@ -0,0 +1,19 @@
+#![allow(dead_code)]
+#![recursion_limit = "256"]
+#![feature(let_chains, error_generic_member_access)]
+
+pub mod containers;
+pub mod gameloop;
+pub mod gametypes;
+pub mod items;
+pub mod mailer;
+pub mod maps;
+pub mod npcs;
+pub mod players;
+pub mod socket;
+pub mod sql;
+pub mod tasks;
+pub mod time_ext;
+
+use gameloop::*;
+use gametypes::*;
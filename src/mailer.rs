@@ -0,0 +1,64 @@
+use crate::{containers::Config, gametypes::*};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::{fs::File, io::Write};
+
+/// Where outgoing Mail gets delivered to.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MailerKind {
+    /// Writes the Mail into the server log. Used for local testing.
+    #[default]
+    Log,
+    /// Appends the Mail to the file set in `mail_file`. Used for local testing.
+    File,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Anything able to deliver Mail. Add a new MailerKind to plug in other senders.
+pub trait Mailer {
+    fn send(&self, mail: &Mail) -> Result<()>;
+}
+
+pub struct LogMailer;
+
+impl Mailer for LogMailer {
+    fn send(&self, mail: &Mail) -> Result<()> {
+        info!(
+            "Mail to: {}, Subject: {}, Body: {}",
+            mail.to, mail.subject, mail.body
+        );
+        Ok(())
+    }
+}
+
+pub struct FileMailer {
+    pub path: String,
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, mail: &Mail) -> Result<()> {
+        let mut file = File::options().append(true).create(true).open(&self.path)?;
+
+        writeln!(
+            file,
+            "To: {}\nSubject: {}\n\n{}\n",
+            mail.to, mail.subject, mail.body
+        )?;
+        Ok(())
+    }
+}
+
+pub fn create_mailer(config: &Config) -> Box<dyn Mailer> {
+    match config.mailer {
+        MailerKind::Log => Box::new(LogMailer),
+        MailerKind::File => Box::new(FileMailer {
+            path: config.mail_file.clone(),
+        }),
+    }
+}
@@ -1,28 +1,25 @@
 #![allow(dead_code)]
-#![recursion_limit = "256"]
-#![feature(let_chains, error_generic_member_access)]
-
-mod containers;
-mod gameloop;
-mod gametypes;
-mod items;
-mod maps;
-mod npcs;
-mod players;
-mod socket;
-mod sql;
-mod tasks;
-mod time_ext;
 
+use ascending_server::{
+    containers::{
+        Config, Storage, World, establish_connection, read_config, restore_world_snapshot,
+    },
+    gameloop::*,
+    socket, sql,
+};
 #[allow(unused_imports)]
 use backtrace::Backtrace;
-use containers::{Storage, World};
-use gameloop::*;
-use gametypes::*;
 use log::{Level, Metadata, Record, error, info};
-use std::{env, fs::File, io::Write, panic};
-
-use crate::containers::read_config;
+use std::{
+    env,
+    fs::File,
+    io::Write,
+    panic,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
 // used to get string input when we add a command console to control the game.
 // until then we will just not use this.
@@ -68,7 +65,89 @@ impl log::Log for MyLogger {
     fn flush(&self) {}
 }
 
+/// Prints the packet schema as JSON so client developers can generate their packet code.
+fn dump_protocol() {
+    let schema = socket::protocol_schema();
+
+    if let Err(e) = socket::verify_protocol_schema(&schema) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    match serde_json::to_string_pretty(&schema) {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Brings the database schema up to date without starting the server.
+/// With `dry_run` it only prints the migrations that would be applied.
+fn migrate_only(config: &Config, dry_run: bool) {
+    let mut rt = tokio::runtime::Runtime::new().unwrap();
+    let local = tokio::task::LocalSet::new();
+    let result = establish_connection(config, &mut rt, &local)
+        .and_then(|conn| sql::migrate(&conn, &mut rt, &local, dry_run));
+
+    match result {
+        Ok(pending) if pending.is_empty() => println!(
+            "Database schema is up to date at version {}.",
+            sql::latest_schema_version()
+        ),
+        Ok(pending) => {
+            for migration in pending {
+                if dry_run {
+                    println!(
+                        "Pending migration {}: {}",
+                        migration.version, migration.name
+                    );
+
+                    for statement in migration.statements {
+                        println!("{}\n", statement.trim());
+                    }
+                } else {
+                    println!(
+                        "Applied migration {}: {}",
+                        migration.version, migration.name
+                    );
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Sets the returned flag on Ctrl+C so the game loop can save everyone and stop.
+/// A second Ctrl+C exits straight away.
+fn listen_for_shutdown(storage: &Storage) -> Arc<AtomicBool> {
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let flag = shutdown.clone();
+
+    storage.rt.borrow().spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            info!("Shutdown requested.");
+            flag.store(true, Ordering::Relaxed);
+        }
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(1);
+        }
+    });
+
+    shutdown
+}
+
 fn main() {
+    if env::args().any(|arg| arg == "--dump-protocol") {
+        dump_protocol();
+        return;
+    }
+
     let config = read_config("settings.toml");
     log::set_logger(&MY_LOGGER).unwrap();
     // Set the Max level we accept logging to the file for.
@@ -92,14 +171,35 @@ fn main() {
         );
     }));
 
+    let dry_run = env::args().any(|arg| arg == "--dry-run");
+
+    if dry_run || env::args().any(|arg| arg == "--migrate-only") {
+        migrate_only(&config, dry_run);
+        return;
+    }
+
     info!("Starting up");
     info!("Initializing Storage");
-    let storage = Storage::new(config).unwrap();
+    let Some(storage) = Storage::new(config) else {
+        error!("Failed to initialize Storage, shutting down.");
+        std::process::exit(1);
+    };
     info!("Initializing PacketRouter");
     let router = PacketRouter::init();
     info!("Initializing World");
     let mut world = World::default();
 
+    if let Err(e) = restore_world_snapshot(&mut world, &storage) {
+        error!("Failed to restore the world snapshot: {}", e);
+    }
+
+    let shutdown = listen_for_shutdown(&storage);
+
     info!("Game Server is Running.");
-    game_loop(&mut world, &storage, &router);
+    game_loop(&mut world, &storage, &router, &shutdown);
+
+    info!("Writing queued saves.");
+    storage.persistence.shutdown();
+    storage.persistence.report();
+    info!("Game Server has shut down.");
 }
@@ -14,6 +14,7 @@ use std::{
     cmp::min,
     sync::{Arc, Mutex},
 };
+use uuid::Uuid;
 
 use super::check_surrounding;
 
@@ -118,7 +119,13 @@ pub fn update_maps(world: &mut World, storage: &Storage) -> Result<()> {
                 let mut storage_mapitem = storage.map_items.borrow_mut();
                 if !storage_mapitem.contains_key(&data.pos) {
                     if data.timer <= tick {
-                        let map_item = create_mapitem(data.index, data.amount, data.pos);
+                        let instance = storage
+                            .bases
+                            .items
+                            .get(data.index as usize)
+                            .filter(|base| !base.stackable)
+                            .map(|_| Uuid::now_v7());
+                        let map_item = create_mapitem(data.index, data.amount, data.pos, instance);
 
                         let id = world.kinds.insert(EntityKind::MapItem);
 
@@ -158,11 +165,12 @@ pub fn update_maps(world: &mut World, storage: &Storage) -> Result<()> {
     Ok(())
 }
 
-pub fn create_mapitem(index: u32, value: u16, pos: Position) -> MapItem {
+pub fn create_mapitem(index: u32, value: u16, pos: Position, instance: Option<Uuid>) -> MapItem {
     MapItem {
         item: Item {
             num: index,
             val: value,
+            instance,
             ..Default::default()
         },
         despawn: None,
@@ -6,10 +6,13 @@ use crate::{
         World,
     },
     gametypes::*,
+    items::Item,
     socket::*,
+    sql::{ItemMove, PGItemEvent, record_item_event},
     tasks::{DataTaskToken, map_item_packet, unload_entity_packet},
     time_ext::MyInstant,
 };
+use uuid::Uuid;
 
 use super::{MapAttribute, create_mapitem};
 
@@ -26,6 +29,8 @@ pub struct DropItem {
     pub index: u32,
     pub amount: u16,
     pub pos: Position,
+    /// The instance id of the item being dropped. Non-stackable items without one get a new one.
+    pub instance: Option<Uuid>,
 }
 
 pub fn update_map_items(world: &mut World, storage: &Storage) -> Result<()> {
@@ -176,6 +181,8 @@ pub fn find_drop_pos(
     Ok(result)
 }
 
+/// Drops the item on or near `drop_item.pos`. `source` is recorded in the item ledger as
+/// where it came from. Returns false if there was nowhere to put it.
 pub fn try_drop_item(
     world: &mut World,
     storage: &Storage,
@@ -183,6 +190,7 @@ pub fn try_drop_item(
     despawn: Option<MyInstant>,
     ownertimer: Option<MyInstant>,
     ownerid: Option<GlobalKey>,
+    source: ItemMove,
 ) -> Result<bool> {
     let item_base = match storage.bases.items.get(drop_item.index as usize) {
         Some(data) => data,
@@ -195,6 +203,12 @@ pub fn try_drop_item(
         return Ok(false);
     }
 
+    let instance = if item_base.stackable {
+        None
+    } else {
+        drop_item.instance.or_else(|| Some(Uuid::now_v7()))
+    };
+
     let mut leftover = drop_item.amount;
     for found_pos in set_pos.iter() {
         if item_base.stackable
@@ -215,7 +229,8 @@ pub fn try_drop_item(
             let mut storage_mapitem = storage.map_items.borrow_mut();
             let mapdata = storage.maps.get(&found_pos.0.map);
             if let Some(map_data) = mapdata {
-                let mut map_item = create_mapitem(drop_item.index, drop_item.amount, found_pos.0);
+                let mut map_item =
+                    create_mapitem(drop_item.index, drop_item.amount, found_pos.0, instance);
                 map_item.despawn = despawn;
                 map_item.ownertimer = ownertimer;
                 map_item.ownerid = ownerid;
@@ -247,6 +262,22 @@ pub fn try_drop_item(
         }
     }
 
+    let item = Item {
+        num: drop_item.index,
+        val: drop_item.amount,
+        instance,
+        ..Default::default()
+    };
+
+    record_item_event(
+        storage,
+        source.uid.unwrap_or_default(),
+        PGItemEvent::new(source.reason, &item, drop_item.amount)
+            .from(source.place, source.uid)
+            .to(ItemPlace::Map, None)
+            .at(drop_item.pos),
+    )?;
+
     Ok(true)
 }
 
@@ -308,8 +339,10 @@ pub fn player_interact_object(
                     {
                         p_data.try_lock()?.is_using_type = IsUsingType::Bank;
                     }
-                    send_storage(world, storage, entity, 0..35)?;
-                    send_storage(world, storage, entity, 35..MAX_STORAGE)?;
+                    send_storage_slots(world, storage, entity)?;
+                    send_storage_tabs(world, storage, entity)?;
+                    send_bank_money(world, storage, entity)?;
+                    send_stash(world, storage, entity)?;
                     send_openstorage(world, storage, entity)?;
                 }
                 MapAttribute::Shop(shop_index) => {
@@ -6,6 +6,7 @@ use crate::{
     maps::*,
     npcs::*,
     players::*,
+    sql::ItemMove,
     tasks::*,
 };
 use rand::{Rng, rng};
@@ -361,10 +362,12 @@ pub fn kill_npc(world: &mut World, storage: &Storage, entity: GlobalKey) -> Resu
                                     index: drop.item,
                                     amount: drop.amount as u16,
                                     pos: npc_pos,
+                                    instance: None,
                                 },
                                 None,
                                 None,
                                 None,
+                                ItemMove::new(ItemReason::NpcDrop, ItemPlace::Npc),
                             )?
                         {
                             break;
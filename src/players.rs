@@ -3,6 +3,7 @@ mod inv;
 mod logic;
 pub mod movement;
 mod player;
+mod player_stash;
 mod player_storage;
 
 pub use combat::*;
@@ -10,6 +11,7 @@ pub use inv::*;
 pub use logic::*;
 pub use movement::*;
 pub use player::*;
+pub use player_stash::*;
 pub use player_storage::*;
 
 pub const fn is_name_acceptable(n: char) -> bool {
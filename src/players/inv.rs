@@ -230,16 +230,29 @@ pub fn set_inv_item(
     Ok(rem)
 }
 
+/// Adds as much of `item` as fits and leaves the rest in `item.val`.
+/// `from` is recorded in the item ledger as where it came from.
 #[inline]
 pub fn give_inv_item(
     world: &mut World,
     storage: &Storage,
     entity: GlobalKey,
     item: &mut Item,
+    from: ItemMove,
 ) -> Result<()> {
     let base = &storage.bases.items[item.num as usize];
-
-    auto_set_inv_item(world, storage, entity, item, base)
+    let given = *item;
+    let start = if item.val == 0 { 1 } else { item.val };
+
+    auto_set_inv_item(world, storage, entity, item, base)?;
+    record_inv_gain(
+        storage,
+        world,
+        entity,
+        &given,
+        start.saturating_sub(item.val),
+        from,
+    )
 }
 
 pub fn check_inv_space(
@@ -286,6 +299,8 @@ pub fn set_inv_slot(
     set_inv_item(world, storage, entity, item, base, slot, amount)
 }
 
+/// Takes `amount` of item `num` across as many slots as it takes. Nothing is taken unless
+/// there is enough. `to` is recorded in the item ledger as where it went.
 #[inline]
 pub fn take_inv_items(
     world: &mut World,
@@ -293,27 +308,36 @@ pub fn take_inv_items(
     entity: GlobalKey,
     num: u32,
     mut amount: u16,
+    to: ItemMove,
 ) -> Result<u16> {
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let inventory = { p_data.try_lock()?.inventory.clone() };
 
         if count_inv_item(num, &inventory.items) >= amount as u64 {
-            while let Some(slot) = find_inv_item(num, &inventory.items) {
-                {
+            let start = amount;
+            let taken = find_inv_item(num, &inventory.items).map(|slot| inventory.items[slot]);
+
+            while amount > 0 {
+                let slot = {
                     let mut p_data = p_data.try_lock()?;
 
+                    let Some(slot) = find_inv_item(num, &p_data.inventory.items) else {
+                        break;
+                    };
+
                     let take_amount = p_data.inventory.items[slot].val;
                     p_data.inventory.items[slot].val =
                         p_data.inventory.items[slot].val.saturating_sub(amount);
 
                     amount = amount.saturating_sub(take_amount);
-                }
+                    slot
+                };
 
                 save_inv_item(world, storage, entity, slot)?;
+            }
 
-                if amount == 0 {
-                    return Ok(0);
-                }
+            if let Some(item) = taken {
+                record_inv_loss(storage, world, entity, &item, start - amount, to)?;
             }
         }
     }
@@ -321,6 +345,43 @@ pub fn take_inv_items(
     Ok(amount)
 }
 
+/// Takes a traded `item` out of the inventory. An item instance is taken from the slot
+/// holding it and anything else by item number. Returns how much could not be taken.
+pub fn take_trade_item(
+    world: &mut World,
+    storage: &Storage,
+    entity: GlobalKey,
+    item: &Item,
+    to: ItemMove,
+) -> Result<u16> {
+    let Some(instance) = item.instance else {
+        return take_inv_items(world, storage, entity, item.num, item.val, to);
+    };
+
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let found = {
+            let p_data = p_data.try_lock()?;
+
+            p_data
+                .inventory
+                .items
+                .iter()
+                .position(|slot| slot.val > 0 && slot.instance == Some(instance))
+                .map(|slot| (slot, p_data.inventory.items[slot]))
+        };
+
+        if let Some((slot, taken)) = found {
+            let amount = item.val.min(taken.val);
+
+            take_inv_itemslot(world, storage, entity, slot, amount)?;
+            record_inv_loss(storage, world, entity, &taken, amount, to)?;
+            return Ok(item.val - amount);
+        }
+    }
+
+    Ok(item.val)
+}
+
 #[inline]
 pub fn take_inv_itemslot(
     world: &mut World,
@@ -531,21 +592,27 @@ pub fn player_unequip(
     slot: usize,
 ) -> Result<bool> {
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
-        let mut item = {
+        let (mut item, cid) = {
             let p_data = p_data.try_lock()?;
 
             if p_data.equipment.items[slot].val == 0 {
                 return Ok(true);
             }
 
-            p_data.equipment.items[slot]
+            (p_data.equipment.items[slot], p_data.character.id)
         };
 
         if !check_inv_space(world, storage, entity, &mut item)? {
             return Ok(false);
         }
 
-        give_inv_item(world, storage, entity, &mut item)?;
+        give_inv_item(
+            world,
+            storage,
+            entity,
+            &mut item,
+            ItemMove::new(ItemReason::Unequip, ItemPlace::Equipment).owned_by(cid),
+        )?;
 
         {
             p_data.try_lock()?.equipment.items[slot] = Item::default();
@@ -588,13 +655,14 @@ pub fn player_use_item(
     }
 
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
-        let (item, player_pos, player_vital) = {
+        let (item, player_pos, player_vital, cid) = {
             let p_data = p_data.try_lock()?;
 
             (
                 p_data.inventory.items[slot as usize],
                 p_data.movement.pos,
                 p_data.combat.vitals,
+                p_data.character.id,
             )
         };
 
@@ -604,7 +672,7 @@ pub fn player_use_item(
 
         let base = &storage.bases.items[item.num as usize];
 
-        match base.itemtype {
+        let used = match base.itemtype {
             ItemTypes::Consume => {
                 if base.data[0] > 0 {
                     let set_vital = player_vital.vital[VitalTypes::Hp as usize]
@@ -631,6 +699,8 @@ pub fn player_use_item(
                         .min(player_vital.vitalmax[VitalTypes::Sp as usize]);
                     player_set_vital(world, storage, entity, VitalTypes::Sp, set_vital)?;
                 }
+
+                ItemMove::new(ItemReason::Use, ItemPlace::Void)
             }
             ItemTypes::Weapon
             | ItemTypes::Helmet
@@ -650,15 +720,18 @@ pub fn player_use_item(
                     return Ok(());
                 }
                 player_equip(world, storage, entity, item, eqslot)?;
+
+                ItemMove::new(ItemReason::Equip, ItemPlace::Equipment).owned_by(cid)
             }
             _ => return Ok(()),
-        }
+        };
 
         if let Some(_sfx) = &base.sound_index {
             send_playitemsfx(world, storage, entity, item.num as u16)?;
         }
 
         take_inv_itemslot(world, storage, entity, slot as usize, 1)?;
+        record_inv_loss(storage, world, entity, &item, 1, used)?;
     }
     Ok(())
 }
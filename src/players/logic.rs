@@ -14,6 +14,7 @@ use crate::{
 use chrono::Duration;
 use log::debug;
 use std::cmp;
+use uuid::Uuid;
 
 pub fn update_players(world: &mut World, storage: &Storage) -> Result<()> {
     let tick = *storage.gettick.borrow();
@@ -184,7 +185,11 @@ pub fn player_earn_exp(
             p_data.general.levelexp
         };
 
+        let mut leveled = false;
+
         while levelexp >= player_get_next_lvl_exp(world, entity)? && cur_level != MAX_LVL as i32 {
+            leveled = true;
+
             {
                 let mut p_data = p_data.try_lock()?;
 
@@ -227,6 +232,10 @@ pub fn player_earn_exp(
 
         let vitals = { p_data.try_lock()?.combat.vitals };
 
+        if leveled {
+            snapshot_player(storage, &*p_data.try_lock()?, SnapshotReason::LevelUp)?;
+        }
+
         send_level(world, storage, entity)?;
         DataTaskToken::Vitals(position.map).add_task(
             storage,
@@ -403,14 +412,14 @@ pub fn get_next_stat_exp(level: u32) -> u64 {
 
 pub fn joingame(world: &mut World, storage: &Storage, entity: GlobalKey) -> Result<()> {
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
-        let (socket_id, account, position) = {
+        let (socket_id, character, position) = {
             let mut p_data = p_data.try_lock()?;
 
             p_data.online_type = OnlineType::Online;
 
             (
                 p_data.socket.id,
-                p_data.account.clone(),
+                p_data.character.clone(),
                 p_data.movement.pos,
             )
         };
@@ -437,7 +446,7 @@ pub fn joingame(world: &mut World, storage: &Storage, entity: GlobalKey) -> Resu
             message_packet(
                 MessageChannel::Map,
                 String::new(),
-                format!("{} has joined the game", account.username),
+                format!("{} has joined the game", character.name),
                 None,
             )?,
         )?;
@@ -460,13 +469,13 @@ pub fn joingame(world: &mut World, storage: &Storage, entity: GlobalKey) -> Resu
 
 pub fn left_game(world: &mut World, storage: &Storage, entity: GlobalKey) -> Result<()> {
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
-        let (online_type, position, account, connection_code) = {
+        let (online_type, position, character, connection_code) = {
             let p_data = p_data.try_lock()?;
 
             (
                 p_data.online_type,
                 p_data.movement.pos,
-                p_data.account.clone(),
+                p_data.character.clone(),
                 p_data.relogin_code.clone(),
             )
         };
@@ -480,7 +489,7 @@ pub fn left_game(world: &mut World, storage: &Storage, entity: GlobalKey) -> Res
             message_packet(
                 MessageChannel::Map,
                 String::new(),
-                format!("{} has left the game", account.username),
+                format!("{} has left the game", character.name),
                 None,
             )?,
         )?;
@@ -651,32 +660,39 @@ pub fn process_player_trade(
                 }
             }
 
+            // Every item moved by this trade is recorded in the ledger under one id.
+            let trade =
+                ItemMove::new(ItemReason::Trade, ItemPlace::Trade).correlated(Uuid::now_v7());
+
             for item in entity_item.items.iter() {
                 if item.val > 0 {
-                    take_inv_items(world, storage, entity, item.num, item.val)?;
+                    take_trade_item(world, storage, entity, item, trade)?;
                 }
             }
             player_take_vals(world, storage, entity, entity_money)?;
             for item in target_item.items.iter() {
                 if item.val > 0 {
-                    take_inv_items(world, storage, target_entity, item.num, item.val)?;
+                    take_trade_item(world, storage, target_entity, item, trade)?;
                 }
             }
             player_take_vals(world, storage, target_entity, target_money)?;
 
             for item in entity_item.items.clone().iter_mut() {
                 if item.val > 0 {
-                    give_inv_item(world, storage, target_entity, item)?;
+                    give_inv_item(world, storage, target_entity, item, trade)?;
                 }
             }
             player_give_vals(world, storage, target_entity, entity_money)?;
             for item in target_item.items.clone().iter_mut() {
                 if item.val > 0 {
-                    give_inv_item(world, storage, entity, item)?;
+                    give_inv_item(world, storage, entity, item, trade)?;
                 }
             }
             player_give_vals(world, storage, entity, target_money)?;
 
+            snapshot_player(storage, &*p1_data.try_lock()?, SnapshotReason::Trade)?;
+            snapshot_player(storage, &*p2_data.try_lock()?, SnapshotReason::Trade)?;
+
             return Ok(true);
         }
     }
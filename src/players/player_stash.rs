@@ -0,0 +1,64 @@
+use crate::{containers::*, gametypes::*, items::*, socket::*, sql::*};
+
+#[inline]
+pub fn save_stash_item(
+    world: &mut World,
+    storage: &Storage,
+    entity: GlobalKey,
+    slot: usize,
+) -> Result<()> {
+    update_stash(storage, world, entity, slot)?;
+    send_stashslot(world, storage, entity, slot)
+}
+
+/// How much of the item can be placed into the slot holding slot_item.
+/// Only empty slots or stacks of the same item can take anything.
+pub fn item_slot_room(storage: &Storage, slot_item: &Item, item: &Item) -> u16 {
+    if slot_item.val == 0 {
+        return item.val;
+    }
+
+    let base = match storage.bases.items.get(item.num as usize) {
+        Some(base) => base,
+        None => return 0,
+    };
+
+    if base.stackable
+        && slot_item.num == item.num
+        && slot_item.level == item.level
+        && slot_item.data == item.data
+    {
+        item.val.min(base.stacklimit.saturating_sub(slot_item.val))
+    } else {
+        0
+    }
+}
+
+pub fn take_stash_itemslot(
+    world: &mut World,
+    storage: &Storage,
+    entity: GlobalKey,
+    slot: usize,
+    mut amount: u16,
+) -> Result<u16> {
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let stash_item_val = {
+            let mut p_data = p_data.try_lock()?;
+
+            amount = std::cmp::min(amount, p_data.stash.items[slot].val);
+
+            p_data.stash.items[slot].val = p_data.stash.items[slot].val.saturating_sub(amount);
+            if p_data.stash.items[slot].val == 0 {
+                p_data.stash.items[slot] = Item::default();
+            }
+
+            p_data.stash.items[slot].val
+        };
+
+        save_stash_item(world, storage, entity, slot)?;
+
+        Ok(stash_item_val)
+    } else {
+        Ok(0)
+    }
+}
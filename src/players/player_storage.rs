@@ -13,7 +13,7 @@ pub fn save_storage_item(
 
 #[inline]
 pub fn count_storage_item(num: u32, storage: &[Item]) -> u64 {
-    (0..MAX_STORAGE)
+    (0..storage.len())
         .filter_map(|id| {
             if storage[id].num == num && storage[id].val > 0 {
                 Some(storage[id].val as u64)
@@ -26,13 +26,13 @@ pub fn count_storage_item(num: u32, storage: &[Item]) -> u64 {
 
 #[inline]
 pub fn find_storage_item(num: u32, storage: &[Item]) -> Option<usize> {
-    (0..MAX_STORAGE).find(|id| storage[*id].num == num && storage[*id].val > 0)
+    (0..storage.len()).find(|id| storage[*id].num == num && storage[*id].val > 0)
 }
 
 #[inline]
 pub fn find_storage_slot(item: &Item, storage: &[Item], base: &ItemData) -> Option<usize> {
     if base.stackable {
-        if let Some(id) = (0..MAX_STORAGE).find(|id| {
+        if let Some(id) = (0..storage.len()).find(|id| {
             storage[*id].num == item.num
                 && storage[*id].val < base.stacklimit
                 && storage[*id].val > 0
@@ -41,7 +41,7 @@ pub fn find_storage_slot(item: &Item, storage: &[Item], base: &ItemData) -> Opti
         }
     }
 
-    (0..MAX_STORAGE).find(|id| storage[*id].val == 0)
+    (0..storage.len()).find(|id| storage[*id].val == 0)
 }
 
 #[inline]
@@ -61,7 +61,7 @@ pub fn auto_set_storage_item(
             let mut p_data = p_data.try_lock()?;
 
             if base.stackable {
-                for id in 0..MAX_STORAGE {
+                for id in 0..p_data.storage.items.len() {
                     if p_data.storage.items[id].num == item.num
                         && p_data.storage.items[id].val < base.stacklimit
                         && p_data.storage.items[id].val > 0
@@ -84,7 +84,7 @@ pub fn auto_set_storage_item(
             item.val = total_left;
 
             if total_left > 0 {
-                for id in 0..MAX_STORAGE {
+                for id in 0..p_data.storage.items.len() {
                     if p_data.storage.items[id].val == 0 {
                         p_data.storage.items[id] = *item;
                         item.val = 0;
@@ -116,7 +116,7 @@ pub fn check_storage_space(
         let p_data = p_data.try_lock()?;
 
         //First try to add it to other of the same type
-        for id in 0..MAX_STORAGE {
+        for id in 0..p_data.storage.items.len() {
             if base.stackable
                 && p_data.storage.items[id].num == item.num
                 && p_data.storage.items[id].val < base.stacklimit
@@ -154,7 +154,7 @@ pub fn check_storage_item_partial_space(
 
         //First try to add it to other of the same type
         if base.stackable {
-            for id in 0..MAX_STORAGE {
+            for id in 0..p_data.storage.items.len() {
                 if p_data.storage.items[id].num == item.num
                     && p_data.storage.items[id].val < base.stacklimit
                     && p_data.storage.items[id].val > 0
@@ -168,7 +168,7 @@ pub fn check_storage_item_partial_space(
             }
         }
 
-        for id in 0..MAX_STORAGE {
+        for id in 0..p_data.storage.items.len() {
             if p_data.storage.items[id].val == 0 {
                 return Ok((0, start_val));
             }
@@ -341,3 +341,119 @@ pub fn take_storage_itemslot(
         Ok(0)
     }
 }
+
+/// Moves vals from the players wallet into their bank. Returns the amount moved.
+pub fn player_deposit_vals(
+    world: &mut World,
+    storage: &Storage,
+    entity: GlobalKey,
+    amount: u64,
+) -> Result<u64> {
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let amount = {
+            let mut p_data = p_data.try_lock()?;
+
+            let amount = amount
+                .min(p_data.money.vals)
+                .min(u64::MAX - p_data.bank_money.vals);
+
+            p_data.money.vals -= amount;
+            p_data.bank_money.vals += amount;
+            amount
+        };
+
+        if amount > 0 {
+            send_money(world, storage, entity)?;
+            send_bank_money(world, storage, entity)?;
+            update_currency(storage, world, entity)?;
+            update_bank_money(storage, world, entity)?;
+        }
+
+        return Ok(amount);
+    }
+
+    Ok(0)
+}
+
+/// Moves vals from the players bank into their wallet. Returns the amount moved.
+pub fn player_withdraw_vals(
+    world: &mut World,
+    storage: &Storage,
+    entity: GlobalKey,
+    amount: u64,
+) -> Result<u64> {
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let amount = {
+            let mut p_data = p_data.try_lock()?;
+
+            let amount = amount
+                .min(p_data.bank_money.vals)
+                .min(u64::MAX - p_data.money.vals);
+
+            p_data.bank_money.vals -= amount;
+            p_data.money.vals += amount;
+            amount
+        };
+
+        if amount > 0 {
+            send_money(world, storage, entity)?;
+            send_bank_money(world, storage, entity)?;
+            update_currency(storage, world, entity)?;
+            update_bank_money(storage, world, entity)?;
+        }
+
+        return Ok(amount);
+    }
+
+    Ok(0)
+}
+
+/// Price of the next storage tab. Each tab bought costs more than the last.
+pub fn storage_tab_price(storage: &Storage, tabs: usize) -> u64 {
+    let bought = tabs.saturating_sub(BASE_STORAGE_TABS) as u64 + 1;
+
+    storage.config.storage_tab_price.saturating_mul(bought)
+}
+
+/// Unlocks one more storage tab paid for with the players wallet.
+/// Returns false if the storage is already at MAX_STORAGE_TABS or they cant afford it.
+pub fn player_buy_storage_tab(
+    world: &mut World,
+    storage: &Storage,
+    entity: GlobalKey,
+) -> Result<bool> {
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let (tabs, old_slots, money) = {
+            let p_data = p_data.try_lock()?;
+
+            (
+                p_data.storage.tabs(),
+                p_data.storage.items.len(),
+                p_data.money.vals,
+            )
+        };
+
+        let price = storage_tab_price(storage, tabs);
+
+        if tabs >= MAX_STORAGE_TABS || money < price {
+            return Ok(false);
+        }
+
+        {
+            let mut p_data = p_data.try_lock()?;
+
+            p_data.money.vals -= price;
+            p_data.storage.resize((tabs + 1) * STORAGE_TAB_SIZE);
+        }
+
+        update_storage_slots(storage, world, entity, old_slots)?;
+        update_currency(storage, world, entity)?;
+        send_money(world, storage, entity)?;
+        send_storage_slots(world, storage, entity)?;
+        send_storage_tabs(world, storage, entity)?;
+
+        return Ok(true);
+    }
+
+    Ok(false)
+}
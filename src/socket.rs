@@ -1,17 +1,29 @@
 mod buffer;
 mod client;
+mod compression;
+mod held_updates;
+mod ip_filter;
 mod packet_ids;
+mod protocol_schema;
+mod rate_limit;
 mod sends;
 mod server;
 mod states;
+mod websocket;
 
 pub use buffer::*;
 #[allow(unused_imports)]
 pub use bytey::{ByteBuffer, ByteBufferError, ByteBufferRead, ByteBufferWrite};
 pub use client::*;
+pub use compression::*;
+pub use held_updates::*;
+pub use ip_filter::*;
 #[allow(unused_imports)]
 pub use mmap_bytey::{MByteBuffer, MByteBufferError, MByteBufferRead, MByteBufferWrite};
 pub use packet_ids::*;
+pub use protocol_schema::*;
+pub use rate_limit::*;
 pub use sends::*;
 pub use server::*;
 pub use states::*;
+pub use websocket::*;
@@ -1,14 +1,15 @@
 use crate::{
     PacketRouter,
-    containers::{Entity, GlobalKey, Storage, World},
+    containers::{Config, Entity, GlobalKey, Storage, World},
     gameloop::SocketID,
     gametypes::*,
     handle_data,
     maps::*,
     players::*,
     socket::*,
-    sql::save_player,
+    sql::{SaveCommand, save_player, snapshot_player},
     tasks::{DataTaskToken, unload_entity_packet},
+    time_ext::MyInstant,
 };
 use chrono::Duration;
 use log::{error, info, trace, warn};
@@ -28,10 +29,26 @@ pub struct Client {
     pub state: ClientState,
     pub poll_state: PollState,
     pub sends: VecDeque<MByteBuffer>,
+    // Bytes waiting in sends.
+    pub send_bytes: usize,
+    // When the sends went over the hard limit. None while under it.
+    pub send_overflow_since: Option<MyInstant>,
     // used for sending encrypted Data.
     pub tls: Option<rustls::ServerConnection>,
+    // Set when the Client connected through a WebSocket listener.
+    pub websocket: Option<WebSocket>,
     pub buffer: Arc<Mutex<ByteBuffer>>,
     pub addr: Arc<String>,
+    // Negotiated protocol version. 0 until the client sends ProtocolVersion.
+    pub protocol_version: u16,
+    // Set when compression was agreed on in ProtocolVersion.
+    pub compression: bool,
+    // Move and Dir updates held back while over the soft send limit.
+    pub held: HeldUpdates,
+    pub rate_limiter: PacketRateLimiter,
+    pub connected_at: MyInstant,
+    // Set once a TLS Client shows a certificate signed by ca_root.
+    pub trusted: bool,
 }
 
 impl Client {
@@ -40,6 +57,7 @@ impl Client {
         stream: TcpStream,
         token: mio::Token,
         tls: Option<rustls::ServerConnection>,
+        websocket: Option<WebSocket>,
         addr: String,
     ) -> Result<Client> {
         Ok(Client {
@@ -49,9 +67,18 @@ impl Client {
             state: ClientState::Open,
             poll_state: PollState::ReadWrite,
             sends: VecDeque::with_capacity(32),
+            send_bytes: 0,
+            send_overflow_since: None,
             tls,
+            websocket,
             buffer: Arc::new(Mutex::new(ByteBuffer::with_capacity(8192)?)),
             addr: Arc::new(addr),
+            protocol_version: 0,
+            compression: false,
+            held: HeldUpdates::default(),
+            rate_limiter: PacketRateLimiter::default(),
+            connected_at: MyInstant::now(),
+            trusted: false,
         })
     }
 
@@ -71,7 +98,13 @@ impl Client {
         }
 
         // Check if the Event has some writable Data from the Poll State.
-        if event.is_writable() {
+        // A WebSocket Close is answered before the socket gets closed.
+        let ws_closed = self
+            .websocket
+            .as_ref()
+            .is_some_and(|ws| ws.state == WebSocketState::Closed);
+
+        if event.is_writable() || ws_closed {
             if self.tls.is_some() {
                 self.tls_write();
             } else {
@@ -79,6 +112,14 @@ impl Client {
             }
         }
 
+        if ws_closed {
+            self.state = ClientState::Closing;
+        }
+
+        if !self.held.is_empty() && !self.over_soft_limit(&storage.config) {
+            self.send_held(storage)?;
+        }
+
         // Check if the Socket is closing if not lets reregister the poll event for it.
         // if `SocketPollState::None` is registers as the poll event we will not get data.
         match self.state {
@@ -116,6 +157,8 @@ impl Client {
 
                 let mut remove_entity = false;
 
+                let _ = storage.char_select.borrow_mut().remove(&self.token);
+
                 if let Some(entity) = self.entity {
                     if let Some(Entity::Player(data)) = world.get_opt_entity(entity) {
                         let mut data = data.try_lock()?;
@@ -139,7 +182,7 @@ impl Client {
 
                             info!(
                                 "Added player on disconnected list : {}",
-                                &data.account.username
+                                &data.character.name
                             );
 
                             data.connection.disconnect_timer = *storage.gettick.borrow()
@@ -206,6 +249,11 @@ impl Client {
                 }
             };
 
+            if !self.trusted && !tls.is_handshaking() && tls.peer_certificates().is_some() {
+                info!("Trusted client certificate from IP: {}", self.addr);
+                self.trusted = true;
+            }
+
             if io_state.plaintext_bytes_to_read() > 0 {
                 let mut buf = vec![0u8; io_state.plaintext_bytes_to_read()];
                 if let Err(e) = tls.reader().read_exact(&mut buf) {
@@ -215,7 +263,14 @@ impl Client {
                     return Ok(());
                 }
 
-                if let Err(e) = buffer.write_slice(&buf) {
+                if let Err(e) = receive_data(
+                    &mut self.websocket,
+                    &mut self.sends,
+                    &mut self.send_bytes,
+                    &mut self.poll_state,
+                    &mut buffer,
+                    &buf,
+                ) {
                     trace!("TLS read buffer write error: {}", e);
                     self.state = ClientState::Closing;
                     buffer.move_cursor(pos)?;
@@ -261,7 +316,14 @@ impl Client {
                     closing = true;
                 }
                 Ok(n) => {
-                    if let Err(e) = buffer.write_slice(&buf[0..n]) {
+                    if let Err(e) = receive_data(
+                        &mut self.websocket,
+                        &mut self.sends,
+                        &mut self.send_bytes,
+                        &mut self.poll_state,
+                        &mut buffer,
+                        &buf[0..n],
+                    ) {
                         trace!("buffer.write_slice, error in socket read: {}", e);
                         closing = true;
                     }
@@ -306,10 +368,13 @@ impl Client {
                 }
             };
 
+            self.send_bytes = self.send_bytes.saturating_sub(packet.length());
+
             match self.stream.write_all(packet.as_slice()) {
                 Ok(()) => count += 1,
                 Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
                     //Operation would block so we insert it back in to try again later.
+                    self.send_bytes += packet.length();
                     self.sends.push_front(packet);
                     break;
                 }
@@ -355,8 +420,11 @@ impl Client {
                 }
             };
 
+            self.send_bytes = self.send_bytes.saturating_sub(packet.length());
+
             match tls.writer().write_all(packet.as_slice()) {
                 Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    self.send_bytes += packet.length();
                     self.sends.push_front(packet);
                     break;
                 }
@@ -412,20 +480,117 @@ impl Client {
     }
 
     #[inline]
-    pub fn send(&mut self, poll: &mio::Poll, buf: MByteBuffer) -> Result<()> {
+    pub fn send(&mut self, storage: &Storage, buf: MByteBuffer) -> Result<()> {
+        let buf = self.compress(storage, buf)?;
+        self.queue(storage, buf)
+    }
+
+    /// Queues a packet that is already compressed for this Client if it needed to be.
+    #[inline]
+    pub fn queue(&mut self, storage: &Storage, mut buf: MByteBuffer) -> Result<()> {
+        if self.websocket.is_some() {
+            buf = binary_frame(&mut buf)?;
+        }
+
+        self.send_bytes += buf.length();
         self.sends.push_back(buf);
         self.poll_state.add(PollState::Write);
-        self.reregister(poll)
+        self.reregister(&storage.poll.borrow())
     }
 
     #[inline]
-    pub fn send_first(&mut self, poll: &mio::Poll, buf: MByteBuffer) -> Result<()> {
+    pub fn send_first(&mut self, storage: &Storage, buf: MByteBuffer) -> Result<()> {
+        let mut buf = self.compress(storage, buf)?;
+
+        if self.websocket.is_some() {
+            buf = binary_frame(&mut buf)?;
+        }
+
+        self.send_bytes += buf.length();
         self.sends.push_front(buf);
         self.poll_state.add(PollState::Write);
-        self.reregister(poll)
+        self.reregister(&storage.poll.borrow())
+    }
+
+    /// Sends a Move or Dir packet the Client can do without, such as movement on other maps.
+    /// While the Client is over the soft send limit only the newest update of each entity
+    /// is held, and those are sent once it catches up.
+    #[inline]
+    pub fn send_low_priority(
+        &mut self,
+        storage: &Storage,
+        packet: &mut BroadcastPacket,
+    ) -> Result<()> {
+        if self.over_soft_limit(&storage.config) {
+            trace!("Held low priority packet for slow client IP: {}", self.addr);
+            return self.held.hold(packet.plain());
+        }
+
+        self.send_held(storage)?;
+
+        let buf = packet.for_client(storage, self.compression)?;
+        self.queue(storage, buf)
+    }
+
+    /// Sends the updates held back while the Client was over the soft send limit.
+    pub fn send_held(&mut self, storage: &Storage) -> Result<()> {
+        for buf in self.held.take_packets()? {
+            self.send(storage, buf)?;
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn over_soft_limit(&self, config: &Config) -> bool {
+        self.sends.len() > config.send_queue_soft_count
+            || self.send_bytes > config.send_queue_soft_bytes
+    }
+
+    #[inline]
+    pub fn over_hard_limit(&self, config: &Config) -> bool {
+        self.sends.len() > config.send_queue_hard_count
+            || self.send_bytes > config.send_queue_hard_bytes
+    }
+
+    #[inline]
+    fn compress(&self, storage: &Storage, buf: MByteBuffer) -> Result<MByteBuffer> {
+        if self.compression && storage.config.compression {
+            compress_packet(storage, buf)
+        } else {
+            Ok(buf)
+        }
     }
 }
 
+/// Adds the received bytes to the packet buffer. WebSocket frames are unwrapped first
+/// and any replies they need are queued to be sent.
+fn receive_data(
+    websocket: &mut Option<WebSocket>,
+    sends: &mut VecDeque<MByteBuffer>,
+    send_bytes: &mut usize,
+    poll_state: &mut PollState,
+    buffer: &mut ByteBuffer,
+    data: &[u8],
+) -> Result<()> {
+    match websocket {
+        Some(websocket) => {
+            let replies = websocket.receive(data, buffer)?;
+
+            if !replies.is_empty() {
+                *send_bytes += replies.iter().map(|reply| reply.length()).sum::<usize>();
+                sends.extend(replies);
+                poll_state.add(PollState::Write);
+            }
+        }
+        None => {
+            buffer.write_slice(data)?;
+        }
+    }
+
+    Ok(())
+}
+
 #[inline]
 pub fn disconnect(playerid: GlobalKey, world: &mut World, storage: &Storage) -> Result<()> {
     left_game(world, storage, playerid)?;
@@ -437,15 +602,19 @@ pub fn disconnect(playerid: GlobalKey, world: &mut World, storage: &Storage) ->
     let _ = storage.player_timeout.borrow_mut().remove(playerid);
 
     let position = if let Some(player) = storage.remove_player(world, playerid)? {
-        let pos = {
+        let (pos, cid) = {
             let player = player.try_lock()?;
 
             trace!("Players Disconnected IP: {} ", &player.socket.addr);
 
-            player.movement.pos
+            snapshot_player(storage, &player, SnapshotReason::Logout)?;
+
+            (player.movement.pos, player.character.id)
         };
 
         save_player(storage, player)?;
+        storage.persistence.save(SaveCommand::Offline { cid })?;
+        storage.persistence.flush()?;
 
         Some(pos)
     } else {
@@ -467,7 +636,7 @@ pub fn disconnect(playerid: GlobalKey, world: &mut World, storage: &Storage) ->
 #[inline]
 pub fn send_to(storage: &Storage, socket_id: Token, buf: MByteBuffer) -> Result<()> {
     if let Some(client) = storage.server.borrow().clients.get(&socket_id) {
-        client.borrow_mut().send(&storage.poll.borrow(), buf)
+        client.borrow_mut().send(storage, buf)
     } else {
         Ok(())
     }
@@ -476,7 +645,7 @@ pub fn send_to(storage: &Storage, socket_id: Token, buf: MByteBuffer) -> Result<
 #[inline]
 pub fn send_to_front(storage: &Storage, socket_id: Token, buf: MByteBuffer) -> Result<()> {
     if let Some(client) = storage.server.borrow().clients.get(&socket_id) {
-        client.borrow_mut().send_first(&storage.poll.borrow(), buf)
+        client.borrow_mut().send_first(storage, buf)
     } else {
         Ok(())
     }
@@ -484,15 +653,18 @@ pub fn send_to_front(storage: &Storage, socket_id: Token, buf: MByteBuffer) -> R
 
 #[inline]
 pub fn send_to_all(world: &mut World, storage: &Storage, buf: MByteBuffer) -> Result<()> {
+    let mut packet = BroadcastPacket::new(buf);
+
     for (_, entity) in world.entities.iter() {
         if let Entity::Player(data) = entity {
             let data = data.try_lock()?;
 
             if data.online_type == OnlineType::Online {
                 if let Some(client) = storage.server.borrow().clients.get(&data.socket.id) {
-                    client
-                        .borrow_mut()
-                        .send(&storage.poll.borrow(), buf.try_clone()?)?;
+                    let mut client = client.borrow_mut();
+                    let buf = packet.for_client(storage, client.compression)?;
+
+                    client.queue(storage, buf)?;
                 }
             }
         }
@@ -506,9 +678,16 @@ pub fn send_to_maps(
     world: &mut World,
     storage: &Storage,
     position: MapPosition,
-    buf: MByteBuffer,
+    mut buf: MByteBuffer,
     avoidindex: Option<GlobalKey>,
 ) -> Result<()> {
+    // Movement on the other surrounding maps is the first thing a slow Client can skip.
+    let low_priority = matches!(
+        ServerPackets::from_packet(&mut buf),
+        Some(ServerPackets::Move | ServerPackets::Dir)
+    );
+    let mut packet = BroadcastPacket::new(buf);
+
     for m in get_surrounding(position, true) {
         let map = match storage.maps.get(&m) {
             Some(map) => map,
@@ -526,9 +705,18 @@ pub fn send_to_maps(
 
                 if data.online_type == OnlineType::Online {
                     if let Some(client) = storage.server.borrow().clients.get(&data.socket.id) {
-                        client
-                            .borrow_mut()
-                            .send(&storage.poll.borrow(), buf.try_clone()?)?;
+                        let mut client = client.borrow_mut();
+
+                        if low_priority && m != position {
+                            client.send_low_priority(storage, &mut packet)?;
+                        } else {
+                            if low_priority {
+                                client.held.forget(packet.plain())?;
+                            }
+
+                            let buf = packet.for_client(storage, client.compression)?;
+                            client.queue(storage, buf)?;
+                        }
                     }
                 }
             }
@@ -545,15 +733,18 @@ pub fn send_to_entities(
     entities: &[GlobalKey],
     buf: MByteBuffer,
 ) -> Result<()> {
+    let mut packet = BroadcastPacket::new(buf);
+
     for entity in entities {
         if let Some(Entity::Player(data)) = world.get_opt_entity(*entity) {
             let data = data.try_lock()?;
 
             if data.online_type == OnlineType::Online {
                 if let Some(client) = storage.server.borrow().clients.get(&data.socket.id) {
-                    client
-                        .borrow_mut()
-                        .send(&storage.poll.borrow(), buf.try_clone()?)?;
+                    let mut client = client.borrow_mut();
+                    let buf = packet.for_client(storage, client.compression)?;
+
+                    client.queue(storage, buf)?;
                 }
             }
         }
@@ -0,0 +1,121 @@
+use crate::{
+    containers::{HashMap, Storage},
+    gametypes::*,
+    socket::ServerPackets,
+};
+use log::info;
+use mmap_bytey::MByteBuffer;
+
+/// Set on the high bit of the length header when the payload is compressed.
+/// The payload is then LZ4 block data with the uncompressed size prepended as a little endian u32.
+pub const COMPRESSED_FLAG: u64 = 1 << 63;
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CompressionStats {
+    pub packets: u64,
+    pub raw_bytes: u64,
+    pub sent_bytes: u64,
+}
+
+/// Bytes saved by compression for each ServerPacket.
+#[derive(Debug, Default)]
+pub struct CompressionMetrics {
+    pub packets: HashMap<ServerPackets, CompressionStats>,
+}
+
+impl CompressionMetrics {
+    pub fn add(&mut self, id: ServerPackets, raw_bytes: usize, sent_bytes: usize) {
+        let stats = self.packets.entry(id).or_default();
+
+        stats.packets += 1;
+        stats.raw_bytes += raw_bytes as u64;
+        stats.sent_bytes += sent_bytes as u64;
+    }
+
+    pub fn report(&self) {
+        let mut packets: Vec<_> = self.packets.iter().collect();
+
+        packets.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.raw_bytes - stats.sent_bytes));
+
+        for (id, stats) in packets {
+            info!(
+                "Compression {:?}: packets: {}, raw bytes: {}, sent bytes: {}, saved bytes: {}",
+                id,
+                stats.packets,
+                stats.raw_bytes,
+                stats.sent_bytes,
+                stats.raw_bytes - stats.sent_bytes
+            );
+        }
+    }
+}
+
+/// Compresses a finished packet if its payload is over the `compression_threshold`.
+/// Packets that would not get any smaller are returned as they are.
+pub fn compress_packet(storage: &Storage, mut buf: MByteBuffer) -> Result<MByteBuffer> {
+    let length = buf.length();
+
+    if length < 8 || length - 8 < storage.config.compression_threshold {
+        return Ok(buf);
+    }
+
+    let id = ServerPackets::from_packet(&mut buf);
+    let compressed = lz4_flex::compress_prepend_size(buf.slice_from(8, length - 8)?);
+
+    if compressed.len() >= length - 8 {
+        buf.move_cursor_to_start();
+        return Ok(buf);
+    }
+
+    let mut packet = MByteBuffer::new()?;
+
+    packet.write(compressed.len() as u64 | COMPRESSED_FLAG)?;
+    packet.write_slice(&compressed)?;
+    packet.move_cursor_to_start();
+
+    if let Some(id) = id {
+        storage
+            .compression_metrics
+            .borrow_mut()
+            .add(id, length, packet.length());
+    }
+
+    Ok(packet)
+}
+
+/// A packet going out to many Clients. It is compressed the first time a Client that
+/// wants compression gets it, and that copy is shared with the rest.
+pub struct BroadcastPacket {
+    plain: MByteBuffer,
+    compressed: Option<MByteBuffer>,
+}
+
+impl BroadcastPacket {
+    pub fn new(buf: MByteBuffer) -> Self {
+        Self {
+            plain: buf,
+            compressed: None,
+        }
+    }
+
+    /// The packet as it was built, before any compression.
+    pub fn plain(&mut self) -> &mut MByteBuffer {
+        &mut self.plain
+    }
+
+    /// A copy of the packet as this Client should get it.
+    pub fn for_client(&mut self, storage: &Storage, compression: bool) -> Result<MByteBuffer> {
+        if !compression || !storage.config.compression {
+            return Ok(self.plain.try_clone()?);
+        }
+
+        if self.compressed.is_none() {
+            self.compressed = Some(compress_packet(storage, self.plain.try_clone()?)?);
+        }
+
+        match &self.compressed {
+            Some(compressed) => Ok(compressed.try_clone()?),
+            None => Ok(self.plain.try_clone()?),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{
+    containers::{GlobalKey, IndexMap},
+    gametypes::*,
+    socket::{BINARY_FRAME_HEADER_MAX, ServerPackets},
+    tasks::{finish_cache, new_cache},
+};
+use mmap_bytey::{BUFFER_SIZE, MByteBuffer};
+
+/// Move and Dir updates held back from a Client that is over its soft send limit.
+/// Only the newest update of each entity is kept, and they go out together once
+/// the Client has caught up.
+#[derive(Debug, Default)]
+pub struct HeldUpdates {
+    moves: IndexMap<GlobalKey, Vec<u8>>,
+    dirs: IndexMap<GlobalKey, Vec<u8>>,
+}
+
+impl HeldUpdates {
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty() && self.dirs.is_empty()
+    }
+
+    /// Keeps the entries of a Move or Dir packet in place of older ones for the same entity.
+    pub fn hold(&mut self, buf: &mut MByteBuffer) -> Result<()> {
+        let id = ServerPackets::from_packet(buf);
+        let held = match id {
+            Some(ServerPackets::Move) => &mut self.moves,
+            Some(ServerPackets::Dir) => &mut self.dirs,
+            _ => return Ok(()),
+        };
+
+        for (entity, entry) in read_entries(buf, id)? {
+            held.shift_remove(&entity);
+            held.insert(entity, entry);
+        }
+
+        Ok(())
+    }
+
+    /// Drops held entries that a newer Move or Dir packet makes stale.
+    pub fn forget(&mut self, buf: &mut MByteBuffer) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let id = ServerPackets::from_packet(buf);
+
+        for (entity, _) in read_entries(buf, id)? {
+            // A Move carries the direction too.
+            if id == Some(ServerPackets::Move) {
+                self.moves.shift_remove(&entity);
+            }
+
+            self.dirs.shift_remove(&entity);
+        }
+
+        Ok(())
+    }
+
+    /// Builds the held entries back into Move and Dir packets and empties the hold.
+    pub fn take_packets(&mut self) -> Result<Vec<MByteBuffer>> {
+        let mut packets = Vec::new();
+
+        for (id, held) in [
+            (ServerPackets::Move, &mut self.moves),
+            (ServerPackets::Dir, &mut self.dirs),
+        ] {
+            if held.is_empty() {
+                continue;
+            }
+
+            let mut buffer = new_cache(id)?;
+            let mut count = 0;
+
+            for (_, entry) in held.drain(..) {
+                if entry.len() + buffer.length() > BUFFER_SIZE - BINARY_FRAME_HEADER_MAX {
+                    finish_cache(&mut buffer, count, false)?;
+                    packets.push(buffer);
+                    buffer = new_cache(id)?;
+                    count = 0;
+                }
+
+                buffer.write_slice(&entry)?;
+                count += 1;
+            }
+
+            finish_cache(&mut buffer, count, false)?;
+            packets.push(buffer);
+        }
+
+        Ok(packets)
+    }
+}
+
+/// Splits a Move or Dir packet into its entries, keyed by the entity each one is for.
+fn read_entries(
+    buf: &mut MByteBuffer,
+    id: Option<ServerPackets>,
+) -> Result<Vec<(GlobalKey, Vec<u8>)>> {
+    let mut entries = Vec::new();
+
+    if !matches!(id, Some(ServerPackets::Move | ServerPackets::Dir)) {
+        return Ok(entries);
+    }
+
+    // 8 bytes for Size + 2 bytes for Packet ID enum to get to the count.
+    buf.move_cursor(10)?;
+    let count = buf.read::<u32>()?;
+
+    for _ in 0..count {
+        let start = buf.cursor();
+        let entity = buf.read::<GlobalKey>()?;
+
+        if id == Some(ServerPackets::Move) {
+            buf.read::<Position>()?;
+            buf.read::<bool>()?;
+            buf.read::<bool>()?;
+        }
+
+        buf.read::<u8>()?;
+
+        let end = buf.cursor();
+        entries.push((entity, buf.slice_from(start, end - start)?.to_vec()));
+    }
+
+    buf.move_cursor_to_start();
+    Ok(entries)
+}
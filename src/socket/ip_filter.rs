@@ -0,0 +1,181 @@
+use crate::containers::Config;
+use log::error;
+use std::{net::IpAddr, str::FromStr};
+
+/// An IP range such as `10.0.0.0/8` or `2001:db8::/32`. A plain IP is treated as a single address.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IpCidr {
+    pub addr: IpAddr,
+    pub prefix: u8,
+}
+
+impl IpCidr {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(range) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(range) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpCidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+
+        let addr = IpAddr::from_str(addr.trim())
+            .map_err(|e| format!("Invalid IP in {}: {}", s, e))?
+            .to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .trim()
+                .parse::<u8>()
+                .map_err(|e| format!("Invalid prefix in {}: {}", s, e))?,
+            None => max,
+        };
+
+        if prefix > max {
+            return Err(format!("Prefix in {} is larger than {}", s, max));
+        }
+
+        Ok(IpCidr { addr, prefix })
+    }
+}
+
+/// Allow and deny lists for new connections. Deny always wins and
+/// an empty allow list lets every address through.
+#[derive(Clone, Debug, Default)]
+pub struct IpFilter {
+    pub allow: Vec<IpCidr>,
+    pub deny: Vec<IpCidr>,
+}
+
+impl IpFilter {
+    pub fn new(config: &Config) -> Self {
+        let parse = |list: &[String]| {
+            list.iter()
+                .filter_map(|range| match range.parse::<IpCidr>() {
+                    Ok(cidr) => Some(cidr),
+                    Err(e) => {
+                        error!("Skipping IP range: {}", e);
+                        None
+                    }
+                })
+                .collect()
+        };
+
+        IpFilter {
+            allow: parse(&config.ip_allow),
+            deny: parse(&config.ip_deny),
+        }
+    }
+
+    pub fn is_allowed(&self, ip: &IpAddr) -> bool {
+        if self.deny.iter().any(|range| range.contains(ip)) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.iter().any(|range| range.contains(ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(range: &str) -> IpCidr {
+        range.parse().expect("valid range")
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().expect("valid ip")
+    }
+
+    #[test]
+    fn prefix_zero_contains_every_address_of_its_family() {
+        assert!(cidr("0.0.0.0/0").contains(&ip("1.2.3.4")));
+        assert!(cidr("0.0.0.0/0").contains(&ip("255.255.255.255")));
+        assert!(cidr("::/0").contains(&ip("2001:db8::1")));
+        assert!(!cidr("0.0.0.0/0").contains(&ip("2001:db8::1")));
+        assert!(!cidr("::/0").contains(&ip("1.2.3.4")));
+    }
+
+    #[test]
+    fn full_prefix_contains_only_its_address() {
+        assert!(cidr("10.0.0.1/32").contains(&ip("10.0.0.1")));
+        assert!(!cidr("10.0.0.1/32").contains(&ip("10.0.0.2")));
+        assert!(cidr("2001:db8::1/128").contains(&ip("2001:db8::1")));
+        assert!(!cidr("2001:db8::1/128").contains(&ip("2001:db8::2")));
+    }
+
+    #[test]
+    fn plain_address_is_a_full_prefix() {
+        assert_eq!(cidr("10.0.0.1"), cidr("10.0.0.1/32"));
+        assert_eq!(cidr("2001:db8::1"), cidr("2001:db8::1/128"));
+    }
+
+    #[test]
+    fn partial_prefix_masks_host_bits() {
+        let range = cidr("192.168.1.77/24");
+
+        assert!(range.contains(&ip("192.168.1.0")));
+        assert!(range.contains(&ip("192.168.1.255")));
+        assert!(!range.contains(&ip("192.168.2.1")));
+        assert!(cidr("2001:db8::/32").contains(&ip("2001:db8:ffff::1")));
+        assert!(!cidr("2001:db8::/32").contains(&ip("2001:db9::1")));
+    }
+
+    #[test]
+    fn ipv4_mapped_ipv6_is_treated_as_ipv4() {
+        assert_eq!(cidr("::ffff:10.0.0.1"), cidr("10.0.0.1/32"));
+        assert!(cidr("10.0.0.0/8").contains(&ip("::ffff:10.1.2.3")));
+        assert!(!cidr("10.0.0.0/8").contains(&ip("::ffff:11.1.2.3")));
+    }
+
+    #[test]
+    fn prefix_larger_than_the_maximum_is_rejected() {
+        assert!("10.0.0.0/33".parse::<IpCidr>().is_err());
+        assert!("2001:db8::/129".parse::<IpCidr>().is_err());
+        // Mapped addresses become IPv4, so their limit is 32.
+        assert!("::ffff:10.0.0.1/64".parse::<IpCidr>().is_err());
+        assert!("10.0.0.0/x".parse::<IpCidr>().is_err());
+        assert!("10.0.0/8".parse::<IpCidr>().is_err());
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let filter = IpFilter {
+            allow: vec![cidr("10.0.0.0/8")],
+            deny: vec![cidr("10.0.0.5")],
+        };
+
+        assert!(filter.is_allowed(&ip("10.0.0.4")));
+        assert!(!filter.is_allowed(&ip("10.0.0.5")));
+        assert!(!filter.is_allowed(&ip("::ffff:10.0.0.5")));
+        assert!(!filter.is_allowed(&ip("11.0.0.1")));
+    }
+
+    #[test]
+    fn empty_allow_list_allows_everything_not_denied() {
+        let filter = IpFilter {
+            allow: Vec::new(),
+            deny: vec![cidr("192.168.0.0/16")],
+        };
+
+        assert!(filter.is_allowed(&ip("8.8.8.8")));
+        assert!(filter.is_allowed(&ip("2001:db8::1")));
+        assert!(!filter.is_allowed(&ip("192.168.4.4")));
+    }
+}
@@ -1,114 +1,258 @@
-use bytey::{ByteBufferRead, ByteBufferWrite};
-use mmap_bytey::{MByteBufferRead, MByteBufferWrite};
+use bytey::{ByteBuffer, ByteBufferRead, ByteBufferWrite};
+use mmap_bytey::{MByteBuffer, MByteBufferError, MByteBufferRead, MByteBufferWrite};
 use serde::{Deserialize, Serialize};
 
-#[derive(
-    Copy,
-    Clone,
-    Debug,
-    PartialEq,
-    Eq,
-    Serialize,
-    Deserialize,
-    ByteBufferRead,
-    ByteBufferWrite,
-    MByteBufferRead,
-    MByteBufferWrite,
-    Hash,
-)]
-pub enum ServerPackets {
-    OnlineCheck,
-    AlertMsg,
-    FltAlert,
-    HandShake,
-    LoginOk,
-    MapItems,
-    MyIndex,
-    Move,
-    MoveOk,
-    Warp,
-    Dir,
-    Vitals,
-    Attack,
-    Death,
-    PlayerData,
-    PlayerSpawn,
-    PlayerInv,
-    PlayerInvSlot,
-    PlayerStorage,
-    PlayerStorageSlot,
-    PlayerEquipment,
-    PlayerLevel,
-    PlayerMoney,
-    PlayerPk,
-    NpcData,
-    ChatMsg,
-    EntityUnload,
-    OpenStorage,
-    OpenShop,
-    ClearIsUsingType,
-    UpdateTradeItem,
-    UpdateTradeMoney,
-    InitTrade,
-    TradeStatus,
-    TradeRequest,
-    PlayItemSfx,
-    Damage,
-    Ping,
-    TlsHandShake,
-    ClearData,
+/// Creates a packet id enum where every variant has a fixed id that is sent over the wire.
+/// Ids must never be reused or changed once a client depends on them; only add new ones.
+macro_rules! packet_ids {
+    ($(#[$meta:meta])* pub enum $name:ident { $($variant:ident = $id:literal,)* }) => {
+        $(#[$meta])*
+        #[repr(u16)]
+        pub enum $name {
+            $($variant = $id,)*
+        }
+
+        // Fails to compile if an id is used twice.
+        const _: () = {
+            let ids = [$($id),*];
+            let mut i = 0;
+
+            while i < ids.len() {
+                let mut j = i + 1;
+
+                while j < ids.len() {
+                    assert!(ids[i] != ids[j], "duplicate packet id");
+                    j += 1;
+                }
+
+                i += 1;
+            }
+        };
+
+        impl $name {
+            pub const ALL: &[Self] = &[$(Self::$variant,)*];
+
+            pub const fn id(self) -> u16 {
+                self as u16
+            }
+
+            pub fn from_id(id: u16) -> Option<Self> {
+                match id {
+                    $($id => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl MByteBufferRead for $name {
+            fn read_from_mbuffer(buffer: &mut MByteBuffer) -> mmap_bytey::Result<Self> {
+                let id = buffer.read::<u16>()?;
+                Self::from_id(id).ok_or(MByteBufferError::OtherError {
+                    error: format!("Invalid id: {}", id),
+                })
+            }
+
+            fn read_from_mbuffer_le(buffer: &mut MByteBuffer) -> mmap_bytey::Result<Self> {
+                let id = buffer.read_le::<u16>()?;
+                Self::from_id(id).ok_or(MByteBufferError::OtherError {
+                    error: format!("Invalid id: {}", id),
+                })
+            }
+
+            fn read_from_mbuffer_be(buffer: &mut MByteBuffer) -> mmap_bytey::Result<Self> {
+                let id = buffer.read_be::<u16>()?;
+                Self::from_id(id).ok_or(MByteBufferError::OtherError {
+                    error: format!("Invalid id: {}", id),
+                })
+            }
+        }
+
+        impl MByteBufferWrite for $name {
+            fn write_to_mbuffer(&self, buffer: &mut MByteBuffer) -> mmap_bytey::Result<()> {
+                buffer.write(self.id())?;
+                Ok(())
+            }
+
+            fn write_to_mbuffer_le(&self, buffer: &mut MByteBuffer) -> mmap_bytey::Result<()> {
+                buffer.write_le(self.id())?;
+                Ok(())
+            }
+
+            fn write_to_mbuffer_be(&self, buffer: &mut MByteBuffer) -> mmap_bytey::Result<()> {
+                buffer.write_be(self.id())?;
+                Ok(())
+            }
+        }
+
+        impl MByteBufferWrite for &$name {
+            fn write_to_mbuffer(&self, buffer: &mut MByteBuffer) -> mmap_bytey::Result<()> {
+                (*self).write_to_mbuffer(buffer)
+            }
+
+            fn write_to_mbuffer_le(&self, buffer: &mut MByteBuffer) -> mmap_bytey::Result<()> {
+                (*self).write_to_mbuffer_le(buffer)
+            }
+
+            fn write_to_mbuffer_be(&self, buffer: &mut MByteBuffer) -> mmap_bytey::Result<()> {
+                (*self).write_to_mbuffer_be(buffer)
+            }
+        }
+
+        impl ByteBufferRead for $name {
+            fn read_from_bytey_buffer(buffer: &mut ByteBuffer) -> bytey::Result<Self> {
+                let id = buffer.read::<u16>()?;
+                Self::from_id(id).ok_or(bytey::ByteBufferError::OtherError {
+                    error: format!("Invalid id: {}", id),
+                })
+            }
+
+            fn read_from_bytey_buffer_le(buffer: &mut ByteBuffer) -> bytey::Result<Self> {
+                let id = buffer.read_le::<u16>()?;
+                Self::from_id(id).ok_or(bytey::ByteBufferError::OtherError {
+                    error: format!("Invalid id: {}", id),
+                })
+            }
+
+            fn read_from_bytey_buffer_be(buffer: &mut ByteBuffer) -> bytey::Result<Self> {
+                let id = buffer.read_be::<u16>()?;
+                Self::from_id(id).ok_or(bytey::ByteBufferError::OtherError {
+                    error: format!("Invalid id: {}", id),
+                })
+            }
+        }
+
+        impl ByteBufferWrite for $name {
+            fn write_to_bytey_buffer(&self, buffer: &mut ByteBuffer) -> bytey::Result<()> {
+                buffer.write(self.id())?;
+                Ok(())
+            }
+
+            fn write_to_bytey_buffer_le(&self, buffer: &mut ByteBuffer) -> bytey::Result<()> {
+                buffer.write_le(self.id())?;
+                Ok(())
+            }
+
+            fn write_to_bytey_buffer_be(&self, buffer: &mut ByteBuffer) -> bytey::Result<()> {
+                buffer.write_be(self.id())?;
+                Ok(())
+            }
+        }
+    };
+}
+
+packet_ids! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+    pub enum ServerPackets {
+        OnlineCheck = 1,
+        AlertMsg = 2,
+        FltAlert = 3,
+        HandShake = 4,
+        LoginOk = 5,
+        MapItems = 6,
+        MyIndex = 7,
+        Move = 8,
+        MoveOk = 9,
+        Warp = 10,
+        Dir = 11,
+        Vitals = 12,
+        Attack = 13,
+        Death = 14,
+        PlayerData = 15,
+        PlayerSpawn = 16,
+        PlayerInv = 17,
+        PlayerInvSlot = 18,
+        PlayerStorage = 19,
+        PlayerStorageSlot = 20,
+        PlayerEquipment = 21,
+        PlayerLevel = 22,
+        PlayerMoney = 23,
+        PlayerPk = 24,
+        NpcData = 25,
+        ChatMsg = 26,
+        EntityUnload = 27,
+        OpenStorage = 28,
+        OpenShop = 29,
+        ClearIsUsingType = 30,
+        UpdateTradeItem = 31,
+        UpdateTradeMoney = 32,
+        InitTrade = 33,
+        TradeStatus = 34,
+        TradeRequest = 35,
+        PlayItemSfx = 36,
+        Damage = 37,
+        Ping = 38,
+        TlsHandShake = 39,
+        ClearData = 40,
+        PlayerBankMoney = 41,
+        PlayerStorageSlots = 42,
+        CharacterList = 43,
+        AccountStash = 44,
+        AccountStashSlot = 45,
+        ProtocolVersion = 46,
+    }
+}
+
+impl ServerPackets {
+    /// Reads the id from a finished packet. The cursor is left at the start.
+    pub fn from_packet(buf: &mut MByteBuffer) -> Option<Self> {
+        let id = buf.move_cursor(8).ok()?.read::<u16>().ok();
+
+        buf.move_cursor_to_start();
+        id.and_then(Self::from_id)
+    }
 }
 
-#[derive(
-    Copy,
-    Clone,
-    Debug,
-    PartialEq,
-    Eq,
-    Serialize,
-    Deserialize,
-    ByteBufferRead,
-    ByteBufferWrite,
-    Hash,
-    MByteBufferRead,
-    MByteBufferWrite,
-)]
-pub enum ClientPacket {
-    OnlineCheck,
-    Register,
-    Login,
-    HandShake,
-    Move,
-    Dir,
-    Attack,
-    UseItem,
-    Unequip,
-    SwitchInvSlot,
-    PickUp,
-    DropItem,
-    DeleteItem,
-    SwitchStorageSlot,
-    DeleteStorageItem,
-    DepositItem,
-    WithdrawItem,
-    Message,
-    Command,
-    SetTarget,
-    CloseStorage,
-    CloseShop,
-    CloseTrade,
-    BuyItem,
-    SellItem,
-    AddTradeItem,
-    RemoveTradeItem,
-    UpdateTradeMoney,
-    SubmitTrade,
-    AcceptTrade,
-    DeclineTrade,
-    Ping,
-    TlsReconnect,
-    TlsHandShake,
-    Reconnect,
-    Disconnect,
-    LoginOk,
+packet_ids! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, Hash)]
+    pub enum ClientPacket {
+        OnlineCheck = 1,
+        Register = 2,
+        Login = 3,
+        HandShake = 4,
+        Move = 5,
+        Dir = 6,
+        Attack = 7,
+        UseItem = 8,
+        Unequip = 9,
+        SwitchInvSlot = 10,
+        PickUp = 11,
+        DropItem = 12,
+        DeleteItem = 13,
+        SwitchStorageSlot = 14,
+        DeleteStorageItem = 15,
+        DepositItem = 16,
+        WithdrawItem = 17,
+        Message = 18,
+        Command = 19,
+        SetTarget = 20,
+        CloseStorage = 21,
+        CloseShop = 22,
+        CloseTrade = 23,
+        BuyItem = 24,
+        SellItem = 25,
+        AddTradeItem = 26,
+        RemoveTradeItem = 27,
+        UpdateTradeMoney = 28,
+        SubmitTrade = 29,
+        AcceptTrade = 30,
+        DeclineTrade = 31,
+        Ping = 32,
+        TlsReconnect = 33,
+        TlsHandShake = 34,
+        Reconnect = 35,
+        Disconnect = 36,
+        LoginOk = 37,
+        DepositMoney = 38,
+        WithdrawMoney = 39,
+        BuyStorageTab = 40,
+        SelectCharacter = 41,
+        CreateCharacter = 42,
+        DeleteCharacter = 43,
+        DepositStashItem = 44,
+        WithdrawStashItem = 45,
+        RestoreCharacter = 46,
+        RenameCharacter = 47,
+        VerifyEmail = 48,
+        ProtocolVersion = 49,
+    }
 }
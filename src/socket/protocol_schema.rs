@@ -0,0 +1,1160 @@
+use crate::{
+    containers::{DeathType, GlobalKey, UserAccess},
+    gametypes::*,
+    items::Item,
+    socket::{ClientPacket, ServerPackets},
+    tasks::*,
+};
+use mmap_bytey::MByteBuffer;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// How values are laid out on the wire. Sent along with the schema so client
+/// developers do not need to read the bytey source to decode a packet.
+const ENCODING: &[&str] = &[
+    "Every value is written in the servers native byte order, which is little endian on all supported targets.",
+    "A packet is a u64 length followed by a u16 packet id and the packet fields. If the high bit of the length is set the rest of the packet is LZ4 compressed with the uncompressed size prepended as a u32.",
+    "Batched packets have a u32 count after the packet id followed by that many entries of the layouts fields.",
+    "string: u64 byte length followed by UTF-8 bytes.",
+    "Option<T>: u8 1 followed by T when Some, u8 2 when None.",
+    "Vec<T> and [T; N]: u64 element count followed by the elements.",
+    "Range<T>: start T followed by end T.",
+    "Repeat<field, T>: T repeated by the value of the earlier field named field.",
+    "Enums: u16 variant tag starting at 1 followed by the variants fields.",
+];
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct LayoutSchema {
+    /// Function that writes or reads this layout.
+    pub source: &'static str,
+    pub batched: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<&'static str>,
+    pub fields: Vec<FieldSchema>,
+}
+
+impl LayoutSchema {
+    fn note(mut self, note: &'static str) -> Self {
+        self.note = Some(note);
+        self
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PacketSchema {
+    pub name: String,
+    pub id: u16,
+    pub layouts: Vec<LayoutSchema>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct VariantSchema {
+    pub name: &'static str,
+    pub tag: u16,
+    pub fields: Vec<FieldSchema>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum TypeSchema {
+    Struct { fields: Vec<FieldSchema> },
+    Enum { variants: Vec<VariantSchema> },
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProtocolSchema {
+    pub version: u16,
+    pub compatible: &'static [u16],
+    pub encoding: &'static [&'static str],
+    pub server: Vec<PacketSchema>,
+    pub client: Vec<PacketSchema>,
+    pub types: BTreeMap<&'static str, TypeSchema>,
+}
+
+macro_rules! fields {
+    ($($name:literal: $ty:expr),* $(,)?) => {
+        vec![$(FieldSchema { name: $name, ty: $ty.to_string() }),*]
+    };
+}
+
+fn layout(source: &'static str, fields: Vec<FieldSchema>) -> LayoutSchema {
+    LayoutSchema {
+        source,
+        batched: false,
+        note: None,
+        fields,
+    }
+}
+
+fn batched(source: &'static str, fields: Vec<FieldSchema>) -> LayoutSchema {
+    LayoutSchema {
+        source,
+        batched: true,
+        note: None,
+        fields,
+    }
+}
+
+fn vitals() -> String {
+    format!("[i32; {}]", VITALS_MAX)
+}
+
+fn variants(names: &[&'static str]) -> Vec<VariantSchema> {
+    names
+        .iter()
+        .zip(1..)
+        .map(|(name, tag)| VariantSchema {
+            name,
+            tag,
+            fields: Vec::new(),
+        })
+        .collect()
+}
+
+/// Layouts of a ServerPacket as written in sends.rs and datatask_builders.rs.
+/// No wildcard arm so a new packet will not compile until it is described here.
+pub fn server_layouts(packet: ServerPackets) -> Vec<LayoutSchema> {
+    match packet {
+        ServerPackets::OnlineCheck => vec![layout("send_ping", fields!["unused": "u64"])],
+        ServerPackets::AlertMsg => vec![layout(
+            "send_infomsg",
+            fields!["message": "string", "close_socket": "u8"],
+        )],
+        ServerPackets::FltAlert => vec![layout(
+            "send_fltalert",
+            fields!["ftltype": "FtlType", "message": "string"],
+        )],
+        ServerPackets::HandShake => vec![layout(
+            "send_codes",
+            fields!["code": "string", "handshake": "string"],
+        )],
+        ServerPackets::LoginOk => {
+            vec![layout("send_loginok", fields!["hour": "u32", "min": "u32"])]
+        }
+        ServerPackets::MapItems => vec![batched(
+            "map_item_packet",
+            fields![
+                "id": "GlobalKey",
+                "position": "Position",
+                "item": "Item",
+                "owner": "Option<GlobalKey>",
+                "did_spawn": "bool",
+            ],
+        )],
+        ServerPackets::MyIndex => vec![layout("send_myindex", fields!["entity": "GlobalKey"])],
+        ServerPackets::Move => vec![batched(
+            "move_packet",
+            fields![
+                "entity": "GlobalKey",
+                "position": "Position",
+                "warp": "bool",
+                "switch": "bool",
+                "dir": "u8",
+            ],
+        )],
+        ServerPackets::MoveOk => vec![layout("send_move_ok", fields!["move_ok": "bool"])],
+        ServerPackets::Warp => vec![batched(
+            "warp_packet",
+            fields!["entity": "GlobalKey", "position": "Position"],
+        )],
+        ServerPackets::Dir => vec![batched(
+            "dir_packet",
+            fields!["entity": "GlobalKey", "dir": "u8"],
+        )],
+        ServerPackets::Vitals => vec![batched(
+            "vitals_packet",
+            fields!["entity": "GlobalKey", "vital": vitals(), "vitalmax": vitals()],
+        )],
+        ServerPackets::Attack => vec![batched("attack_packet", fields!["entity": "GlobalKey"])],
+        ServerPackets::Death => vec![batched(
+            "death_packet",
+            fields!["entity": "GlobalKey", "life": "DeathType"],
+        )],
+        ServerPackets::PlayerData => vec![layout(
+            "send_playerdata",
+            fields![
+                "name": "string",
+                "user_access": "UserAccess",
+                "dir": "u8",
+                "equipment": "Equipment",
+                "level": "i32",
+                "death_type": "DeathType",
+                "damage": "u32",
+                "defense": "u32",
+                "position": "Position",
+                "pk": "bool",
+                "pvpon": "bool",
+                "sprite": "u8",
+                "vital": vitals(),
+                "vitalmax": vitals(),
+            ],
+        )],
+        ServerPackets::PlayerSpawn => vec![batched(
+            "player_spawn_packet",
+            fields![
+                "name": "string",
+                "dir": "u8",
+                "entity": "GlobalKey",
+                "level": "i32",
+                "death_type": "DeathType",
+                "damage": "u32",
+                "defense": "u32",
+                "position": "Position",
+                "sprite": "u16",
+                "vital": vitals(),
+                "vitalmax": vitals(),
+                "user_access": "UserAccess",
+                "equipment": "Equipment",
+                "pk": "bool",
+                "pvpon": "bool",
+                "did_spawn": "bool",
+            ],
+        )],
+        ServerPackets::PlayerInv => vec![layout("send_inv", fields!["items": "Vec<Item>"])],
+        ServerPackets::PlayerInvSlot => vec![layout(
+            "send_invslot",
+            fields!["slot": "u64", "item": "Item"],
+        )],
+        ServerPackets::PlayerStorage => vec![layout(
+            "send_storage",
+            fields!["slots": "Range<u64>", "items": "Vec<Item>"],
+        )],
+        ServerPackets::PlayerStorageSlot => vec![layout(
+            "send_storageslot",
+            fields!["slot": "u64", "item": "Item"],
+        )],
+        ServerPackets::PlayerEquipment => vec![layout(
+            "send_equipment",
+            fields!["entity": "GlobalKey", "equipment": "Equipment"],
+        )],
+        ServerPackets::PlayerLevel => vec![
+            layout("send_level", fields!["level": "i32", "levelexp": "u64"])
+                .note("Sent only to the player it belongs to."),
+            batched(
+                "level_packet",
+                fields!["entity": "GlobalKey", "level": "i32", "levelexp": "u64"],
+            )
+            .note("Sent to the players on the surrounding maps."),
+        ],
+        ServerPackets::PlayerMoney => vec![layout("send_money", fields!["money": "u64"])],
+        ServerPackets::PlayerPk => vec![layout("send_pk", fields!["pk": "bool"])],
+        ServerPackets::NpcData => vec![batched(
+            "npc_spawn_packet",
+            fields![
+                "dir": "u8",
+                "entity": "GlobalKey",
+                "level": "i32",
+                "death_type": "DeathType",
+                "mode": "NpcMode",
+                "index": "u64",
+                "damage": "u32",
+                "defense": "u32",
+                "position": "Position",
+                "sprite": "u16",
+                "vital": vitals(),
+                "vitalmax": vitals(),
+                "did_spawn": "bool",
+            ],
+        )],
+        ServerPackets::ChatMsg => vec![
+            batched(
+                "message_packet",
+                fields![
+                    "channel": "MessageChannel",
+                    "head": "string",
+                    "msg": "string",
+                    "access": "Option<UserAccess>",
+                ],
+            )
+            .note("send_message writes private and direct messages with a count of 1."),
+        ],
+        ServerPackets::EntityUnload => vec![batched(
+            "unload_entity_packet",
+            fields!["entity": "GlobalKey"],
+        )],
+        ServerPackets::OpenStorage => vec![layout("send_openstorage", fields!["unused": "u32"])],
+        ServerPackets::OpenShop => vec![layout("send_openshop", fields!["shop_index": "u16"])],
+        ServerPackets::ClearIsUsingType => {
+            vec![layout("send_clearisusingtype", fields!["unused": "u16"])]
+        }
+        ServerPackets::UpdateTradeItem => vec![layout(
+            "send_updatetradeitem",
+            fields!["is_mine": "bool", "trade_slot": "u16", "item": "Item"],
+        )],
+        ServerPackets::UpdateTradeMoney => {
+            vec![layout("send_updatetrademoney", fields!["money": "u64"])]
+        }
+        ServerPackets::InitTrade => vec![layout("send_inittrade", fields!["target": "GlobalKey"])],
+        ServerPackets::TradeStatus => vec![layout(
+            "send_tradestatus",
+            fields!["my_status": "TradeStatus", "their_status": "TradeStatus"],
+        )],
+        ServerPackets::TradeRequest => {
+            vec![layout("send_traderequest", fields!["entity": "GlobalKey"])]
+        }
+        ServerPackets::PlayItemSfx => {
+            vec![layout("send_playitemsfx", fields!["item_index": "u16"])]
+        }
+        ServerPackets::Damage => vec![batched(
+            "damage_packet",
+            fields![
+                "entity": "GlobalKey",
+                "damage": "u16",
+                "position": "Position",
+                "is_damage": "bool",
+            ],
+        )],
+        ServerPackets::Ping => vec![layout("send_gameping", fields!["unused": "u64"])],
+        ServerPackets::TlsHandShake => vec![layout(
+            "send_tls_codes",
+            fields!["code": "string", "handshake": "string"],
+        )],
+        ServerPackets::ClearData => vec![layout("send_clear_data", fields!["unused": "u32"])],
+        ServerPackets::PlayerBankMoney => vec![layout("send_bank_money", fields!["money": "u64"])],
+        ServerPackets::PlayerStorageSlots => {
+            vec![layout("send_storage_slots", fields!["slots": "u16"])]
+        }
+        ServerPackets::CharacterList => vec![layout(
+            "send_character_list",
+            fields![
+                "max_characters": "u8",
+                "count": "u8",
+                "characters": "Repeat<count, CharacterSummary>",
+            ],
+        )],
+        ServerPackets::AccountStash => vec![layout("send_stash", fields!["items": "Vec<Item>"])],
+        ServerPackets::AccountStashSlot => vec![layout(
+            "send_stashslot",
+            fields!["slot": "u64", "item": "Item"],
+        )],
+        ServerPackets::ProtocolVersion => vec![
+            layout(
+                "send_protocol_version",
+                fields!["version": "u16", "accepted": "bool"],
+            )
+            .note("Sent to protocol 1 clients."),
+            layout(
+                "send_protocol_version",
+                fields!["version": "u16", "accepted": "bool", "compression": "bool"],
+            )
+            .note("Sent to protocol 2 and later clients."),
+        ],
+    }
+}
+
+/// Layouts of a ClientPacket as read by its handler.
+/// No wildcard arm so a new packet will not compile until it is described here.
+pub fn client_layouts(packet: ClientPacket) -> Vec<LayoutSchema> {
+    match packet {
+        ClientPacket::OnlineCheck => vec![layout("handle_data", Vec::new())],
+        ClientPacket::Register => vec![layout(
+            "handle_register",
+            fields![
+                "username": "string",
+                "password": "string",
+                "email": "string",
+                "sprite_id": "u8",
+            ],
+        )],
+        ClientPacket::Login => vec![layout(
+            "handle_login",
+            fields![
+                "username": "string",
+                "password": "string",
+                "reconnect_code": "string",
+            ],
+        )],
+        ClientPacket::HandShake => vec![layout("handle_handshake", fields!["handshake": "string"])],
+        ClientPacket::Move => vec![layout("handle_move", fields!["dir": "Option<u8>"])],
+        ClientPacket::Dir => vec![layout("handle_dir", fields!["dir": "u8"])],
+        ClientPacket::Attack => vec![layout(
+            "handle_attack",
+            fields!["dir": "u8", "target": "Option<GlobalKey>"],
+        )],
+        ClientPacket::UseItem => vec![layout("handle_useitem", fields!["slot": "u16"])],
+        ClientPacket::Unequip => vec![layout("handle_unequip", fields!["slot": "u16"])],
+        ClientPacket::SwitchInvSlot => vec![layout(
+            "handle_switchinvslot",
+            fields!["oldslot": "u16", "newslot": "u16", "amount": "u16"],
+        )],
+        ClientPacket::PickUp => vec![layout("handle_pickup", Vec::new())],
+        ClientPacket::DropItem => vec![layout(
+            "handle_dropitem",
+            fields!["slot": "u16", "amount": "u16"],
+        )],
+        ClientPacket::DeleteItem => vec![layout("handle_deleteitem", fields!["slot": "u16"])],
+        ClientPacket::SwitchStorageSlot => vec![layout(
+            "handle_switchstorageslot",
+            fields!["oldslot": "u16", "newslot": "u16", "amount": "u16"],
+        )],
+        ClientPacket::DeleteStorageItem => {
+            vec![layout("handle_deletestorageitem", fields!["slot": "u16"])]
+        }
+        ClientPacket::DepositItem => vec![layout(
+            "handle_deposititem",
+            fields!["inv_slot": "u16", "bank_slot": "u16", "amount": "u16"],
+        )],
+        ClientPacket::WithdrawItem => vec![layout(
+            "handle_withdrawitem",
+            fields!["inv_slot": "u16", "bank_slot": "u16", "amount": "u16"],
+        )],
+        ClientPacket::Message => vec![
+            layout(
+                "handle_message",
+                fields!["channel": "MessageChannel", "msg": "string", "name": "string"],
+            )
+            .note("name is only used by the Private channel."),
+        ],
+        ClientPacket::Command => vec![layout("handle_command", fields!["command": "Command"])],
+        ClientPacket::SetTarget => vec![layout(
+            "handle_settarget",
+            fields!["target": "Option<GlobalKey>"],
+        )],
+        ClientPacket::CloseStorage => vec![layout("handle_closestorage", Vec::new())],
+        ClientPacket::CloseShop => vec![layout("handle_closeshop", Vec::new())],
+        ClientPacket::CloseTrade => vec![layout("handle_closetrade", Vec::new())],
+        ClientPacket::BuyItem => vec![layout("handle_buyitem", fields!["slot": "u16"])],
+        ClientPacket::SellItem => vec![layout(
+            "handle_sellitem",
+            fields!["slot": "u16", "amount": "u16"],
+        )],
+        ClientPacket::AddTradeItem => vec![layout(
+            "handle_addtradeitem",
+            fields!["slot": "u16", "amount": "u16"],
+        )],
+        ClientPacket::RemoveTradeItem => vec![layout(
+            "handle_removetradeitem",
+            fields!["slot": "u16", "amount": "u64"],
+        )],
+        ClientPacket::UpdateTradeMoney => {
+            vec![layout("handle_updatetrademoney", fields!["amount": "u64"])]
+        }
+        ClientPacket::SubmitTrade => vec![layout("handle_submittrade", Vec::new())],
+        ClientPacket::AcceptTrade => vec![layout("handle_accepttrade", Vec::new())],
+        ClientPacket::DeclineTrade => vec![layout("handle_declinetrade", Vec::new())],
+        ClientPacket::Ping => vec![layout("handle_ping", Vec::new())],
+        ClientPacket::TlsReconnect => vec![layout(
+            "handle_tls_reconnect",
+            fields!["connection_code": "string"],
+        )],
+        ClientPacket::TlsHandShake => vec![layout(
+            "handle_tls_handshake",
+            fields!["handshake": "string"],
+        )],
+        ClientPacket::Reconnect => vec![layout(
+            "handle_reconnect",
+            fields!["connection_code": "string"],
+        )],
+        ClientPacket::Disconnect => vec![layout("handle_disconnect", fields!["unused": "u32"])],
+        ClientPacket::LoginOk => vec![layout(
+            "handle_login_ok",
+            fields!["connection_code": "string"],
+        )],
+        ClientPacket::DepositMoney => vec![layout("handle_depositmoney", fields!["amount": "u64"])],
+        ClientPacket::WithdrawMoney => {
+            vec![layout("handle_withdrawmoney", fields!["amount": "u64"])]
+        }
+        ClientPacket::BuyStorageTab => vec![layout("handle_buystoragetab", Vec::new())],
+        ClientPacket::SelectCharacter => {
+            vec![layout("handle_selectcharacter", fields!["slot": "u8"])]
+        }
+        ClientPacket::CreateCharacter => vec![layout(
+            "handle_createcharacter",
+            fields!["name": "string", "sprite_id": "u8"],
+        )],
+        ClientPacket::DeleteCharacter => vec![layout(
+            "handle_deletecharacter",
+            fields!["slot": "u8", "name": "string"],
+        )],
+        ClientPacket::DepositStashItem => vec![layout(
+            "handle_depositstashitem",
+            fields!["inv_slot": "u16", "stash_slot": "u16", "amount": "u16"],
+        )],
+        ClientPacket::WithdrawStashItem => vec![layout(
+            "handle_withdrawstashitem",
+            fields!["inv_slot": "u16", "stash_slot": "u16", "amount": "u16"],
+        )],
+        ClientPacket::RestoreCharacter => {
+            vec![layout("handle_restorecharacter", fields!["slot": "u8"])]
+        }
+        ClientPacket::RenameCharacter => {
+            vec![layout("handle_renamecharacter", fields!["name": "string"])]
+        }
+        ClientPacket::VerifyEmail => vec![layout(
+            "handle_verifyemail",
+            fields!["email": "string", "token": "string"],
+        )],
+        ClientPacket::ProtocolVersion => vec![
+            layout("handle_protocolversion", fields!["version": "u16"])
+                .note("Sent by protocol 1 clients."),
+            layout(
+                "handle_protocolversion",
+                fields!["version": "u16", "wants_compression": "bool"],
+            )
+            .note("Sent by protocol 2 and later clients."),
+        ],
+    }
+}
+
+/// Structs and enums used by the packet fields.
+pub fn protocol_types() -> BTreeMap<&'static str, TypeSchema> {
+    let mut types = BTreeMap::new();
+
+    types.insert(
+        "GlobalKey",
+        TypeSchema::Struct {
+            fields: fields!["key": "u64"],
+        },
+    );
+    types.insert(
+        "MapPosition",
+        TypeSchema::Struct {
+            fields: fields!["x": "i32", "y": "i32", "group": "i32"],
+        },
+    );
+    types.insert(
+        "Position",
+        TypeSchema::Struct {
+            fields: fields!["x": "i32", "y": "i32", "map": "MapPosition"],
+        },
+    );
+    types.insert(
+        "Item",
+        TypeSchema::Struct {
+            fields: fields![
+                "num": "u32",
+                "val": "u16",
+                "level": "u8",
+                "data": "[i16; 5]",
+            ],
+        },
+    );
+    types.insert(
+        "Equipment",
+        TypeSchema::Struct {
+            fields: fields!["items": "Vec<Item>"],
+        },
+    );
+    types.insert(
+        "CharacterSummary",
+        TypeSchema::Struct {
+            fields: fields![
+                "slot": "u8",
+                "name": "string",
+                "level": "i32",
+                "sprite": "u16",
+                "deleted": "bool",
+                "restore_seconds_left": "i64",
+            ],
+        },
+    );
+    types.insert(
+        "UserAccess",
+        TypeSchema::Enum {
+            variants: variants(&["None", "Monitor", "Admin"]),
+        },
+    );
+    types.insert(
+        "DeathType",
+        TypeSchema::Enum {
+            variants: variants(&["Alive", "Spirit", "Dead", "Spawning"]),
+        },
+    );
+    types.insert(
+        "NpcMode",
+        TypeSchema::Enum {
+            variants: variants(&["None", "Normal", "Pet", "Summon", "Boss"]),
+        },
+    );
+    types.insert(
+        "TradeStatus",
+        TypeSchema::Enum {
+            variants: variants(&["None", "Accepted", "Submitted"]),
+        },
+    );
+    types.insert(
+        "FtlType",
+        TypeSchema::Enum {
+            variants: variants(&["Message", "Error", "Item", "Quest", "Level", "Money"]),
+        },
+    );
+    types.insert(
+        "MessageChannel",
+        TypeSchema::Enum {
+            variants: variants(&[
+                "Map", "Global", "Trade", "Party", "Private", "Guild", "Help", "Quest", "Npc",
+            ]),
+        },
+    );
+
+    let command = Command::VARIANTS
+        .iter()
+        .zip(1..)
+        .map(|((name, fields), tag)| VariantSchema {
+            name,
+            tag,
+            fields: fields
+                .iter()
+                .map(|(name, ty)| FieldSchema {
+                    name,
+                    ty: match *ty {
+                        "String" => "string".to_owned(),
+                        ty => ty.to_owned(),
+                    },
+                })
+                .collect(),
+        })
+        .collect();
+    types.insert("Command", TypeSchema::Enum { variants: command });
+
+    types
+}
+
+pub fn protocol_schema() -> ProtocolSchema {
+    ProtocolSchema {
+        version: PROTOCOL_VERSION,
+        compatible: COMPATIBLE_PROTOCOLS,
+        encoding: ENCODING,
+        server: ServerPackets::ALL
+            .iter()
+            .map(|packet| PacketSchema {
+                name: format!("{:?}", packet),
+                id: packet.id(),
+                layouts: server_layouts(*packet),
+            })
+            .collect(),
+        client: ClientPacket::ALL
+            .iter()
+            .map(|packet| PacketSchema {
+                name: format!("{:?}", packet),
+                id: packet.id(),
+                layouts: client_layouts(*packet),
+            })
+            .collect(),
+        types: protocol_types(),
+    }
+}
+
+/// Walks a buffer using the schema. Used to check the schema against the real packet builders.
+struct SchemaReader<'a> {
+    types: &'a BTreeMap<&'static str, TypeSchema>,
+    // Integer fields read so far so Repeat can find its count.
+    counts: Vec<(&'static str, u64)>,
+}
+
+impl SchemaReader<'_> {
+    fn read_fields(&mut self, buf: &mut MByteBuffer, fields: &[FieldSchema]) -> Result<()> {
+        for field in fields {
+            if let Some(value) = self.read_type(buf, &field.ty)? {
+                self.counts.push((field.name, value));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the value of unsigned integers so they can be used as a Repeat count.
+    fn read_type(&mut self, buf: &mut MByteBuffer, ty: &str) -> Result<Option<u64>> {
+        match ty {
+            "bool" => {
+                buf.read::<u8>()?;
+            }
+            "u8" => return Ok(Some(buf.read::<u8>()? as u64)),
+            "u16" => return Ok(Some(buf.read::<u16>()? as u64)),
+            "u32" => return Ok(Some(buf.read::<u32>()? as u64)),
+            "u64" => return Ok(Some(buf.read::<u64>()?)),
+            "i16" => {
+                buf.read::<i16>()?;
+            }
+            "i32" => {
+                buf.read::<i32>()?;
+            }
+            "i64" => {
+                buf.read::<i64>()?;
+            }
+            "string" => {
+                let len = buf.read::<u64>()? as usize;
+                std::str::from_utf8(buf.read_slice(len)?)?;
+            }
+            _ => self.read_composite(buf, ty)?,
+        }
+
+        Ok(None)
+    }
+
+    fn read_composite(&mut self, buf: &mut MByteBuffer, ty: &str) -> Result<()> {
+        if let Some(inner) = generic(ty, "Option<") {
+            match buf.read::<u8>()? {
+                1 => {
+                    self.read_type(buf, inner)?;
+                }
+                2 => {}
+                _ => return Err(mismatch(ty, "invalid Option flag")),
+            }
+        } else if let Some(inner) = generic(ty, "Vec<") {
+            for _ in 0..buf.read::<u64>()? {
+                self.read_type(buf, inner)?;
+            }
+        } else if let Some(inner) = generic(ty, "Range<") {
+            self.read_type(buf, inner)?;
+            self.read_type(buf, inner)?;
+        } else if let Some(inner) = generic(ty, "Repeat<") {
+            let (count, inner) = inner
+                .split_once(", ")
+                .ok_or_else(|| mismatch(ty, "Repeat is missing its count"))?;
+            let count = self
+                .counts
+                .iter()
+                .rev()
+                .find(|(name, _)| *name == count)
+                .map(|(_, value)| *value)
+                .ok_or_else(|| mismatch(ty, "Repeat count was not read"))?;
+
+            for _ in 0..count {
+                self.read_type(buf, inner)?;
+            }
+        } else if let Some(inner) = ty.strip_prefix('[').and_then(|ty| ty.strip_suffix(']')) {
+            let (inner, len) = inner
+                .split_once("; ")
+                .and_then(|(inner, len)| Some((inner, len.parse::<u64>().ok()?)))
+                .ok_or_else(|| mismatch(ty, "array is missing its length"))?;
+
+            if buf.read::<u64>()? != len {
+                return Err(mismatch(ty, "array length does not match"));
+            }
+
+            for _ in 0..len {
+                self.read_type(buf, inner)?;
+            }
+        } else {
+            match self.types.get(ty) {
+                Some(TypeSchema::Struct { fields }) => self.read_fields(buf, fields)?,
+                Some(TypeSchema::Enum { variants }) => {
+                    let tag = buf.read::<u16>()?;
+                    let variant = variants
+                        .iter()
+                        .find(|variant| variant.tag == tag)
+                        .ok_or_else(|| mismatch(ty, "invalid enum tag"))?;
+
+                    self.read_fields(buf, &variant.fields)?;
+                }
+                None => return Err(mismatch(ty, "unknown type")),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn generic<'a>(ty: &'a str, prefix: &str) -> Option<&'a str> {
+    ty.strip_prefix(prefix).and_then(|ty| ty.strip_suffix('>'))
+}
+
+fn mismatch(name: &str, message: &str) -> AscendingError {
+    AscendingError::ProtocolSchema {
+        name: name.to_owned(),
+        message: message.to_owned(),
+    }
+}
+
+/// Encodes sample packets with the real builders and checks that the schema reads
+/// back exactly the bytes that were written. Run before the schema is exported.
+pub fn verify_protocol_schema(schema: &ProtocolSchema) -> Result<()> {
+    let key = GlobalKey::default();
+    let position = Position::default();
+    let item = Item::new(1);
+    let samples = [
+        (
+            ServerPackets::Move,
+            "move_packet",
+            move_packet(key, position, true, false, 2)?,
+        ),
+        (
+            ServerPackets::Warp,
+            "warp_packet",
+            warp_packet(key, position)?,
+        ),
+        (ServerPackets::Dir, "dir_packet", dir_packet(key, 3)?),
+        (
+            ServerPackets::Death,
+            "death_packet",
+            death_packet(key, DeathType::Spirit)?,
+        ),
+        (ServerPackets::Attack, "attack_packet", attack_packet(key)?),
+        (
+            ServerPackets::Vitals,
+            "vitals_packet",
+            vitals_packet(key, [1; VITALS_MAX], [2; VITALS_MAX])?,
+        ),
+        (
+            ServerPackets::EntityUnload,
+            "unload_entity_packet",
+            unload_entity_packet(key)?,
+        ),
+        (
+            ServerPackets::ChatMsg,
+            "message_packet",
+            message_packet(
+                MessageChannel::Private,
+                "[Head]".into(),
+                "Message".into(),
+                Some(UserAccess::Admin),
+            )?,
+        ),
+        (
+            ServerPackets::MapItems,
+            "map_item_packet",
+            map_item_packet(key, position, item, Some(key), true)?,
+        ),
+        (
+            ServerPackets::Damage,
+            "damage_packet",
+            damage_packet(key, 10, position, true)?,
+        ),
+        (
+            ServerPackets::PlayerLevel,
+            "level_packet",
+            level_packet(key, 5, 100)?,
+        ),
+    ];
+
+    for (packet, source, mut buf) in samples {
+        let name = format!("{:?}", packet);
+        let layout = schema
+            .server
+            .iter()
+            .find(|schema| schema.id == packet.id())
+            .and_then(|schema| schema.layouts.iter().find(|l| l.source == source))
+            .ok_or_else(|| mismatch(&name, "layout is missing"))?;
+
+        read_layout(&schema.types, &name, layout, &mut buf)?;
+    }
+
+    Ok(())
+}
+
+/// Reads a sample with a layout and checks that the layout covers exactly its bytes.
+fn read_layout(
+    types: &BTreeMap<&'static str, TypeSchema>,
+    name: &str,
+    layout: &LayoutSchema,
+    buf: &mut MByteBuffer,
+) -> Result<()> {
+    let mut reader = SchemaReader {
+        types,
+        counts: Vec::new(),
+    };
+
+    buf.move_cursor_to_start();
+    reader
+        .read_fields(buf, &layout.fields)
+        .map_err(|e| match e {
+            AscendingError::MByteyError { .. } => {
+                mismatch(name, "schema is longer than the packet")
+            }
+            e => e,
+        })?;
+
+    if buf.cursor() != buf.length() {
+        return Err(mismatch(name, "schema is shorter than the packet"));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::containers::{Equipment, NpcMode, TradeStatus};
+
+    macro_rules! sample {
+        () => {
+            MByteBuffer::new()?
+        };
+        ($($value:expr),+ $(,)?) => {{
+            let mut buf = MByteBuffer::new()?;
+            $(buf.write($value)?;)*
+            buf
+        }};
+    }
+
+    /// One sample per layout of a ServerPacket, in layout order, written with the same
+    /// types as the function named by the layout.
+    fn server_samples(packet: ServerPackets) -> Result<Vec<MByteBuffer>> {
+        let key = GlobalKey::default();
+        let position = Position::default();
+        let item = Item::new(1);
+        let items = vec![item; 3];
+        let vital = [1; VITALS_MAX];
+
+        Ok(match packet {
+            ServerPackets::OnlineCheck => vec![sample![0u64]],
+            ServerPackets::AlertMsg => vec![sample!["Alert".to_owned(), 1u8]],
+            ServerPackets::FltAlert => vec![sample![FtlType::Error, "Alert".to_owned()]],
+            ServerPackets::HandShake => vec![sample!["code".to_owned(), "hand".to_owned()]],
+            ServerPackets::LoginOk => vec![sample![12u32, 30u32]],
+            ServerPackets::MapItems => {
+                vec![map_item_packet(key, position, item, Some(key), true)?]
+            }
+            ServerPackets::MyIndex => vec![sample![key]],
+            ServerPackets::Move => vec![move_packet(key, position, true, false, 2)?],
+            ServerPackets::MoveOk => vec![sample![true]],
+            ServerPackets::Warp => vec![warp_packet(key, position)?],
+            ServerPackets::Dir => vec![dir_packet(key, 3)?],
+            ServerPackets::Vitals => vec![vitals_packet(key, vital, vital)?],
+            ServerPackets::Attack => vec![attack_packet(key)?],
+            ServerPackets::Death => vec![death_packet(key, DeathType::Spirit)?],
+            ServerPackets::PlayerData => vec![sample![
+                "Name".to_owned(),
+                UserAccess::Admin,
+                2u8,
+                &Equipment::default(),
+                5i32,
+                DeathType::Alive,
+                10u32,
+                4u32,
+                position,
+                false,
+                true,
+                1u8,
+                vital,
+                vital,
+            ]],
+            ServerPackets::PlayerSpawn => vec![sample![
+                "Name".to_owned(),
+                2u8,
+                key,
+                5i32,
+                DeathType::Alive,
+                10u32,
+                4u32,
+                position,
+                1u16,
+                vital,
+                vital,
+                UserAccess::None,
+                &Equipment::default(),
+                false,
+                true,
+                true,
+            ]],
+            ServerPackets::PlayerInv => vec![sample![&items]],
+            ServerPackets::PlayerInvSlot => vec![sample![1usize, item]],
+            ServerPackets::PlayerStorage => vec![sample![0..3usize, &items[0..3]]],
+            ServerPackets::PlayerStorageSlot => vec![sample![1usize, item]],
+            ServerPackets::PlayerEquipment => vec![sample![key, &Equipment::default()]],
+            ServerPackets::PlayerLevel => {
+                vec![sample![5i32, 100u64], level_packet(key, 5, 100)?]
+            }
+            ServerPackets::PlayerMoney => vec![sample![100u64]],
+            ServerPackets::PlayerPk => vec![sample![true]],
+            ServerPackets::NpcData => vec![sample![
+                2u8,
+                key,
+                5i32,
+                DeathType::Alive,
+                NpcMode::Boss,
+                3u64,
+                10u32,
+                4u32,
+                position,
+                1u16,
+                vital,
+                vital,
+                true,
+            ]],
+            ServerPackets::ChatMsg => vec![message_packet(
+                MessageChannel::Private,
+                "[Head]".into(),
+                "Message".into(),
+                Some(UserAccess::Admin),
+            )?],
+            ServerPackets::EntityUnload => vec![unload_entity_packet(key)?],
+            ServerPackets::OpenStorage => vec![sample![1u32]],
+            ServerPackets::OpenShop => vec![sample![2u16]],
+            ServerPackets::ClearIsUsingType => vec![sample![1u16]],
+            ServerPackets::UpdateTradeItem => vec![sample![true, 3u16, item]],
+            ServerPackets::UpdateTradeMoney => vec![sample![100u64]],
+            ServerPackets::InitTrade => vec![sample![key]],
+            ServerPackets::TradeStatus => {
+                vec![sample![TradeStatus::Accepted, TradeStatus::Submitted]]
+            }
+            ServerPackets::TradeRequest => vec![sample![key]],
+            ServerPackets::PlayItemSfx => vec![sample![4u16]],
+            ServerPackets::Damage => vec![damage_packet(key, 10, position, true)?],
+            ServerPackets::Ping => vec![sample![0u64]],
+            ServerPackets::TlsHandShake => vec![sample!["code".to_owned(), "hand".to_owned()]],
+            ServerPackets::ClearData => vec![sample![0u32]],
+            ServerPackets::PlayerBankMoney => vec![sample![100u64]],
+            ServerPackets::PlayerStorageSlots => vec![sample![70u16]],
+            ServerPackets::CharacterList => vec![sample![
+                MAX_CHARACTERS as u8,
+                2u8,
+                0u8,
+                "First".to_owned(),
+                5i32,
+                1u16,
+                false,
+                0i64,
+                1u8,
+                "Second".to_owned(),
+                1i32,
+                2u16,
+                true,
+                3600i64,
+            ]],
+            ServerPackets::AccountStash => vec![sample![&items]],
+            ServerPackets::AccountStashSlot => vec![sample![1usize, item]],
+            ServerPackets::ProtocolVersion => vec![
+                sample![PROTOCOL_VERSION, true],
+                sample![PROTOCOL_VERSION, true, true],
+            ],
+        })
+    }
+
+    /// One sample per layout of a ClientPacket, in layout order, written with the same
+    /// types as the handler named by the layout reads.
+    fn client_samples(packet: ClientPacket) -> Result<Vec<MByteBuffer>> {
+        let key = GlobalKey::default();
+        let name = || "Name".to_owned();
+
+        Ok(match packet {
+            ClientPacket::OnlineCheck => vec![sample![]],
+            ClientPacket::Register => vec![sample![name(), name(), name(), 1u8]],
+            ClientPacket::Login => vec![sample![name(), name(), name()]],
+            ClientPacket::HandShake => vec![sample![name()]],
+            ClientPacket::Move => vec![sample![Some(2u8)]],
+            ClientPacket::Dir => vec![sample![2u8]],
+            ClientPacket::Attack => vec![sample![2u8, Some(key)]],
+            ClientPacket::UseItem => vec![sample![1u16]],
+            ClientPacket::Unequip => vec![sample![1u16]],
+            ClientPacket::SwitchInvSlot => vec![sample![1u16, 2u16, 3u16]],
+            ClientPacket::PickUp => vec![sample![]],
+            ClientPacket::DropItem => vec![sample![1u16, 2u16]],
+            ClientPacket::DeleteItem => vec![sample![1u16]],
+            ClientPacket::SwitchStorageSlot => vec![sample![1u16, 2u16, 3u16]],
+            ClientPacket::DeleteStorageItem => vec![sample![1u16]],
+            ClientPacket::DepositItem => vec![sample![1u16, 2u16, 3u16]],
+            ClientPacket::WithdrawItem => vec![sample![1u16, 2u16, 3u16]],
+            ClientPacket::Message => vec![sample![MessageChannel::Private, name(), name()]],
+            ClientPacket::Command => vec![sample![Command::SpawnNpc(1, Position::default())]],
+            ClientPacket::SetTarget => vec![sample![None::<GlobalKey>]],
+            ClientPacket::CloseStorage => vec![sample![]],
+            ClientPacket::CloseShop => vec![sample![]],
+            ClientPacket::CloseTrade => vec![sample![]],
+            ClientPacket::BuyItem => vec![sample![1u16]],
+            ClientPacket::SellItem => vec![sample![1u16, 2u16]],
+            ClientPacket::AddTradeItem => vec![sample![1u16, 2u16]],
+            ClientPacket::RemoveTradeItem => vec![sample![1u16, 2u64]],
+            ClientPacket::UpdateTradeMoney => vec![sample![100u64]],
+            ClientPacket::SubmitTrade => vec![sample![]],
+            ClientPacket::AcceptTrade => vec![sample![]],
+            ClientPacket::DeclineTrade => vec![sample![]],
+            ClientPacket::Ping => vec![sample![]],
+            ClientPacket::TlsReconnect => vec![sample![name()]],
+            ClientPacket::TlsHandShake => vec![sample![name()]],
+            ClientPacket::Reconnect => vec![sample![name()]],
+            ClientPacket::Disconnect => vec![sample![0u32]],
+            ClientPacket::LoginOk => vec![sample![name()]],
+            ClientPacket::DepositMoney => vec![sample![100u64]],
+            ClientPacket::WithdrawMoney => vec![sample![100u64]],
+            ClientPacket::BuyStorageTab => vec![sample![]],
+            ClientPacket::SelectCharacter => vec![sample![1u8]],
+            ClientPacket::CreateCharacter => vec![sample![name(), 1u8]],
+            ClientPacket::DeleteCharacter => vec![sample![1u8, name()]],
+            ClientPacket::DepositStashItem => vec![sample![1u16, 2u16, 3u16]],
+            ClientPacket::WithdrawStashItem => vec![sample![1u16, 2u16, 3u16]],
+            ClientPacket::RestoreCharacter => vec![sample![1u8]],
+            ClientPacket::RenameCharacter => vec![sample![name()]],
+            ClientPacket::VerifyEmail => vec![sample![name(), name()]],
+            ClientPacket::ProtocolVersion => {
+                vec![sample![PROTOCOL_VERSION], sample![PROTOCOL_VERSION, true]]
+            }
+        })
+    }
+
+    fn check_packets(
+        packets: &[PacketSchema],
+        samples: impl Fn(&PacketSchema) -> Result<Vec<MByteBuffer>>,
+    ) -> Result<()> {
+        let types = protocol_types();
+
+        for packet in packets {
+            let samples = samples(packet)?;
+            assert_eq!(
+                samples.len(),
+                packet.layouts.len(),
+                "{} needs one sample per layout",
+                packet.name
+            );
+
+            for (layout, mut buf) in packet.layouts.iter().zip(samples) {
+                read_layout(&types, &packet.name, layout, &mut buf)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn server_layouts_match_their_packets() -> Result<()> {
+        let schema = protocol_schema();
+
+        check_packets(&schema.server, |packet| {
+            server_samples(ServerPackets::from_id(packet.id).expect("packet id"))
+        })
+    }
+
+    #[test]
+    fn client_layouts_match_their_packets() -> Result<()> {
+        let schema = protocol_schema();
+
+        check_packets(&schema.client, |packet| {
+            client_samples(ClientPacket::from_id(packet.id).expect("packet id"))
+        })
+    }
+
+    #[test]
+    fn command_variants_match_their_encoding() -> Result<()> {
+        let types = protocol_types();
+        let layout = layout("handle_command", fields!["command": "Command"]);
+        let position = Position::default();
+        let commands = [
+            Command::KickPlayer,
+            Command::KickPlayerByName("Name".into()),
+            Command::WarpTo(position),
+            Command::SpawnNpc(1, position),
+            Command::Trade,
+            Command::ReloadTls,
+        ];
+        let mut tags = Vec::new();
+
+        for command in commands {
+            let mut buf = sample![command];
+            read_layout(&types, "Command", &layout, &mut buf)?;
+
+            buf.move_cursor_to_start();
+            tags.push(buf.read::<u16>()?);
+        }
+
+        let Some(TypeSchema::Enum { variants }) = types.get("Command") else {
+            panic!("Command is missing from the types");
+        };
+        let schema_tags: Vec<u16> = variants.iter().map(|variant| variant.tag).collect();
+        assert_eq!(tags, schema_tags, "every Command variant needs a sample");
+
+        Ok(())
+    }
+
+    #[test]
+    fn builder_samples_match_the_schema() -> Result<()> {
+        verify_protocol_schema(&protocol_schema())
+    }
+}
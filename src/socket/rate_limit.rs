@@ -0,0 +1,245 @@
+use crate::{
+    containers::{Config, HashMap},
+    socket::ClientPacket,
+    time_ext::MyInstant,
+};
+use serde::{Deserialize, Serialize};
+
+/// Token bucket settings for a single ClientPacket.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct PacketLimit {
+    /// Packets allowed per second once the burst is used up.
+    pub rate: f32,
+    /// Packets that can be sent at once.
+    pub burst: f32,
+}
+
+pub fn default_packet_limits() -> HashMap<ClientPacket, PacketLimit> {
+    [
+        (ClientPacket::Move, 20.0, 30.0),
+        (ClientPacket::Dir, 20.0, 30.0),
+        (ClientPacket::Attack, 10.0, 15.0),
+        (ClientPacket::Message, 2.0, 5.0),
+        (ClientPacket::Command, 2.0, 5.0),
+    ]
+    .into_iter()
+    .map(|(packet, rate, burst)| (packet, PacketLimit { rate, burst }))
+    .collect()
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TokenBucket {
+    pub tokens: f32,
+    pub last_update: MyInstant,
+}
+
+impl TokenBucket {
+    pub fn new(burst: f32, tick: MyInstant) -> Self {
+        Self {
+            tokens: burst,
+            last_update: tick,
+        }
+    }
+
+    /// Refills the bucket for the time passed then takes a token if one is left.
+    pub fn take(&mut self, rate: f32, burst: f32, tick: MyInstant) -> bool {
+        let elapsed = tick.0.saturating_duration_since(self.last_update.0);
+
+        self.tokens = (self.tokens + elapsed.as_secs_f32() * rate).min(burst);
+        self.last_update = tick;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RateLimitResult {
+    Allow,
+    Drop,
+    Disconnect,
+}
+
+/// Per Client packet counters. Strikes are added for every dropped packet and
+/// wear off at one per second. Too many strikes disconnects the Client.
+#[derive(Debug, Default)]
+pub struct PacketRateLimiter {
+    buckets: HashMap<ClientPacket, TokenBucket>,
+    strikes: f32,
+    last_strike: Option<MyInstant>,
+}
+
+impl PacketRateLimiter {
+    pub fn check(&mut self, config: &Config, id: ClientPacket, tick: MyInstant) -> RateLimitResult {
+        let limit = match config.packet_limits.get(&id) {
+            Some(limit) => limit,
+            None => return RateLimitResult::Allow,
+        };
+
+        let bucket = self
+            .buckets
+            .entry(id)
+            .or_insert_with(|| TokenBucket::new(limit.burst, tick));
+
+        if bucket.take(limit.rate, limit.burst, tick) {
+            return RateLimitResult::Allow;
+        }
+
+        if let Some(last_strike) = self.last_strike {
+            let elapsed = tick.0.saturating_duration_since(last_strike.0);
+            self.strikes = (self.strikes - elapsed.as_secs_f32()).max(0.0);
+        }
+
+        self.strikes += 1.0;
+        self.last_strike = Some(tick);
+
+        if self.strikes > config.packet_abuse_limit as f32 {
+            RateLimitResult::Disconnect
+        } else {
+            RateLimitResult::Drop
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::containers::test_config;
+    use std::time::Duration;
+
+    /// Ticks measured in seconds from one start.
+    struct Clock(MyInstant);
+
+    impl Clock {
+        fn new() -> Self {
+            Self(MyInstant::now())
+        }
+
+        fn at(&self, secs: f32) -> MyInstant {
+            MyInstant(self.0.0 + Duration::from_secs_f32(secs))
+        }
+    }
+
+    fn config(rate: f32, burst: f32, abuse_limit: u32) -> Config {
+        let mut config = test_config();
+
+        config.packet_abuse_limit = abuse_limit;
+        config.packet_limits = [(ClientPacket::Move, PacketLimit { rate, burst })]
+            .into_iter()
+            .collect();
+        config
+    }
+
+    #[test]
+    fn bucket_starts_full_and_empties() {
+        let clock = Clock::new();
+        let mut bucket = TokenBucket::new(3.0, clock.at(0.0));
+
+        assert!((0..3).all(|_| bucket.take(1.0, 3.0, clock.at(0.0))));
+        assert!(!bucket.take(1.0, 3.0, clock.at(0.0)));
+    }
+
+    #[test]
+    fn bucket_refills_at_its_rate() {
+        let clock = Clock::new();
+        let mut bucket = TokenBucket::new(1.0, clock.at(0.0));
+
+        assert!(bucket.take(4.0, 10.0, clock.at(0.0)));
+        assert!(!bucket.take(4.0, 10.0, clock.at(0.1)));
+        // 0.1s then 0.4s more at 4 per second is 2 tokens, less the failed take.
+        assert!(bucket.take(4.0, 10.0, clock.at(0.5)));
+        assert!(bucket.take(4.0, 10.0, clock.at(0.5)));
+        assert!(!bucket.take(4.0, 10.0, clock.at(0.5)));
+    }
+
+    #[test]
+    fn bucket_refill_is_capped_at_the_burst() {
+        let clock = Clock::new();
+        let mut bucket = TokenBucket::new(2.0, clock.at(0.0));
+
+        assert!(bucket.take(1.0, 2.0, clock.at(0.0)));
+        assert!(bucket.take(1.0, 2.0, clock.at(0.0)));
+
+        let later = clock.at(100.0);
+        assert!(bucket.take(1.0, 2.0, later));
+        assert!(bucket.take(1.0, 2.0, later));
+        assert!(!bucket.take(1.0, 2.0, later));
+    }
+
+    #[test]
+    fn bucket_does_not_refill_when_time_goes_back() {
+        let clock = Clock::new();
+        let mut bucket = TokenBucket::new(1.0, clock.at(5.0));
+
+        assert!(bucket.take(10.0, 5.0, clock.at(5.0)));
+        assert!(!bucket.take(10.0, 5.0, clock.at(1.0)));
+    }
+
+    #[test]
+    fn packets_without_a_limit_are_allowed() {
+        let clock = Clock::new();
+        let config = config(0.0, 0.0, 0);
+        let mut limiter = PacketRateLimiter::default();
+
+        for _ in 0..100 {
+            assert_eq!(
+                limiter.check(&config, ClientPacket::Ping, clock.at(0.0)),
+                RateLimitResult::Allow
+            );
+        }
+    }
+
+    #[test]
+    fn disconnects_once_strikes_pass_the_abuse_limit() {
+        let clock = Clock::new();
+        let config = config(1.0, 2.0, 3);
+        let mut limiter = PacketRateLimiter::default();
+        let mut check = || limiter.check(&config, ClientPacket::Move, clock.at(0.0));
+
+        assert_eq!(check(), RateLimitResult::Allow);
+        assert_eq!(check(), RateLimitResult::Allow);
+        assert_eq!(check(), RateLimitResult::Drop);
+        assert_eq!(check(), RateLimitResult::Drop);
+        assert_eq!(check(), RateLimitResult::Drop);
+        assert_eq!(check(), RateLimitResult::Disconnect);
+    }
+
+    #[test]
+    fn strikes_wear_off_one_per_second() {
+        let clock = Clock::new();
+        let config = config(0.01, 1.0, 2);
+        let mut limiter = PacketRateLimiter::default();
+
+        assert_eq!(
+            limiter.check(&config, ClientPacket::Move, clock.at(0.0)),
+            RateLimitResult::Allow
+        );
+
+        // A dropped packet every second never builds up more than one strike.
+        for second in 1..20 {
+            assert_eq!(
+                limiter.check(&config, ClientPacket::Move, clock.at(second as f32)),
+                RateLimitResult::Drop
+            );
+        }
+
+        // The last strike has worn off, so three at once go past the limit of 2.
+        let tick = clock.at(20.0);
+
+        for _ in 0..2 {
+            assert_eq!(
+                limiter.check(&config, ClientPacket::Move, tick),
+                RateLimitResult::Drop
+            );
+        }
+
+        assert_eq!(
+            limiter.check(&config, ClientPacket::Move, tick),
+            RateLimitResult::Disconnect
+        );
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
     containers::{Entity, GlobalKey, Storage, TradeStatus, World},
     gametypes::*,
     socket::*,
+    sql::PGCharacterSummary,
     tasks::*,
 };
 
@@ -89,7 +90,7 @@ pub fn send_playerdata(
         let mut buf = MByteBuffer::new_packet()?;
 
         buf.write(ServerPackets::PlayerData)?;
-        buf.write(&data.account.username)?;
+        buf.write(&data.character.name)?;
         buf.write(data.user_access)?;
         buf.write(data.movement.dir)?;
         buf.write(&data.equipment)?;
@@ -225,6 +226,124 @@ pub fn send_storageslot(
     Ok(())
 }
 
+/// Sends the whole storage split into tab sized packets.
+#[inline]
+pub fn send_storage_tabs(world: &mut World, storage: &Storage, entity: GlobalKey) -> Result<()> {
+    let slots = if let Some(Entity::Player(data)) = world.get_opt_entity(entity) {
+        data.try_lock()?.storage.items.len()
+    } else {
+        return Ok(());
+    };
+
+    for start in (0..slots).step_by(STORAGE_TAB_SIZE) {
+        send_storage(
+            world,
+            storage,
+            entity,
+            start..(start + STORAGE_TAB_SIZE).min(slots),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[inline]
+pub fn send_storage_slots(world: &mut World, storage: &Storage, entity: GlobalKey) -> Result<()> {
+    if let Some(Entity::Player(data)) = world.get_opt_entity(entity) {
+        let data = data.try_lock()?;
+
+        let mut buf = MByteBuffer::new_packet()?;
+
+        buf.write(ServerPackets::PlayerStorageSlots)?;
+        buf.write(data.storage.items.len() as u16)?;
+        buf.finish()?;
+
+        send_to(storage, data.socket.id, buf)?;
+    }
+    Ok(())
+}
+
+#[inline]
+pub fn send_bank_money(world: &mut World, storage: &Storage, entity: GlobalKey) -> Result<()> {
+    if let Some(Entity::Player(data)) = world.get_opt_entity(entity) {
+        let data = data.try_lock()?;
+
+        let mut buf = MByteBuffer::new_packet()?;
+
+        buf.write(ServerPackets::PlayerBankMoney)?;
+        buf.write(data.bank_money.vals)?;
+        buf.finish()?;
+
+        send_to(storage, data.socket.id, buf)?;
+    }
+    Ok(())
+}
+
+#[inline]
+pub fn send_stash(world: &mut World, storage: &Storage, entity: GlobalKey) -> Result<()> {
+    if let Some(Entity::Player(data)) = world.get_opt_entity(entity) {
+        let data = data.try_lock()?;
+
+        let mut buf = MByteBuffer::new_packet()?;
+
+        buf.write(ServerPackets::AccountStash)?;
+        buf.write(&data.stash.items)?;
+        buf.finish()?;
+
+        send_to(storage, data.socket.id, buf)?;
+    }
+    Ok(())
+}
+
+#[inline]
+pub fn send_stashslot(
+    world: &mut World,
+    storage: &Storage,
+    entity: GlobalKey,
+    id: usize,
+) -> Result<()> {
+    if let Some(Entity::Player(data)) = world.get_opt_entity(entity) {
+        let data = data.try_lock()?;
+
+        let mut buf = MByteBuffer::new_packet()?;
+
+        buf.write(ServerPackets::AccountStashSlot)?;
+        buf.write(id)?;
+        buf.write(data.stash.items[id])?;
+        buf.finish()?;
+
+        send_to(storage, data.socket.id, buf)?;
+    }
+    Ok(())
+}
+
+/// Sends the Accounts Characters for the character select screen.
+#[inline]
+pub fn send_character_list(
+    storage: &Storage,
+    socket_id: Token,
+    characters: &[PGCharacterSummary],
+) -> Result<()> {
+    let mut buf = MByteBuffer::new_packet()?;
+
+    buf.write(ServerPackets::CharacterList)?;
+    buf.write(MAX_CHARACTERS as u8)?;
+    buf.write(characters.len() as u8)?;
+
+    for character in characters {
+        buf.write(character.slot as u8)?;
+        buf.write(&character.name)?;
+        buf.write(character.level.unwrap_or(1))?;
+        buf.write(character.sprite())?;
+        buf.write(character.deleted_on.is_some())?;
+        buf.write(character.restore_seconds_left(storage.config.character_restore_days))?;
+    }
+
+    buf.finish()?;
+
+    send_to(storage, socket_id, buf)
+}
+
 #[inline]
 pub fn send_equipment(world: &mut World, storage: &Storage, entity: GlobalKey) -> Result<()> {
     if let Some(Entity::Player(data)) = world.get_opt_entity(entity) {
@@ -569,6 +688,29 @@ pub fn send_playitemsfx(
     send_to(storage, socket_id, buf)
 }
 
+/// Lets the client know if its protocol version was accepted along with the servers version.
+#[inline]
+pub fn send_protocol_version(
+    storage: &Storage,
+    socket_id: Token,
+    accepted: bool,
+    compression: Option<bool>,
+) -> Result<()> {
+    let mut buf = MByteBuffer::new_packet()?;
+
+    buf.write(ServerPackets::ProtocolVersion)?;
+    buf.write(PROTOCOL_VERSION)?;
+    buf.write(accepted)?;
+
+    if let Some(compression) = compression {
+        buf.write(compression)?;
+    }
+
+    buf.finish()?;
+
+    send_to(storage, socket_id, buf)
+}
+
 #[inline]
 pub fn send_gameping(storage: &Storage, socket_id: Token) -> Result<()> {
     let mut buf = MByteBuffer::new_packet()?;
@@ -1,68 +1,129 @@
 use crate::{
-    containers::{HashMap, Storage, World},
-    gametypes::{MAX_SOCKET_PLAYERS, Result},
-    socket::{Client, ClientState},
+    containers::{Config, Entity, HashMap, Storage, World, build_tls_config, tls_files_modified},
+    gametypes::{LogType, MAX_SOCKET_PLAYERS, Result},
+    socket::{Client, ClientState, IpFilter, TokenBucket, WebSocket},
+    sql::{PGLog, sql_new_log},
+    time_ext::MyInstant,
 };
-use log::{trace, warn};
+use log::{error, info, trace, warn};
 use mio::{Events, Poll, net::TcpListener};
-use std::{cell::RefCell, collections::VecDeque, io, sync::Arc, time::Duration};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
 
-pub const SERVER: mio::Token = mio::Token(0);
-pub const TLS_SERVER: mio::Token = mio::Token(1);
+/// What a listener speaks. Every bind address gets its own listener and mio token.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ListenerKind {
+    Tcp,
+    Tls,
+    WebSocket,
+    WebSocketTls,
+}
 
-pub struct Server {
+impl ListenerKind {
+    pub fn is_tls(self) -> bool {
+        matches!(self, ListenerKind::Tls | ListenerKind::WebSocketTls)
+    }
+
+    pub fn is_websocket(self) -> bool {
+        matches!(self, ListenerKind::WebSocket | ListenerKind::WebSocketTls)
+    }
+}
+
+pub struct Listener {
     pub listener: TcpListener,
-    pub tls_listener: TcpListener,
+    pub kind: ListenerKind,
+    pub addr: SocketAddr,
+}
+
+pub struct Server {
+    // Indexed by their mio token. Client tokens start after the last listener.
+    pub listeners: Vec<Listener>,
     pub clients: HashMap<mio::Token, RefCell<Client>>,
     pub tokens: VecDeque<mio::Token>,
     pub tls_config: Arc<rustls::ServerConfig>,
+    // Modified time of the cert files tls_config was built from.
+    pub tls_modified: Option<SystemTime>,
+    pub ip_filter: IpFilter,
+    // Open sockets per IP.
+    pub ip_connections: HashMap<IpAddr, usize>,
+    // New connections per IP.
+    pub ip_rates: HashMap<IpAddr, TokenBucket>,
 }
 
 impl Server {
     #[inline]
     pub fn new(
         poll: &mut Poll,
-        addr: &str,
-        tls_addr: &str,
-        max: usize,
+        config: &Config,
         cfg: Arc<rustls::ServerConfig>,
+        ip_filter: IpFilter,
     ) -> Result<Server> {
+        /* Set up the TCP listeners. */
+        let mut listeners = Vec::new();
+
+        for (addrs, kind) in [
+            (&config.listen, ListenerKind::Tcp),
+            (&config.tls_listen, ListenerKind::Tls),
+            (&config.ws_listen, ListenerKind::WebSocket),
+            (&config.wss_listen, ListenerKind::WebSocketTls),
+        ] {
+            for addr in addrs.iter().filter(|addr| !addr.is_empty()) {
+                let addr: SocketAddr = addr.parse()?;
+                let mut listener = bind_listener(addr)?;
+                let token = mio::Token(listeners.len());
+
+                poll.registry()
+                    .register(&mut listener, token, mio::Interest::READABLE)?;
+                info!("Listening for {:?} on {}", kind, addr);
+                listeners.push(Listener {
+                    listener,
+                    kind,
+                    addr,
+                });
+            }
+        }
+
         /* Create a bag of unique tokens. */
+        let max = config.maxconnections;
         let mut tokens = VecDeque::with_capacity(max);
 
-        for i in 2..max {
+        for i in listeners.len()..max {
             tokens.push_back(mio::Token(i));
         }
 
-        /* Set up the TCP listener. */
-        let addr = addr.parse()?;
-        let mut listener = TcpListener::bind(addr)?;
-
-        let tls_addr = tls_addr.parse()?;
-        let mut tls_listener = TcpListener::bind(tls_addr)?;
-
-        poll.registry()
-            .register(&mut listener, SERVER, mio::Interest::READABLE)?;
-        poll.registry()
-            .register(&mut tls_listener, TLS_SERVER, mio::Interest::READABLE)?;
-
         Ok(Server {
-            listener,
-            tls_listener,
+            listeners,
             clients: HashMap::default(),
             tokens,
             tls_config: cfg,
+            tls_modified: tls_files_modified(config),
+            ip_filter,
+            ip_connections: HashMap::default(),
+            ip_rates: HashMap::default(),
         })
     }
 
-    pub fn accept(&mut self, storage: &Storage, is_tls: bool) -> Result<()> {
+    pub fn accept(&mut self, storage: &Storage, listener_token: mio::Token) -> Result<()> {
+        let Some(kind) = self
+            .listeners
+            .get(listener_token.0)
+            .map(|listener| listener.kind)
+        else {
+            return Ok(());
+        };
+        let is_tls = kind.is_tls();
+
         /* Wait for a new connection to accept and try to grab a token from the bag. */
         loop {
-            let (stream, addr) = match if is_tls {
-                self.tls_listener.accept()
-            } else {
-                self.listener.accept()
-            } {
+            let (stream, addr) = match self.listeners[listener_token.0].listener.accept() {
                 Ok((stream, addr)) => (stream, addr),
                 Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
                 Err(e) => {
@@ -70,17 +131,24 @@ impl Server {
                     return Err(e.into());
                 }
             };
+            let addr = SocketAddr::new(addr.ip().to_canonical(), addr.port());
 
             if !is_tls {
                 stream.set_nodelay(true)?;
             }
 
+            if !self.can_connect(storage, addr.ip()) {
+                drop(stream);
+                continue;
+            }
+
             if let Some(token) = self.tokens.pop_front() {
                 if self.clients.len() + 1 >= MAX_SOCKET_PLAYERS {
                     warn!(
                         "Server is full. has reached MAX_SOCKET_PLAYERS: {} ",
                         MAX_SOCKET_PLAYERS
                     );
+                    self.tokens.push_front(token);
                     drop(stream);
                     return Ok(());
                 }
@@ -91,14 +159,17 @@ impl Server {
                     None
                 };
 
+                let websocket = kind.is_websocket().then(WebSocket::default);
+
                 // Lets make the Client to handle hwo we send packets.
-                let mut client = Client::new(stream, token, tls_conn, addr.to_string())?;
+                let mut client = Client::new(stream, token, tls_conn, websocket, addr.to_string())?;
                 //client.poll_state.add(crate::socket::PollState::Write);
                 //Register the Poll to the client for recv and Sending
                 client.register(&storage.poll.borrow_mut())?;
 
                 // insert client into handled list.
                 self.clients.insert(token, RefCell::new(client));
+                *self.ip_connections.entry(addr.ip()).or_default() += 1;
             } else {
                 warn!("listener.accept No tokens left to give out.");
                 drop(stream);
@@ -107,16 +178,216 @@ impl Server {
         Ok(())
     }
 
+    /// Rebuilds the TLS config from the cert files. Sockets already open keep the old one.
+    pub fn reload_tls(&mut self, config: &Config) -> Result<()> {
+        self.tls_modified = tls_files_modified(config);
+        self.tls_config = build_tls_config(config)?;
+        info!("TLS certificates reloaded.");
+        Ok(())
+    }
+
+    /// Reloads TLS when the cert files have changed since they were last loaded.
+    pub fn check_tls_files(&mut self, config: &Config) {
+        if tls_files_modified(config) != self.tls_modified
+            && let Err(e) = self.reload_tls(config)
+        {
+            error!("Failed to reload TLS certificates: {}", e);
+        }
+    }
+
+    /// Checks the allow and deny lists along with the per IP connection limits.
+    fn can_connect(&mut self, storage: &Storage, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        let config = &storage.config;
+
+        if !self.ip_filter.is_allowed(&ip) {
+            trace!("Connection from IP: {} was denied by the IP filter.", ip);
+            return false;
+        }
+
+        if self.ip_connections.get(&ip).copied().unwrap_or(0) >= config.max_connections_per_ip {
+            warn!(
+                "IP: {} has reached max_connections_per_ip: {}",
+                ip, config.max_connections_per_ip
+            );
+            return false;
+        }
+
+        let tick = *storage.gettick.borrow();
+
+        if !self
+            .ip_rates
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(config.ip_connection_burst, tick))
+            .take(config.ip_connection_rate, config.ip_connection_burst, tick)
+        {
+            warn!("IP: {} is connecting too fast.", ip);
+            return false;
+        }
+
+        true
+    }
+
+    /// Forgets connection rates for IPs whose buckets have filled back up.
+    pub fn clear_ip_rates(&mut self, storage: &Storage, tick: MyInstant) {
+        let config = &storage.config;
+        let refill = Duration::from_secs_f32(
+            config.ip_connection_burst / config.ip_connection_rate.max(f32::EPSILON),
+        );
+
+        self.ip_rates
+            .retain(|_, bucket| bucket.last_update.0 + refill > tick.0);
+    }
+
     #[inline]
     pub fn remove(&mut self, token: mio::Token) {
         /* If the token is valid, let's remove the connection and add the token back to the bag. */
-        if self.clients.contains_key(&token) {
-            self.clients.remove(&token);
+        if let Some(client) = self.clients.remove(&token) {
+            if let Ok(addr) = client.borrow().addr.parse::<SocketAddr>() {
+                let ip = addr.ip().to_canonical();
+
+                if let Some(count) = self.ip_connections.get_mut(&ip) {
+                    *count = count.saturating_sub(1);
+
+                    if *count == 0 {
+                        let _ = self.ip_connections.remove(&ip);
+                    }
+                }
+            }
+
             self.tokens.push_front(token);
         }
     }
 }
 
+/// Closes sockets that have not logged in before the login deadline, and sockets that
+/// have stayed on the character select screen past the character select deadline.
+pub fn close_login_timeouts(world: &mut World, storage: &Storage) -> Result<()> {
+    let tick = *storage.gettick.borrow();
+    let deadline = chrono::Duration::try_seconds(storage.config.login_deadline_secs as i64)
+        .unwrap_or_default();
+    let select_deadline =
+        chrono::Duration::try_seconds(storage.config.char_select_deadline_secs as i64)
+            .unwrap_or_default();
+
+    let timed_out: Vec<mio::Token> = storage
+        .server
+        .borrow()
+        .clients
+        .iter()
+        .filter_map(|(token, client)| {
+            let client = client.borrow();
+
+            if client.entity.is_some() {
+                return None;
+            }
+
+            match storage.char_select.borrow().get(token) {
+                Some((_, since)) => (*since + select_deadline < tick).then_some(*token),
+                None => (client.connected_at + deadline < tick).then_some(*token),
+            }
+        })
+        .collect();
+
+    for token in timed_out {
+        if let Some(client) = storage.server.borrow().clients.get(&token) {
+            trace!(
+                "IP: {} did not login or pick a Character in time.",
+                client.borrow().addr
+            );
+        }
+
+        close_client(world, storage, token)?;
+    }
+
+    Ok(())
+}
+
+/// Closes Clients whose send queue has stayed over the hard limit for `send_queue_hard_secs`.
+pub fn close_slow_clients(world: &mut World, storage: &Storage) -> Result<()> {
+    let tick = *storage.gettick.borrow();
+    let limit = chrono::Duration::try_seconds(storage.config.send_queue_hard_secs as i64)
+        .unwrap_or_default();
+    let mut slow = Vec::new();
+
+    for (token, client) in storage.server.borrow().clients.iter() {
+        let mut client = client.borrow_mut();
+
+        if !client.over_hard_limit(&storage.config) {
+            client.send_overflow_since = None;
+            continue;
+        }
+
+        match client.send_overflow_since {
+            Some(since) if since + limit < tick => {
+                slow.push((
+                    *token,
+                    client.entity,
+                    client.addr.clone(),
+                    client.sends.len(),
+                    client.send_bytes,
+                ));
+            }
+            Some(_) => {}
+            None => client.send_overflow_since = Some(tick),
+        }
+    }
+
+    for (token, entity, addr, count, bytes) in slow {
+        warn!(
+            "IP: {} was disconnected for not keeping up. Queued packets: {}, bytes: {}",
+            addr, count, bytes
+        );
+
+        let userid = match entity.and_then(|entity| world.get_opt_entity(entity)) {
+            Some(Entity::Player(data)) => data.try_lock()?.account.id,
+            _ => Uuid::nil(),
+        };
+
+        sql_new_log(
+            storage,
+            &PGLog::new(
+                storage.config.server_id,
+                userid,
+                LogType::Warning,
+                format!("Slow client with {} queued packets, {} bytes", count, bytes),
+                addr.to_string(),
+            ),
+        )?;
+
+        close_client(world, storage, token)?;
+    }
+
+    Ok(())
+}
+
+fn close_client(world: &mut World, storage: &Storage, token: mio::Token) -> Result<()> {
+    let mut server = storage.server.borrow_mut();
+
+    if let Some(client) = server.clients.get(&token) {
+        client.borrow_mut().close_socket(world, storage)?;
+    }
+
+    server.remove(token);
+    Ok(())
+}
+
+/// Binds a listener. IPv6 addresses also take IPv4 connections so `[::]` is dual stack.
+fn bind_listener(addr: SocketAddr) -> Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    if addr.is_ipv6() {
+        socket.set_only_v6(false)?;
+    }
+
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+
+    Ok(TcpListener::from_std(socket.into()))
+}
+
 pub fn poll_events(world: &mut World, storage: &Storage) -> Result<()> {
     let mut events = Events::with_capacity(1024);
 
@@ -127,19 +398,13 @@ pub fn poll_events(world: &mut World, storage: &Storage) -> Result<()> {
 
     for event in events.iter() {
         match event.token() {
-            SERVER => {
-                storage.server.borrow_mut().accept(storage, false)?;
-                storage.poll.borrow_mut().registry().reregister(
-                    &mut storage.server.borrow_mut().listener,
-                    SERVER,
-                    mio::Interest::READABLE,
-                )?;
-            }
-            TLS_SERVER => {
-                storage.server.borrow_mut().accept(storage, true)?;
+            token if token.0 < storage.server.borrow().listeners.len() => {
+                let mut server = storage.server.borrow_mut();
+
+                server.accept(storage, token)?;
                 storage.poll.borrow_mut().registry().reregister(
-                    &mut storage.server.borrow_mut().tls_listener,
-                    TLS_SERVER,
+                    &mut server.listeners[token.0].listener,
+                    token,
                     mio::Interest::READABLE,
                 )?;
             }
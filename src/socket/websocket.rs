@@ -0,0 +1,494 @@
+use crate::gametypes::*;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use bytey::ByteBuffer;
+use mmap_bytey::MByteBuffer;
+use sha1::{Digest, Sha1};
+
+/// Added to the Clients key when building the handshake accept key. Set by RFC 6455.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Largest HTTP upgrade request we will hold onto before giving up.
+const MAX_HANDSHAKE_SIZE: usize = 4096;
+/// Largest single frame a Client can send us.
+const MAX_FRAME_SIZE: usize = 65536;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WebSocketState {
+    /// Waiting on the HTTP upgrade request.
+    Handshake,
+    Open,
+    /// The Client sent a Close frame.
+    Closed,
+}
+
+/// RFC 6455 framing for a Client. Binary frames carry the same length prefixed
+/// packets as a raw socket so once unwrapped they go through the normal packet buffer.
+#[derive(Debug)]
+pub struct WebSocket {
+    pub state: WebSocketState,
+    // Raw bytes waiting on a full handshake or frame.
+    incoming: Vec<u8>,
+}
+
+impl Default for WebSocket {
+    fn default() -> Self {
+        Self {
+            state: WebSocketState::Handshake,
+            incoming: Vec::with_capacity(1024),
+        }
+    }
+}
+
+impl WebSocket {
+    /// Takes the raw bytes read from the stream. Packet data is written into `buffer`
+    /// and any handshake or control frame replies are returned to be sent back.
+    pub fn receive(&mut self, data: &[u8], buffer: &mut ByteBuffer) -> Result<Vec<MByteBuffer>> {
+        let mut replies = Vec::new();
+
+        self.incoming.extend_from_slice(data);
+
+        if self.state == WebSocketState::Handshake {
+            match self.handshake()? {
+                Some(reply) => replies.push(reply),
+                None => return Ok(replies),
+            }
+        }
+
+        while self.state == WebSocketState::Open {
+            if !self.read_frame(buffer, &mut replies)? {
+                break;
+            }
+        }
+
+        Ok(replies)
+    }
+
+    /// Checks the upgrade request once it is fully received and builds the reply.
+    fn handshake(&mut self) -> Result<Option<MByteBuffer>> {
+        let end = match self.incoming.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(end) => end,
+            None if self.incoming.len() > MAX_HANDSHAKE_SIZE => {
+                return Err(AscendingError::WebSocket("Handshake is too large"));
+            }
+            None => return Ok(None),
+        };
+
+        let request = std::str::from_utf8(&self.incoming[..end])?;
+        let mut lines = request.split("\r\n");
+
+        match lines.next() {
+            Some(line) if line.starts_with("GET ") && line.ends_with(" HTTP/1.1") => {}
+            _ => return Err(AscendingError::WebSocket("Handshake is not a GET request")),
+        }
+
+        let mut key = None;
+        let mut upgrade = false;
+        let mut connection = false;
+        let mut version = false;
+
+        for line in lines {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match name.trim().to_ascii_lowercase().as_str() {
+                "sec-websocket-key" => key = Some(value.to_owned()),
+                "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+                "connection" => {
+                    connection = value
+                        .split(',')
+                        .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+                }
+                "sec-websocket-version" => version = value == "13",
+                _ => {}
+            }
+        }
+
+        let key = match key {
+            Some(key) if upgrade && connection && version => key,
+            _ => return Err(AscendingError::WebSocket("Handshake is missing headers")),
+        };
+
+        let mut sha = Sha1::new();
+        sha.update(key.as_bytes());
+        sha.update(WEBSOCKET_GUID.as_bytes());
+        let accept = STANDARD.encode(sha.finalize());
+
+        let mut reply = MByteBuffer::new()?;
+        reply.write_slice(
+            format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+                accept
+            )
+            .as_bytes(),
+        )?;
+
+        self.incoming.drain(..end + 4);
+        self.state = WebSocketState::Open;
+        Ok(Some(reply))
+    }
+
+    /// Reads a single frame. Returns false if a full frame has not arrived yet.
+    fn read_frame(
+        &mut self,
+        buffer: &mut ByteBuffer,
+        replies: &mut Vec<MByteBuffer>,
+    ) -> Result<bool> {
+        if self.incoming.len() < 2 {
+            return Ok(false);
+        }
+
+        let fin = self.incoming[0] & 0x80 != 0;
+        let opcode = self.incoming[0] & 0x0F;
+        let masked = self.incoming[1] & 0x80 != 0;
+
+        if self.incoming[0] & 0x70 != 0 {
+            return Err(AscendingError::WebSocket("Reserved frame bits were set"));
+        }
+
+        if !masked {
+            return Err(AscendingError::WebSocket("Client frame was not masked"));
+        }
+
+        let (len, mut pos) = match self.incoming[1] & 0x7F {
+            126 => {
+                if self.incoming.len() < 4 {
+                    return Ok(false);
+                }
+
+                (
+                    u16::from_be_bytes([self.incoming[2], self.incoming[3]]) as usize,
+                    4,
+                )
+            }
+            127 => {
+                if self.incoming.len() < 10 {
+                    return Ok(false);
+                }
+
+                let mut len = [0u8; 8];
+                len.copy_from_slice(&self.incoming[2..10]);
+                (
+                    usize::try_from(u64::from_be_bytes(len)).unwrap_or(usize::MAX),
+                    10,
+                )
+            }
+            len => (len as usize, 2),
+        };
+
+        if len > MAX_FRAME_SIZE {
+            return Err(AscendingError::WebSocket("Frame is too large"));
+        }
+
+        if opcode >= OPCODE_CLOSE && (!fin || len > 125) {
+            return Err(AscendingError::WebSocket("Control frame was fragmented"));
+        }
+
+        if self.incoming.len() < pos + 4 + len {
+            return Ok(false);
+        }
+
+        let mut mask = [0u8; 4];
+        mask.copy_from_slice(&self.incoming[pos..pos + 4]);
+        pos += 4;
+
+        let payload: Vec<u8> = self.incoming[pos..pos + len]
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4])
+            .collect();
+
+        self.incoming.drain(..pos + len);
+
+        match opcode {
+            // Packets are a byte stream so fragments can be added as they come in.
+            OPCODE_CONTINUATION | OPCODE_BINARY => {
+                buffer.write_slice(&payload)?;
+            }
+            OPCODE_TEXT => {
+                return Err(AscendingError::WebSocket("Text frames are not supported"));
+            }
+            OPCODE_CLOSE => {
+                replies.push(control_frame(
+                    OPCODE_CLOSE,
+                    &payload[..payload.len().min(2)],
+                )?);
+                self.state = WebSocketState::Closed;
+            }
+            OPCODE_PING => replies.push(control_frame(OPCODE_PONG, &payload)?),
+            OPCODE_PONG => {}
+            _ => return Err(AscendingError::WebSocket("Unknown frame opcode")),
+        }
+
+        Ok(true)
+    }
+}
+
+/// Largest header `binary_frame` puts in front of a packet. Packets never go past
+/// BUFFER_SIZE, so their length always fits in the 16 bit form.
+pub const BINARY_FRAME_HEADER_MAX: usize = 4;
+
+/// Wraps a packet in a binary frame, with the header and data in one buffer.
+/// Server frames are never masked.
+pub fn binary_frame(buf: &mut MByteBuffer) -> Result<MByteBuffer> {
+    let len = buf.length();
+    let mut frame = MByteBuffer::new()?;
+
+    frame.write(0x80 | OPCODE_BINARY)?;
+
+    if len < 126 {
+        frame.write(len as u8)?;
+    } else {
+        frame.write(126u8)?;
+        frame.write_be(len as u16)?;
+    }
+
+    frame.write_slice(buf.as_slice())?;
+    frame.move_cursor_to_start();
+    Ok(frame)
+}
+
+fn control_frame(opcode: u8, payload: &[u8]) -> Result<MByteBuffer> {
+    let mut frame = MByteBuffer::new()?;
+
+    frame.write(0x80 | opcode)?;
+    frame.write(payload.len() as u8)?;
+    frame.write_slice(payload)?;
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASK: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+    /// The sample handshake from RFC 6455 section 1.3.
+    const REQUEST: &[u8] = b"GET /chat HTTP/1.1\r\nHost: server.example.com\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n";
+
+    /// Builds a masked Client frame using the shortest length form unless `long` is set.
+    fn client_frame(fin: bool, opcode: u8, payload: &[u8], long: bool) -> Vec<u8> {
+        let mut frame = vec![if fin { 0x80 } else { 0 } | opcode];
+
+        if long {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+        } else if payload.len() > 125 {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        } else {
+            frame.push(0x80 | payload.len() as u8);
+        }
+
+        frame.extend_from_slice(&MASK);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ MASK[i % 4]),
+        );
+        frame
+    }
+
+    fn open() -> Result<(WebSocket, ByteBuffer)> {
+        let mut websocket = WebSocket::default();
+        let mut buffer = ByteBuffer::new()?;
+
+        websocket.receive(REQUEST, &mut buffer)?;
+        Ok((websocket, buffer))
+    }
+
+    fn is_websocket_error<T>(result: Result<T>) -> bool {
+        matches!(result, Err(AscendingError::WebSocket(_)))
+    }
+
+    #[test]
+    fn handshake_replies_with_the_rfc_accept_key() -> Result<()> {
+        let mut websocket = WebSocket::default();
+        let mut buffer = ByteBuffer::new()?;
+        let (first, rest) = REQUEST.split_at(40);
+
+        assert!(websocket.receive(first, &mut buffer)?.is_empty());
+        assert_eq!(websocket.state, WebSocketState::Handshake);
+
+        let mut replies = websocket.receive(rest, &mut buffer)?;
+        let reply = std::str::from_utf8(replies[0].as_slice())?.to_owned();
+
+        assert_eq!(replies.len(), 1);
+        assert!(reply.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        assert!(reply.contains("\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        assert_eq!(websocket.state, WebSocketState::Open);
+        Ok(())
+    }
+
+    #[test]
+    fn handshake_without_upgrade_headers_fails() -> Result<()> {
+        let mut websocket = WebSocket::default();
+        let mut buffer = ByteBuffer::new()?;
+        let request = b"GET / HTTP/1.1\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n";
+
+        assert!(is_websocket_error(websocket.receive(request, &mut buffer)));
+        Ok(())
+    }
+
+    #[test]
+    fn frame_split_across_reads() -> Result<()> {
+        let (mut websocket, mut buffer) = open()?;
+        let frame = client_frame(true, OPCODE_BINARY, b"packet data", false);
+
+        for byte in &frame[..frame.len() - 1] {
+            assert!(websocket.receive(&[*byte], &mut buffer)?.is_empty());
+            assert_eq!(buffer.length(), 0);
+        }
+
+        websocket.receive(&frame[frame.len() - 1..], &mut buffer)?;
+        assert_eq!(buffer.as_slice(), b"packet data");
+        Ok(())
+    }
+
+    #[test]
+    fn handshake_and_frames_in_one_read() -> Result<()> {
+        let mut websocket = WebSocket::default();
+        let mut buffer = ByteBuffer::new()?;
+        let mut data = REQUEST.to_vec();
+
+        data.extend(client_frame(false, OPCODE_BINARY, b"one ", false));
+        data.extend(client_frame(true, OPCODE_CONTINUATION, b"two", false));
+
+        assert_eq!(websocket.receive(&data, &mut buffer)?.len(), 1);
+        assert_eq!(buffer.as_slice(), b"one two");
+        Ok(())
+    }
+
+    #[test]
+    fn sixteen_and_sixty_four_bit_lengths() -> Result<()> {
+        let (mut websocket, mut buffer) = open()?;
+        let medium: Vec<u8> = (0..300u32).map(|i| i as u8).collect();
+        let large: Vec<u8> = (0..MAX_FRAME_SIZE as u32).map(|i| (i * 7) as u8).collect();
+
+        websocket.receive(
+            &client_frame(true, OPCODE_BINARY, &medium, false),
+            &mut buffer,
+        )?;
+        assert_eq!(buffer.as_slice(), &medium[..]);
+
+        let (mut websocket, mut buffer) = open()?;
+        websocket.receive(
+            &client_frame(true, OPCODE_BINARY, &large, true),
+            &mut buffer,
+        )?;
+        assert_eq!(buffer.as_slice(), &large[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn oversize_frame_fails_before_its_payload_arrives() -> Result<()> {
+        let (mut websocket, mut buffer) = open()?;
+        let mut header = vec![0x80 | OPCODE_BINARY, 0x80 | 127];
+
+        header.extend_from_slice(&(MAX_FRAME_SIZE as u64 + 1).to_be_bytes());
+        assert!(is_websocket_error(websocket.receive(&header, &mut buffer)));
+
+        let (mut websocket, mut buffer) = open()?;
+        let mut header = vec![0x80 | OPCODE_BINARY, 0x80 | 127];
+
+        header.extend_from_slice(&u64::MAX.to_be_bytes());
+        assert!(is_websocket_error(websocket.receive(&header, &mut buffer)));
+        Ok(())
+    }
+
+    #[test]
+    fn unmasked_frame_fails() -> Result<()> {
+        let (mut websocket, mut buffer) = open()?;
+        let mut frame = client_frame(true, OPCODE_BINARY, b"data", false);
+
+        frame[1] &= 0x7F;
+        assert!(is_websocket_error(websocket.receive(&frame, &mut buffer)));
+        Ok(())
+    }
+
+    #[test]
+    fn fragmented_or_long_control_frames_fail() -> Result<()> {
+        let (mut websocket, mut buffer) = open()?;
+        let frame = client_frame(false, OPCODE_PING, b"ping", false);
+
+        assert!(is_websocket_error(websocket.receive(&frame, &mut buffer)));
+
+        let (mut websocket, mut buffer) = open()?;
+        let frame = client_frame(true, OPCODE_PING, &[0; 126], false);
+
+        assert!(is_websocket_error(websocket.receive(&frame, &mut buffer)));
+        Ok(())
+    }
+
+    #[test]
+    fn text_frame_fails() -> Result<()> {
+        let (mut websocket, mut buffer) = open()?;
+        let frame = client_frame(true, OPCODE_TEXT, b"text", false);
+
+        assert!(is_websocket_error(websocket.receive(&frame, &mut buffer)));
+        Ok(())
+    }
+
+    #[test]
+    fn close_is_answered_with_its_status_code() -> Result<()> {
+        let (mut websocket, mut buffer) = open()?;
+        let mut data = client_frame(true, OPCODE_CLOSE, b"\x03\xe8going away", false);
+
+        // Nothing after a Close is read.
+        data.extend(client_frame(true, OPCODE_BINARY, b"late", false));
+
+        let mut replies = websocket.receive(&data, &mut buffer)?;
+
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].as_slice(), &[0x80 | OPCODE_CLOSE, 2, 0x03, 0xe8]);
+        assert_eq!(websocket.state, WebSocketState::Closed);
+        assert_eq!(buffer.length(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn ping_is_answered_with_a_pong() -> Result<()> {
+        let (mut websocket, mut buffer) = open()?;
+        let mut data = client_frame(true, OPCODE_PING, b"hello", false);
+
+        data.extend(client_frame(true, OPCODE_PONG, b"ignored", false));
+
+        let mut replies = websocket.receive(&data, &mut buffer)?;
+
+        assert_eq!(replies.len(), 1);
+        assert_eq!(
+            replies[0].as_slice(),
+            &[0x80 | OPCODE_PONG, 5, b'h', b'e', b'l', b'l', b'o']
+        );
+        assert_eq!(websocket.state, WebSocketState::Open);
+        Ok(())
+    }
+
+    #[test]
+    fn binary_frame_uses_the_shortest_length_form() -> Result<()> {
+        let mut small = MByteBuffer::new()?;
+        small.write_slice(&[9; 125])?;
+
+        let mut frame = binary_frame(&mut small)?;
+        assert_eq!(&frame.as_slice()[..2], &[0x80 | OPCODE_BINARY, 125]);
+        assert_eq!(frame.length(), 127);
+
+        let mut large = MByteBuffer::new()?;
+        large.write_slice(&[9; 1000])?;
+
+        let mut frame = binary_frame(&mut large)?;
+        assert_eq!(
+            &frame.as_slice()[..4],
+            &[0x80 | OPCODE_BINARY, 126, 0x03, 0xe8]
+        );
+        assert_eq!(frame.length(), BINARY_FRAME_HEADER_MAX + 1000);
+        Ok(())
+    }
+}
@@ -1,13 +1,19 @@
 mod integers;
 mod logstruct;
+mod migrations;
+mod persistence;
 mod queries;
 mod schema;
 mod schema_enums;
 mod schema_structs;
+mod table;
 mod updater;
 
+pub use integers::Shifting;
 #[allow(unused_imports)]
 pub use logstruct::PGLog;
+pub use migrations::*;
+pub use persistence::*;
 pub use queries::*;
 #[allow(unused_imports)]
 pub use schema::*;
@@ -15,4 +21,5 @@ pub use schema::*;
 pub use schema_enums::*;
 #[allow(unused_imports)]
 pub use schema_structs::*;
+pub use table::*;
 pub use updater::*;
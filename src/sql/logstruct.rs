@@ -1,20 +1,21 @@
 use sqlx::FromRow;
+use uuid::Uuid;
 
 use crate::gametypes::*;
 
-#[derive(Debug, FromRow)]
+#[derive(Debug, Clone, FromRow)]
 pub struct PGLog {
-    serverid: i16,
-    userid: i64,
-    logtype: LogType,
-    message: String,
-    ipaddress: String,
+    pub serverid: i16,
+    pub userid: Uuid,
+    pub logtype: LogType,
+    pub message: String,
+    pub ipaddress: String,
 }
 
 impl PGLog {
     pub fn new(
         serverid: i16,
-        userid: i64,
+        userid: Uuid,
         logtype: LogType,
         message: String,
         ipaddress: String,
@@ -0,0 +1,235 @@
+use crate::gametypes::*;
+use log::info;
+use sqlx::PgPool;
+use tokio::{runtime::Runtime, task};
+
+use super::{schema::*, schema_enums::*, schema_structs::*};
+
+/// Key for the advisory lock held while a migration is applied, so two servers
+/// starting against the same database do not both apply it.
+const MIGRATION_LOCK: i64 = 0x6173_6365_6e64;
+
+/// A numbered schema change. Each statement is run separately inside one transaction.
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub statements: &'static [&'static str],
+}
+
+/// Every schema change in order. Add new ones to the end with the next version and never
+/// edit one that has shipped, since databases that already ran it will not run it again.
+pub const MIGRATIONS: &[Migration] = &[
+    // Databases made before migrations existed already have these, which is fine
+    // since every statement here skips what already exists.
+    Migration {
+        version: 1,
+        name: "initial schema",
+        statements: &[
+            PG_CRYPTO_EXTENSION,
+            PG_UUID,
+            LOGTYPE_SCHEMA,
+            LOGTYPE_SCHEMA_ALTER,
+            USERACCESS_SCHEMA,
+            USERACCESS_SCHEMA_ALTER,
+            MAP_POSITION_SCHEMA,
+            MAP_POSITION_SCHEMA_ALTER,
+            POSITION_SCHEMA,
+            POSITION_SCHEMA_ALTER,
+            LOGS_SCHEMA,
+            LOGS_SCHEMA_ALTER,
+            ACCOUNT_SCHEMA,
+            ACCOUNT_SCHEMA_ALTER,
+            GENERAL_SCHEMA,
+            GENERAL_SCHEMA_ALTER,
+            LOCATION_SCHEMA,
+            LOCATION_SCHEMA_ALTER,
+            COMBAT_SCHEMA,
+            COMBAT_SCHEMA_ALTER,
+            EQUIPMENT_SCHEMA,
+            EQUIPMENT_SCHEMA_ALTER,
+            INVENTORY_SCHEMA,
+            INVENTORY_SCHEMA_ALTER,
+            STORAGE_SCHEMA,
+            STORAGE_SCHEMA_ALTER,
+            BANK_SCHEMA,
+            BANK_SCHEMA_ALTER,
+            CHARACTERS_SCHEMA,
+            CHARACTERS_SCHEMA_ALTER,
+            STASH_SCHEMA,
+            STASH_SCHEMA_ALTER,
+            EMAIL_VERIFICATION_SCHEMA,
+            EMAIL_VERIFICATION_SCHEMA_ALTER,
+        ],
+    },
+    Migration {
+        version: 2,
+        name: "index character rows by owner",
+        statements: &[
+            GENERAL_INDEX,
+            LOCATION_INDEX,
+            COMBAT_INDEX,
+            EQUIPMENT_INDEX,
+            INVENTORY_INDEX,
+            STORAGE_INDEX,
+            STASH_INDEX,
+        ],
+    },
+    Migration {
+        version: 3,
+        name: "item event ledger",
+        statements: &[
+            ITEM_PLACE_SCHEMA,
+            ITEM_PLACE_SCHEMA_ALTER,
+            ITEM_REASON_SCHEMA,
+            ITEM_REASON_SCHEMA_ALTER,
+            ITEM_EVENTS_SCHEMA,
+            ITEM_EVENTS_SCHEMA_ALTER,
+            ITEM_EVENTS_NUM_INDEX,
+            ITEM_EVENTS_UID_INDEX,
+            ITEM_EVENTS_CORRELATION_INDEX,
+            ITEM_EVENTS_SOURCE_UID_INDEX,
+            ITEM_EVENTS_DESTINATION_UID_INDEX,
+        ],
+    },
+    Migration {
+        version: 4,
+        name: "item instance ids",
+        statements: &[
+            EQUIPMENT_INSTANCE,
+            INVENTORY_INSTANCE,
+            STORAGE_INSTANCE,
+            STASH_INSTANCE,
+            ITEM_EVENTS_INSTANCE,
+            ITEM_EVENTS_INSTANCE_INDEX,
+        ],
+    },
+    Migration {
+        version: 5,
+        name: "admin tool",
+        statements: &[CHARACTERS_ONLINE, ITEM_REASON_ADMIN],
+    },
+    Migration {
+        version: 6,
+        name: "case-insensitive account and character names",
+        statements: &[
+            ACCOUNT_CASE_COLLISIONS,
+            ACCOUNT_USERNAME_LOWER_INDEX,
+            ACCOUNT_EMAIL_LOWER_INDEX,
+            ACCOUNT_DROP_CASE_SENSITIVE,
+            CHARACTERS_CASE_COLLISIONS,
+            CHARACTERS_NAME_LOWER_INDEX,
+            CHARACTERS_DROP_CASE_SENSITIVE,
+        ],
+    },
+    Migration {
+        version: 7,
+        name: "character snapshots",
+        statements: &[
+            SNAPSHOT_REASON_SCHEMA,
+            SNAPSHOT_REASON_SCHEMA_ALTER,
+            CHARACTER_SNAPSHOTS_SCHEMA,
+            CHARACTER_SNAPSHOTS_SCHEMA_ALTER,
+            CHARACTER_SNAPSHOTS_UID_INDEX,
+        ],
+    },
+];
+
+/// The schema version this build expects.
+pub fn latest_schema_version() -> i32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Reads the databases schema version. A database without the schema_version table is at 0.
+async fn current_schema_version(conn: &PgPool) -> Result<i32> {
+    let (exists,): (bool,) =
+        sqlx::query_as("SELECT to_regclass('public.schema_version') IS NOT NULL")
+            .fetch_one(conn)
+            .await?;
+
+    if !exists {
+        return Ok(0);
+    }
+
+    let (version,): (Option<i32>,) =
+        sqlx::query_as("SELECT MAX(version) FROM public.schema_version")
+            .fetch_one(conn)
+            .await?;
+
+    Ok(version.unwrap_or_default())
+}
+
+async fn apply_migration(conn: &PgPool, migration: &Migration) -> Result<()> {
+    let mut tx = conn.begin().await?;
+
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(MIGRATION_LOCK)
+        .execute(&mut *tx)
+        .await?;
+
+    // Another server may have applied it while we waited on the lock.
+    let (applied,): (bool,) =
+        sqlx::query_as("SELECT EXISTS(SELECT 1 FROM public.schema_version WHERE version = $1)")
+            .bind(migration.version)
+            .fetch_one(&mut *tx)
+            .await?;
+
+    if applied {
+        return Ok(());
+    }
+
+    for statement in migration.statements {
+        sqlx::query(statement).execute(&mut *tx).await?;
+    }
+
+    sqlx::query("INSERT INTO public.schema_version(version, name) VALUES ($1, $2)")
+        .bind(migration.version)
+        .bind(migration.name)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Applies every migration the database has not run yet, each in its own transaction.
+/// With `dry_run` nothing is changed. Returns the migrations that were pending.
+/// Fails if the database is at a newer version than this build knows about.
+pub fn migrate(
+    conn: &PgPool,
+    rt: &mut Runtime,
+    local: &task::LocalSet,
+    dry_run: bool,
+) -> Result<Vec<&'static Migration>> {
+    local.block_on(rt, async {
+        let current = current_schema_version(conn).await?;
+        let latest = latest_schema_version();
+
+        if current > latest {
+            return Err(AscendingError::SchemaTooNew {
+                database: current,
+                binary: latest,
+            });
+        }
+
+        let pending: Vec<_> = MIGRATIONS
+            .iter()
+            .filter(|migration| migration.version > current)
+            .collect();
+
+        if dry_run || pending.is_empty() {
+            return Ok(pending);
+        }
+
+        sqlx::query(SCHEMA_VERSION_SCHEMA).execute(conn).await?;
+
+        for migration in &pending {
+            info!(
+                "Applying migration {}: {}",
+                migration.version, migration.name
+            );
+            apply_migration(conn, migration).await?;
+        }
+
+        Ok(pending)
+    })
+}
@@ -0,0 +1,729 @@
+use crate::{
+    containers::{Config, HashMap, UserAccess},
+    gametypes::*,
+};
+use log::{error, info, warn};
+use sqlx::{PgConnection, PgPool};
+use std::{
+    cell::RefCell,
+    ops::Range,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tokio::runtime::Handle;
+use uuid::Uuid;
+
+use super::{
+    PGCharacterSnapshot, PGCombat, PGEquipmentSlot, PGGeneral, PGInventorySlot, PGItemEvent,
+    PGLocation, PGLog, PGStashSlot, PGStorageSlot, pg_insert, sql_add_storage_slots,
+    sql_insert_log, sql_prune_character_snapshots, sql_set_character_offline, sql_update_account,
+    sql_update_bank_money, sql_update_bank_slots, sql_update_combat, sql_update_equipment_slot,
+    sql_update_general, sql_update_inventory_slot, sql_update_level, sql_update_location,
+    sql_update_money, sql_update_resetcount, sql_update_stash_slot, sql_update_storage_slot,
+};
+
+/// How many times a failing batch is retried before the worker gives up on shutdown.
+const SHUTDOWN_ATTEMPTS: u32 = 5;
+/// How long loading a player waits on their earlier saves to be written.
+const FLUSH_WAIT: Duration = Duration::from_secs(5);
+
+/// A write the game loop hands to the persistence worker. Each one holds the values
+/// at the time it was queued so a newer command for the same row can replace it.
+/// `Offline` is queued after a character's last saves, so it is only marked offline
+/// once they are written.
+#[derive(Debug)]
+pub enum SaveCommand {
+    Account {
+        uid: Uuid,
+        user_access: UserAccess,
+    },
+    General {
+        cid: Uuid,
+        data: PGGeneral,
+    },
+    Money {
+        cid: Uuid,
+        money: i64,
+    },
+    ResetCount {
+        cid: Uuid,
+        resetcount: i16,
+    },
+    Combat {
+        cid: Uuid,
+        data: PGCombat,
+    },
+    Level {
+        cid: Uuid,
+        data: PGCombat,
+    },
+    Location {
+        cid: Uuid,
+        data: PGLocation,
+    },
+    InventorySlot {
+        cid: Uuid,
+        data: PGInventorySlot,
+    },
+    EquipmentSlot {
+        cid: Uuid,
+        data: PGEquipmentSlot,
+    },
+    StorageSlot {
+        cid: Uuid,
+        data: PGStorageSlot,
+    },
+    StorageRows {
+        cid: Uuid,
+        slots: Range<usize>,
+    },
+    StashSlot {
+        uid: Uuid,
+        data: PGStashSlot,
+    },
+    BankMoney {
+        uid: Uuid,
+        money: i64,
+    },
+    BankSlots {
+        uid: Uuid,
+        slots: i16,
+    },
+    ItemEvent {
+        uid: Uuid,
+        data: Box<PGItemEvent>,
+    },
+    Snapshot {
+        cid: Uuid,
+        data: Box<PGCharacterSnapshot>,
+    },
+    Log {
+        data: Box<PGLog>,
+    },
+    PruneSnapshots {
+        keep_days: u32,
+        keep_count: u32,
+    },
+    Offline {
+        cid: Uuid,
+    },
+}
+
+/// The row and columns a SaveCommand writes. Commands with the same key are coalesced.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum SaveKey {
+    Account(Uuid),
+    General(Uuid),
+    Money(Uuid),
+    ResetCount(Uuid),
+    Combat(Uuid),
+    Level(Uuid),
+    Location(Uuid),
+    InventorySlot(Uuid, i16),
+    EquipmentSlot(Uuid, i16),
+    StorageSlot(Uuid, i16),
+    StorageRows(Uuid, usize),
+    StashSlot(Uuid, i16),
+    BankMoney(Uuid),
+    BankSlots(Uuid),
+    Offline(Uuid),
+    PruneSnapshots,
+    /// Ledger entries, snapshots and logs are all kept, so each gets the order it was received in.
+    Received(u64),
+}
+
+impl SaveCommand {
+    fn key(&self, order: u64) -> SaveKey {
+        match self {
+            SaveCommand::Account { uid, .. } => SaveKey::Account(*uid),
+            SaveCommand::General { cid, .. } => SaveKey::General(*cid),
+            SaveCommand::Money { cid, .. } => SaveKey::Money(*cid),
+            SaveCommand::ResetCount { cid, .. } => SaveKey::ResetCount(*cid),
+            SaveCommand::Combat { cid, .. } => SaveKey::Combat(*cid),
+            SaveCommand::Level { cid, .. } => SaveKey::Level(*cid),
+            SaveCommand::Location { cid, .. } => SaveKey::Location(*cid),
+            SaveCommand::InventorySlot { cid, data } => SaveKey::InventorySlot(*cid, data.id),
+            SaveCommand::EquipmentSlot { cid, data } => SaveKey::EquipmentSlot(*cid, data.id),
+            SaveCommand::StorageSlot { cid, data } => SaveKey::StorageSlot(*cid, data.id),
+            // Row inserts are never replaced, each range has to be created.
+            SaveCommand::StorageRows { cid, slots } => SaveKey::StorageRows(*cid, slots.start),
+            SaveCommand::StashSlot { uid, data } => SaveKey::StashSlot(*uid, data.id),
+            SaveCommand::BankMoney { uid, .. } => SaveKey::BankMoney(*uid),
+            SaveCommand::BankSlots { uid, .. } => SaveKey::BankSlots(*uid),
+            SaveCommand::ItemEvent { .. }
+            | SaveCommand::Snapshot { .. }
+            | SaveCommand::Log { .. } => SaveKey::Received(order),
+            SaveCommand::PruneSnapshots { .. } => SaveKey::PruneSnapshots,
+            SaveCommand::Offline { cid } => SaveKey::Offline(*cid),
+        }
+    }
+
+    /// The Character or Account whose loaded data this command changes. Ledger entries,
+    /// snapshots and logs are never read back on login, so they have none.
+    fn owner(&self) -> Option<Uuid> {
+        match self {
+            SaveCommand::Account { uid, .. }
+            | SaveCommand::StashSlot { uid, .. }
+            | SaveCommand::BankMoney { uid, .. }
+            | SaveCommand::BankSlots { uid, .. } => Some(*uid),
+            SaveCommand::General { cid, .. }
+            | SaveCommand::Money { cid, .. }
+            | SaveCommand::ResetCount { cid, .. }
+            | SaveCommand::Combat { cid, .. }
+            | SaveCommand::Level { cid, .. }
+            | SaveCommand::Location { cid, .. }
+            | SaveCommand::InventorySlot { cid, .. }
+            | SaveCommand::EquipmentSlot { cid, .. }
+            | SaveCommand::StorageSlot { cid, .. }
+            | SaveCommand::StorageRows { cid, .. }
+            | SaveCommand::Offline { cid } => Some(*cid),
+            SaveCommand::ItemEvent { .. }
+            | SaveCommand::Snapshot { .. }
+            | SaveCommand::Log { .. }
+            | SaveCommand::PruneSnapshots { .. } => None,
+        }
+    }
+
+    async fn execute(&self, conn: &mut PgConnection) -> Result<()> {
+        match self {
+            SaveCommand::Account { uid, user_access } => {
+                sql_update_account(conn, *uid, *user_access).await
+            }
+            SaveCommand::General { cid, data } => sql_update_general(conn, *cid, data).await,
+            SaveCommand::Money { cid, money } => sql_update_money(conn, *cid, *money).await,
+            SaveCommand::ResetCount { cid, resetcount } => {
+                sql_update_resetcount(conn, *cid, *resetcount).await
+            }
+            SaveCommand::Combat { cid, data } => sql_update_combat(conn, *cid, data).await,
+            SaveCommand::Level { cid, data } => sql_update_level(conn, *cid, data).await,
+            SaveCommand::Location { cid, data } => sql_update_location(conn, *cid, data).await,
+            SaveCommand::InventorySlot { cid, data } => {
+                sql_update_inventory_slot(conn, *cid, data).await
+            }
+            SaveCommand::EquipmentSlot { cid, data } => {
+                sql_update_equipment_slot(conn, *cid, data).await
+            }
+            SaveCommand::StorageSlot { cid, data } => {
+                sql_update_storage_slot(conn, *cid, data).await
+            }
+            SaveCommand::StorageRows { cid, slots } => {
+                sql_add_storage_slots(conn, *cid, slots.clone()).await
+            }
+            SaveCommand::StashSlot { uid, data } => sql_update_stash_slot(conn, *uid, data).await,
+            SaveCommand::BankMoney { uid, money } => {
+                sql_update_bank_money(conn, *uid, *money).await
+            }
+            SaveCommand::BankSlots { uid, slots } => {
+                sql_update_bank_slots(conn, *uid, *slots).await
+            }
+            SaveCommand::ItemEvent { uid, data } => pg_insert(conn, *uid, data.as_ref()).await,
+            SaveCommand::Snapshot { cid, data } => pg_insert(conn, *cid, data.as_ref()).await,
+            SaveCommand::Log { data } => sql_insert_log(conn, data).await,
+            SaveCommand::PruneSnapshots {
+                keep_days,
+                keep_count,
+            } => {
+                let count = sql_prune_character_snapshots(conn, *keep_days, *keep_count).await?;
+
+                if count > 0 {
+                    info!("Pruned {} character snapshots.", count);
+                }
+
+                Ok(())
+            }
+            SaveCommand::Offline { cid } => sql_set_character_offline(conn, *cid).await,
+        }
+    }
+}
+
+enum PersistMessage {
+    Save(SaveCommand),
+    /// Write everything pending now instead of waiting for the flush interval.
+    Flush,
+    Shutdown,
+}
+
+/// Counters shared between the game loop and the persistence worker.
+#[derive(Default)]
+pub struct PersistMetrics {
+    /// Commands queued by the game loop.
+    pub queued: AtomicU64,
+    /// Commands the worker has received and committed, including coalesced ones.
+    pub flushed: AtomicU64,
+    /// Statements written to the database.
+    pub written: AtomicU64,
+    /// Commands dropped because a newer one for the same row replaced them.
+    pub coalesced: AtomicU64,
+    pub batches: AtomicU64,
+    pub failed_batches: AtomicU64,
+    /// Commands the database refused even when written on their own. They are logged and dropped.
+    pub dead_letters: AtomicU64,
+    /// Times the queue was full and the game loop had to wait on the worker.
+    pub stalls: AtomicU64,
+    pub max_pending: AtomicU64,
+    pub slowest_batch_ms: AtomicU64,
+    /// Set while the database can not be reached. New logins are refused until it clears.
+    pub degraded: AtomicBool,
+    /// Times the database became unreachable.
+    pub outages: AtomicU64,
+}
+
+/// How many saves of each Character or Account are queued but not yet committed, so
+/// loading one only waits on its own saves. Wakes the game loop as they are committed.
+#[derive(Default)]
+struct PendingOwners {
+    counts: Mutex<HashMap<Uuid, u64>>,
+    condvar: Condvar,
+}
+
+impl PendingOwners {
+    fn add(&self, owner: Option<Uuid>) {
+        if let (Some(owner), Ok(mut counts)) = (owner, self.counts.lock()) {
+            *counts.entry(owner).or_default() += 1;
+        }
+    }
+
+    /// Marks saves as committed or dropped.
+    fn done(&self, owners: impl Iterator<Item = Option<Uuid>>) {
+        let Ok(mut counts) = self.counts.lock() else {
+            return;
+        };
+
+        for owner in owners.flatten() {
+            if let Some(count) = counts.get_mut(&owner) {
+                *count -= 1;
+
+                if *count == 0 {
+                    counts.remove(&owner);
+                }
+            }
+        }
+
+        self.condvar.notify_all();
+    }
+}
+
+/// Write-behind persistence. The game loop queues SaveCommands which a worker thread
+/// coalesces per row and writes in batched transactions, so a slow database does not
+/// stall the game loop.
+pub struct Persistence {
+    sender: SyncSender<PersistMessage>,
+    worker: RefCell<Option<JoinHandle<()>>>,
+    pub metrics: Arc<PersistMetrics>,
+    owners: Arc<PendingOwners>,
+}
+
+impl Persistence {
+    pub fn new(config: &Config, pool: PgPool, handle: Handle) -> Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel(config.persist_queue_capacity.max(1));
+        let metrics = Arc::new(PersistMetrics::default());
+        let owners = Arc::new(PendingOwners::default());
+        let worker = PersistWorker {
+            pool,
+            handle,
+            metrics: metrics.clone(),
+            owners: owners.clone(),
+            pending: HashMap::default(),
+            writing: Vec::new(),
+            received: 0,
+            batch_size: config.persist_batch_size.max(1),
+            queue_capacity: config.persist_queue_capacity.max(1),
+            retry_min: Duration::from_millis(config.db_retry_min_ms.max(1)),
+            retry_max: Duration::from_millis(config.db_retry_max_ms.max(config.db_retry_min_ms)),
+            retry_delay: None,
+            health_interval: Duration::from_secs(config.db_health_check_secs.max(1)),
+        };
+        let interval = Duration::from_millis(config.persist_flush_ms);
+
+        let worker = thread::Builder::new()
+            .name("persistence".into())
+            .spawn(move || worker.run(receiver, interval))?;
+
+        Ok(Self {
+            sender,
+            worker: RefCell::new(Some(worker)),
+            metrics,
+            owners,
+        })
+    }
+
+    /// Queues a save. This only blocks when the worker has fallen a full queue behind.
+    pub fn save(&self, command: SaveCommand) -> Result<()> {
+        let owner = command.owner();
+
+        // Counted before sending so the worker can not commit it before it is counted.
+        self.owners.add(owner);
+
+        let sent = match self.sender.try_send(PersistMessage::Save(command)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(message)) => {
+                self.metrics.stalls.fetch_add(1, Ordering::Relaxed);
+                warn!("Persistence queue is full, waiting on the database.");
+
+                self.sender.send(message).map_err(|_| ())
+            }
+            Err(TrySendError::Disconnected(_)) => Err(()),
+        };
+
+        if sent.is_err() {
+            self.owners.done(std::iter::once(owner));
+            return Err(AscendingError::Persistence("worker has stopped"));
+        }
+
+        self.metrics.queued.fetch_add(1, Ordering::Release);
+        Ok(())
+    }
+
+    /// Asks the worker to write what it has now. Used when a player logs out.
+    pub fn flush(&self) -> Result<()> {
+        match self.sender.try_send(PersistMessage::Flush) {
+            // A full queue means the worker is already busy writing.
+            Ok(()) | Err(TrySendError::Full(_)) => Ok(()),
+            Err(TrySendError::Disconnected(_)) => {
+                Err(AscendingError::Persistence("worker has stopped"))
+            }
+        }
+    }
+
+    /// Whether the database is currently unreachable.
+    pub fn degraded(&self) -> bool {
+        self.metrics.degraded.load(Ordering::Acquire)
+    }
+
+    /// Blocks until the saves queued for any of `owners` have been written. Called before
+    /// loading a player so data saved on their last logout is not read back stale. Returns
+    /// at once when they have nothing queued, however much else is.
+    pub fn wait_for_saves(&self, owners: &[Uuid]) -> Result<()> {
+        let queued =
+            |counts: &mut HashMap<Uuid, u64>| owners.iter().any(|owner| counts.contains_key(owner));
+
+        if !queued(&mut *self.owners.counts.lock()?) {
+            return Ok(());
+        }
+
+        self.flush()?;
+
+        let counts = self.owners.counts.lock()?;
+        let (_counts, wait) = self
+            .owners
+            .condvar
+            .wait_timeout_while(counts, FLUSH_WAIT, queued)?;
+
+        if wait.timed_out() {
+            return Err(AscendingError::Persistence(
+                "timed out waiting for saves to be written",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Writes everything still queued and stops the worker.
+    pub fn shutdown(&self) {
+        if let Some(worker) = self.worker.borrow_mut().take() {
+            let _ = self.sender.send(PersistMessage::Shutdown);
+
+            if worker.join().is_err() {
+                error!("Persistence worker panicked before it finished writing.");
+            }
+        }
+    }
+
+    pub fn report(&self) {
+        let metrics = &self.metrics;
+        let queued = metrics.queued.load(Ordering::Relaxed);
+        let flushed = metrics.flushed.load(Ordering::Relaxed);
+
+        info!(
+            "Persistence: queued: {}, pending: {}, written: {}, coalesced: {}, batches: {}, failed batches: {}, dead letters: {}, queue stalls: {}, max pending: {}, slowest batch: {}ms, database outages: {}{}",
+            queued,
+            queued.saturating_sub(flushed),
+            metrics.written.load(Ordering::Relaxed),
+            metrics.coalesced.load(Ordering::Relaxed),
+            metrics.batches.load(Ordering::Relaxed),
+            metrics.failed_batches.load(Ordering::Relaxed),
+            metrics.dead_letters.load(Ordering::Relaxed),
+            metrics.stalls.load(Ordering::Relaxed),
+            metrics.max_pending.load(Ordering::Relaxed),
+            metrics.slowest_batch_ms.load(Ordering::Relaxed),
+            metrics.outages.load(Ordering::Relaxed),
+            if self.degraded() {
+                ", database unreachable"
+            } else {
+                ""
+            },
+        );
+    }
+}
+
+impl Drop for Persistence {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+struct PersistWorker {
+    pool: PgPool,
+    handle: Handle,
+    metrics: Arc<PersistMetrics>,
+    owners: Arc<PendingOwners>,
+    /// Commands waiting for the next flush along with the order they were received in.
+    pending: HashMap<SaveKey, (u64, SaveCommand)>,
+    /// Commands taken from `pending` that have not been committed yet.
+    writing: Vec<(u64, SaveCommand)>,
+    received: u64,
+    batch_size: usize,
+    queue_capacity: usize,
+    retry_min: Duration,
+    retry_max: Duration,
+    /// How long to wait before the next retry while writes are failing.
+    retry_delay: Option<Duration>,
+    health_interval: Duration,
+}
+
+impl PersistWorker {
+    fn run(mut self, receiver: Receiver<PersistMessage>, interval: Duration) {
+        let mut flush_at: Option<Instant> = None;
+        let mut check_at = Instant::now() + self.health_interval;
+
+        loop {
+            let wake_at = flush_at.map_or(check_at, |at| at.min(check_at));
+            let message = receiver.recv_timeout(wake_at.saturating_duration_since(Instant::now()));
+            let mut flush = flush_at.is_some_and(|at| at <= Instant::now());
+            let mut shutdown = matches!(message, Err(RecvTimeoutError::Disconnected));
+
+            // Take whatever else is already queued so it is coalesced into the same flush.
+            let queued = receiver.try_iter().take(self.queue_capacity);
+
+            for message in message.into_iter().chain(queued) {
+                match message {
+                    PersistMessage::Save(command) => self.add(command),
+                    // While retrying, the retry waits out its delay instead.
+                    PersistMessage::Flush => flush |= self.retry_delay.is_none(),
+                    PersistMessage::Shutdown => shutdown = true,
+                }
+            }
+
+            if shutdown {
+                self.shutdown();
+                return;
+            }
+
+            if flush {
+                match self.flush() {
+                    Ok(()) => {
+                        flush_at = None;
+                        check_at = Instant::now() + self.health_interval;
+                        self.retry_delay = None;
+                        self.set_reachable(true, None);
+                    }
+                    Err(e) => {
+                        let delay = self.retry_delay.unwrap_or(self.retry_min);
+
+                        error!(
+                            "Persistence failed to write a batch, retrying in {}ms: {}",
+                            delay.as_millis(),
+                            e
+                        );
+
+                        if e.is_database_unreachable() {
+                            self.set_reachable(false, Some(&e));
+                        }
+
+                        flush_at = Some(Instant::now() + delay);
+                        self.retry_delay = Some((delay * 2).min(self.retry_max));
+                    }
+                }
+            } else if flush_at.is_none() && self.has_pending() {
+                flush_at = Some(Instant::now() + interval);
+            }
+
+            // While retrying, the retries already tell when the database is back.
+            if self.retry_delay.is_none() && check_at <= Instant::now() {
+                self.check_health();
+                check_at = Instant::now() + self.health_interval;
+            }
+        }
+    }
+
+    /// Runs a trivial query so an outage is noticed even when nothing is being saved.
+    fn check_health(&self) {
+        let result = self
+            .handle
+            .block_on(sqlx::query("SELECT 1").execute(&self.pool));
+
+        match result.map_err(AscendingError::from) {
+            Ok(_) => self.set_reachable(true, None),
+            Err(e) if e.is_database_unreachable() => self.set_reachable(false, Some(&e)),
+            Err(e) => error!("Database health check failed: {}", e),
+        }
+    }
+
+    fn set_reachable(&self, reachable: bool, error: Option<&AscendingError>) {
+        let was_degraded = self.metrics.degraded.swap(!reachable, Ordering::AcqRel);
+
+        if reachable && was_degraded {
+            info!("Database is reachable again, logins are allowed.");
+        } else if !reachable && !was_degraded {
+            self.metrics.outages.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "Database is unreachable, blocking new logins and queueing saves: {}",
+                error.map(ToString::to_string).unwrap_or_default()
+            );
+        }
+    }
+
+    fn add(&mut self, command: SaveCommand) {
+        self.received += 1;
+
+        if let Some((_, replaced)) = self
+            .pending
+            .insert(command.key(self.received), (self.received, command))
+        {
+            self.metrics.coalesced.fetch_add(1, Ordering::Relaxed);
+            self.owners.done(std::iter::once(replaced.owner()));
+        }
+
+        self.metrics.max_pending.fetch_max(
+            (self.pending.len() + self.writing.len()) as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    fn has_pending(&self) -> bool {
+        !self.pending.is_empty() || !self.writing.is_empty()
+    }
+
+    /// Writes everything pending in transactions of up to `batch_size` commands. Commands
+    /// are written in the order they were last queued, so when two commands write the
+    /// same column the newest value wins.
+    fn flush(&mut self) -> Result<()> {
+        let mut pending: Vec<_> = self.pending.drain().map(|(_, entry)| entry).collect();
+
+        pending.sort_unstable_by_key(|(order, _)| *order);
+        self.writing.extend(pending);
+
+        while !self.writing.is_empty() {
+            let count = self.writing.len().min(self.batch_size);
+            let started = Instant::now();
+            let batch = &self.writing[..count];
+            let result = self.handle.block_on(async {
+                let mut tx = self.pool.begin().await?;
+
+                for (_, command) in batch {
+                    command.execute(&mut tx).await?;
+                }
+
+                tx.commit().await?;
+                Ok::<(), AscendingError>(())
+            });
+
+            let written = match result {
+                Ok(()) => count,
+                Err(e) => {
+                    self.metrics.failed_batches.fetch_add(1, Ordering::Relaxed);
+
+                    if e.is_database_unreachable() {
+                        return Err(e);
+                    }
+
+                    warn!(
+                        "Persistence batch failed, writing its {} saves one at a time: {}",
+                        count, e
+                    );
+                    self.write_singly(count)?
+                }
+            };
+
+            self.finish(count);
+            self.metrics.batches.fetch_add(1, Ordering::Relaxed);
+            self.metrics
+                .written
+                .fetch_add(written as u64, Ordering::Relaxed);
+            self.metrics
+                .slowest_batch_ms
+                .fetch_max(started.elapsed().as_millis() as u64, Ordering::Relaxed);
+        }
+
+        self.metrics.flushed.store(self.received, Ordering::Release);
+        Ok(())
+    }
+
+    /// Removes the first `count` commands from `writing` once they are committed or dropped.
+    fn finish(&mut self, count: usize) {
+        self.owners.done(
+            self.writing
+                .drain(..count)
+                .map(|(_, command)| command.owner()),
+        );
+    }
+
+    /// Writes the first `count` commands each in their own transaction, after their batch
+    /// failed for a reason other than the database being down. A command the database still
+    /// refuses is dead lettered: logged and dropped so it does not hold up the rest.
+    /// Returns how many were written.
+    fn write_singly(&mut self, count: usize) -> Result<usize> {
+        let mut written = 0;
+
+        for index in 0..count {
+            let command = &self.writing[index].1;
+            let result = self.handle.block_on(async {
+                let mut tx = self.pool.begin().await?;
+
+                command.execute(&mut tx).await?;
+                tx.commit().await?;
+                Ok::<(), AscendingError>(())
+            });
+
+            match result {
+                Ok(()) => written += 1,
+                Err(e) if e.is_database_unreachable() => {
+                    // Keep the ones not tried yet for the retry.
+                    self.finish(index);
+                    self.metrics
+                        .written
+                        .fetch_add(written as u64, Ordering::Relaxed);
+                    return Err(e);
+                }
+                Err(e) => {
+                    self.metrics.dead_letters.fetch_add(1, Ordering::Relaxed);
+                    error!(
+                        "Persistence dropped a save it could not write: {:?}: {}",
+                        command, e
+                    );
+                }
+            }
+        }
+
+        Ok(written)
+    }
+
+    fn shutdown(&mut self) {
+        for attempt in 1..=SHUTDOWN_ATTEMPTS {
+            match self.flush() {
+                Ok(()) => return,
+                Err(e) => {
+                    error!(
+                        "Persistence failed to write on shutdown, attempt {} of {}: {}",
+                        attempt, SHUTDOWN_ATTEMPTS, e
+                    );
+                    thread::sleep(self.retry_min);
+                }
+            }
+        }
+
+        error!(
+            "Persistence gave up with {} saves unwritten:",
+            self.writing.len()
+        );
+
+        for (_, command) in &self.writing {
+            error!("Unwritten save: {:?}", command);
+        }
+    }
+}
@@ -3,31 +3,47 @@ use std::sync::{Arc, Mutex};
 use crate::{containers::*, gametypes::*, sql::*};
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use chrono::Duration;
-use sqlx::{FromRow, PgPool};
-use tokio::{runtime::Runtime, task};
+use sqlx::FromRow;
 use uuid::Uuid;
 
 mod account;
+mod bank;
+mod character;
 mod combat;
 mod equipment;
 mod general;
 mod inventory;
+mod item_event;
+mod item_instance;
 mod location;
+mod log;
+mod snapshot;
+mod stash;
 mod storage;
+mod verification;
 
 pub use account::*;
+pub use bank::*;
+pub use character::*;
 pub use combat::*;
 pub use equipment::*;
 pub use general::*;
 pub use inventory::*;
+pub use item_event::*;
+pub use item_instance::*;
 pub use location::*;
+pub use log::*;
+pub use snapshot::*;
+pub use stash::*;
 pub use storage::*;
+pub use verification::*;
 
 use super::integers::Shifting;
 
 #[derive(Debug, PartialEq, Eq, FromRow)]
 pub struct PlayerWithPassword {
     pub uid: Uuid,
+    pub email: String,
     pub password: String,
 }
 
@@ -36,55 +52,24 @@ pub struct Check {
     pub check: bool,
 }
 
-pub fn initiate(conn: &PgPool, rt: &mut Runtime, local: &task::LocalSet) -> Result<()> {
-    let queries = [
-        PG_CRYPTO_EXTENSION,
-        PG_UUID,
-        LOGTYPE_SCHEMA,
-        LOGTYPE_SCHEMA_ALTER,
-        USERACCESS_SCHEMA,
-        USERACCESS_SCHEMA_ALTER,
-        MAP_POSITION_SCHEMA,
-        MAP_POSITION_SCHEMA_ALTER,
-        POSITION_SCHEMA,
-        POSITION_SCHEMA_ALTER,
-        LOGS_SCHEMA,
-        LOGS_SCHEMA_ALTER,
-        ACCOUNT_SCHEMA,
-        ACCOUNT_SCHEMA_ALTER,
-        GENERAL_SCHEMA,
-        GENERAL_SCHEMA_ALTER,
-        LOCATION_SCHEMA,
-        LOCATION_SCHEMA_ALTER,
-        COMBAT_SCHEMA,
-        COMBAT_SCHEMA_ALTER,
-        EQUIPMENT_SCHEMA,
-        EQUIPMENT_SCHEMA_ALTER,
-        INVENTORY_SCHEMA,
-        INVENTORY_SCHEMA_ALTER,
-        STORAGE_SCHEMA,
-        STORAGE_SCHEMA_ALTER,
-    ];
-
-    for quere in queries {
-        local.block_on(rt, sqlx::query(quere).execute(conn))?;
-    }
-
-    Ok(())
-}
-
-pub fn find_player(storage: &Storage, email: &str, password: &str) -> Result<Option<Uuid>> {
+/// Finds the Account by its username or email, ignoring case, and checks the password.
+/// Returns the Account uid and email.
+pub fn find_player(
+    storage: &Storage,
+    login: &str,
+    password: &str,
+) -> Result<Option<(Uuid, String)>> {
     let rt = storage.rt.borrow_mut();
     let local = storage.local.borrow();
     let userdata: Option<PlayerWithPassword> = local.block_on(
         &rt,
         sqlx::query_as(
             r#"
-                SELECT uid, password FROM public.account
-                WHERE email = $1
+                SELECT uid, email, password FROM public.account
+                WHERE lower(username) = lower($1) OR lower(email) = lower($1)
             "#,
         )
-        .bind(email)
+        .bind(login)
         .fetch_optional(&storage.pgconn),
     )?;
 
@@ -98,7 +83,7 @@ pub fn find_player(storage: &Storage, email: &str, password: &str) -> Result<Opt
             .verify_password(password.as_bytes(), &hash)
             .is_ok()
         {
-            Ok(Some(userdata.uid))
+            Ok(Some((userdata.uid, userdata.email)))
         } else {
             Err(AscendingError::IncorrectPassword)
         }
@@ -107,15 +92,43 @@ pub fn find_player(storage: &Storage, email: &str, password: &str) -> Result<Opt
     }
 }
 
+/// Finds the uid of the Account with this username or email, ignoring case.
+pub fn sql_find_account_id(storage: &Storage, login: &str) -> Result<Option<Uuid>> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+    let uid: Option<(Uuid,)> = local.block_on(
+        &rt,
+        sqlx::query_as(
+            r#"
+                SELECT uid FROM public.account
+                WHERE lower(username) = lower($1) OR lower(email) = lower($1)
+            "#,
+        )
+        .bind(login)
+        .fetch_optional(&storage.pgconn),
+    )?;
+
+    Ok(uid.map(|(uid,)| uid))
+}
+
+/// Checks if the username or email is taken, ignoring case.
+/// Returns 1 if the username is taken, 2 if the email is taken and 0 otherwise.
 pub fn check_existance(storage: &Storage, username: &str, email: &str) -> Result<i64> {
     let rt = storage.rt.borrow_mut();
     let local = storage.local.borrow();
 
     let check: Check = local.block_on(
         &rt,
-        sqlx::query_as(r#"SELECT EXISTS(SELECT 1 FROM public.account WHERE username=$1) as check"#)
-            .bind(username)
-            .fetch_one(&storage.pgconn),
+        sqlx::query_as(
+            r#"
+            SELECT (
+                EXISTS(SELECT 1 FROM public.account WHERE lower(username) = lower($1))
+                OR EXISTS(SELECT 1 FROM public.characters WHERE lower(name) = lower($1))
+            ) as check
+            "#,
+        )
+        .bind(username)
+        .fetch_one(&storage.pgconn),
     )?;
 
     if check.check {
@@ -124,9 +137,11 @@ pub fn check_existance(storage: &Storage, username: &str, email: &str) -> Result
 
     let check: Check = local.block_on(
         &rt,
-        sqlx::query_as(r#"SELECT EXISTS(SELECT 1 FROM public.account WHERE email=$1) as check"#)
-            .bind(email)
-            .fetch_one(&storage.pgconn),
+        sqlx::query_as(
+            r#"SELECT EXISTS(SELECT 1 FROM public.account WHERE lower(email) = lower($1)) as check"#,
+        )
+        .bind(email)
+        .fetch_one(&storage.pgconn),
     )?;
 
     if check.check {
@@ -136,35 +151,69 @@ pub fn check_existance(storage: &Storage, username: &str, email: &str) -> Result
     Ok(0)
 }
 
+/// Creates the Account along with its first Character named after the Account.
+/// Returns the Account uid and the Character cid.
 pub fn new_player(
     storage: &Storage,
     username: String,
     email: String,
     password: String,
+    sprite: u16,
     socket: &Socket,
-) -> Result<Uuid> {
+) -> Result<(Uuid, Uuid)> {
     let uid: Uuid = sql_new_account(storage, &username, &socket.addr, &password, &email)?;
 
-    sql_new_general(storage, uid)?;
-    sql_new_equipment(storage, uid)?;
-    sql_new_inventory(storage, uid)?;
-    sql_new_storage(storage, uid)?;
-    sql_new_combat(storage, uid)?;
-    sql_new_location(storage, uid)?;
+    sql_new_bank(storage, uid)?;
+    sql_new_stash(storage, uid)?;
+
+    let cid = new_character(storage, uid, 0, &username, sprite)?;
+
+    Ok((uid, cid))
+}
+
+pub fn new_character(
+    storage: &Storage,
+    uid: Uuid,
+    slot: i16,
+    name: &str,
+    sprite: u16,
+) -> Result<Uuid> {
+    let cid: Uuid = sql_new_character(storage, uid, slot, name)?;
+
+    sql_new_general(storage, cid, sprite)?;
+    sql_new_equipment(storage, cid)?;
+    sql_new_inventory(storage, cid)?;
+    sql_new_storage(storage, cid)?;
+    sql_new_combat(storage, cid)?;
+    sql_new_location(storage, cid)?;
 
-    Ok(uid)
+    Ok(cid)
 }
 
-pub fn load_player(storage: &Storage, entity: &mut PlayerEntity, account_id: Uuid) -> Result<()> {
+pub fn load_player(
+    storage: &Storage,
+    entity: &mut PlayerEntity,
+    account_id: Uuid,
+    character: &PGCharacter,
+) -> Result<()> {
     let tick = *storage.gettick.borrow();
+    let character_id = character.cid;
+
+    // Saves from an earlier session may still be queued, so wait on them before reading.
+    storage
+        .persistence
+        .wait_for_saves(&[account_id, character_id])?;
+    sql_set_character_online(storage, character_id)?;
 
     let account_data = sql_load_account(storage, account_id)?;
-    let general_data = sql_load_general(storage, account_id)?;
-    let equipment_data = sql_load_equipment(storage, account_id)?;
-    let inventory_data = sql_load_inventory(storage, account_id)?;
-    let storage_data = sql_load_storage(storage, account_id)?;
-    let combat_data = sql_load_combat(storage, account_id)?;
-    let location_data = sql_load_location(storage, account_id)?;
+    let bank_data = sql_load_bank(storage, account_id)?;
+    let stash_data = sql_load_stash(storage, account_id)?;
+    let general_data = sql_load_general(storage, character_id)?;
+    let equipment_data = sql_load_equipment(storage, character_id)?;
+    let inventory_data = sql_load_inventory(storage, character_id)?;
+    let storage_data = sql_load_storage(storage, character_id)?;
+    let combat_data = sql_load_combat(storage, character_id)?;
+    let location_data = sql_load_location(storage, character_id)?;
 
     entity.user_access = account_data.useraccess;
     entity.account.id = account_id;
@@ -173,6 +222,9 @@ pub fn load_player(storage: &Storage, entity: &mut PlayerEntity, account_id: Uui
         .account
         .passresetcode
         .clone_from(&account_data.passresetcode);
+    entity.character.id = character_id;
+    entity.character.name.clone_from(&character.name);
+    entity.character.slot = character.slot as u8;
 
     entity.sprite.id = general_data.sprite.shift_signed();
     entity.money.vals = general_data.money.shift_signed();
@@ -184,28 +236,37 @@ pub fn load_player(storage: &Storage, entity: &mut PlayerEntity, account_id: Uui
 
     for eq_data in equipment_data.slot.iter() {
         if let Some(data) = entity.equipment.items.get_mut(eq_data.id as usize) {
-            data.num = eq_data.num.shift_signed();
-            data.val = eq_data.val.shift_signed();
-            data.level = eq_data.level as u8;
-            data.data = eq_data.data;
+            *data = eq_data.item();
         }
     }
 
     for inv_data in inventory_data.slot.iter() {
         if let Some(data) = entity.inventory.items.get_mut(inv_data.id as usize) {
-            data.num = inv_data.num.shift_signed();
-            data.val = inv_data.val.shift_signed();
-            data.level = inv_data.level as u8;
-            data.data = inv_data.data;
+            *data = inv_data.item();
         }
     }
 
+    entity.bank_money.vals = bank_data.money.shift_signed();
+    entity.storage.resize(bank_data.slots.max(0) as usize);
+
+    // Older accounts may be missing rows for some of their slots so fill in the gap.
+    let storage_rows = storage_data.slot.len().min(entity.storage.items.len());
+    if storage_rows < entity.storage.items.len() {
+        storage.persistence.save(SaveCommand::StorageRows {
+            cid: character_id,
+            slots: storage_rows..entity.storage.items.len(),
+        })?;
+    }
+
     for item_data in storage_data.slot.iter() {
         if let Some(data) = entity.storage.items.get_mut(item_data.id as usize) {
-            data.num = item_data.num.shift_signed();
-            data.val = item_data.val.shift_signed();
-            data.level = item_data.level as u8;
-            data.data = item_data.data;
+            *data = item_data.item();
+        }
+    }
+
+    for item_data in stash_data.slot.iter() {
+        if let Some(data) = entity.stash.items.get_mut(item_data.id as usize) {
+            *data = item_data.item();
         }
     }
 
@@ -226,23 +287,25 @@ pub fn save_player(storage: &Storage, player: Arc<Mutex<PlayerEntity>>) -> Resul
     let tick = *storage.gettick.borrow();
     let p_data = player.try_lock()?;
     let accountid = p_data.account.id;
-
-    sql_update_account(storage, accountid, p_data.user_access)?;
-    sql_update_general(
-        storage,
-        accountid,
-        PGGeneral {
+    let characterid = p_data.character.id;
+
+    storage.persistence.save(SaveCommand::Account {
+        uid: accountid,
+        user_access: p_data.user_access,
+    })?;
+    storage.persistence.save(SaveCommand::General {
+        cid: characterid,
+        data: PGGeneral {
             sprite: i16::unshift_signed(&p_data.sprite.id),
             money: i64::unshift_signed(&p_data.money.vals),
             resetcount: p_data.general.resetcount,
             itemtimer: get_time_left(p_data.item_timer.itemtimer, tick),
             deathtimer: get_time_left(p_data.combat.death_timer.0, tick),
         },
-    )?;
-    sql_update_combat(
-        storage,
-        accountid,
-        PGCombat {
+    })?;
+    storage.persistence.save(SaveCommand::Combat {
+        cid: characterid,
+        data: PGCombat {
             indeath: p_data.combat.death_type.is_dead(),
             level: p_data.combat.level,
             levelexp: i64::unshift_signed(&p_data.general.levelexp),
@@ -250,16 +313,15 @@ pub fn save_player(storage: &Storage, player: Arc<Mutex<PlayerEntity>>) -> Resul
             vital: p_data.combat.vitals.vital,
             vital_max: p_data.combat.vitals.vitalmax,
         },
-    )?;
-    sql_update_location(
-        storage,
-        accountid,
-        PGLocation {
+    })?;
+    storage.persistence.save(SaveCommand::Location {
+        cid: characterid,
+        data: PGLocation {
             spawn: p_data.movement.spawn.pos,
             pos: p_data.movement.pos,
             dir: p_data.movement.dir as i16,
         },
-    )?;
+    })?;
 
     // Inventory Not needed since its saved per change.
     // Equipment Not needed since its saved per change.
@@ -7,7 +7,7 @@ use crate::{
     gametypes::*,
 };
 
-use sqlx::FromRow;
+use sqlx::{FromRow, PgConnection};
 
 #[derive(Debug, FromRow, Default)]
 pub struct PGAccount {
@@ -65,38 +65,38 @@ pub fn sql_load_account(storage: &Storage, account_id: Uuid) -> Result<PGAccount
     let rt = storage.rt.borrow_mut();
     let local = storage.local.borrow();
 
-    let query = format!(
-        r#"
-        SELECT username, email, passresetcode, useraccess
-        FROM public.account
-        WHERE uid = '{}';
-        "#,
-        account_id,
-    );
-    let data: PGAccount = local.block_on(&rt, sqlx::query_as(&query).fetch_one(&storage.pgconn))?;
+    let data: PGAccount = local.block_on(
+        &rt,
+        sqlx::query_as(
+            r#"
+            SELECT username, email, passresetcode, useraccess
+            FROM public.account
+            WHERE uid = $1;
+            "#,
+        )
+        .bind(account_id)
+        .fetch_one(&storage.pgconn),
+    )?;
 
     Ok(data)
 }
 
-pub fn sql_update_account(storage: &Storage, uid: Uuid, user_access: UserAccess) -> Result<()> {
-    let rt = storage.rt.borrow_mut();
-    let local = storage.local.borrow();
-
-    let query_text = format!(
+pub async fn sql_update_account(
+    conn: &mut PgConnection,
+    uid: Uuid,
+    user_access: UserAccess,
+) -> Result<()> {
+    sqlx::query(
         r#"
         UPDATE public.account
-        SET useraccess=$1
-        WHERE uid = '{0}';
+        SET useraccess = $1
+        WHERE uid = $2;
         "#,
-        uid
-    );
-
-    local.block_on(
-        &rt,
-        sqlx::query(&query_text)
-            .bind(user_access)
-            .execute(&storage.pgconn),
-    )?;
+    )
+    .bind(user_access)
+    .bind(uid)
+    .execute(conn)
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,82 @@
+use crate::{
+    containers::Storage,
+    sql::{integers::Shifting, *},
+};
+use uuid::Uuid;
+
+use crate::gametypes::*;
+
+use sqlx::PgConnection;
+
+pg_table! {
+    pub struct PGBank in "public.bank" {
+        money: i64,
+        slots: i16,
+    }
+}
+
+impl Default for PGBank {
+    fn default() -> Self {
+        Self {
+            money: i64::unshift_signed(&0),
+            slots: BASE_STORAGE as i16,
+        }
+    }
+}
+
+pub fn sql_new_bank(storage: &Storage, uid: Uuid) -> Result<()> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    local.block_on(&rt, pg_insert(&storage.pgconn, uid, &PGBank::default()))
+}
+
+/// Loads the bank row. Accounts made before the bank table existed get one created.
+pub fn sql_load_bank(storage: &Storage, account_id: Uuid) -> Result<PGBank> {
+    let data: Option<PGBank> = {
+        let rt = storage.rt.borrow_mut();
+        let local = storage.local.borrow();
+
+        local.block_on(&rt, pg_select_optional(&storage.pgconn, account_id))?
+    };
+
+    match data {
+        Some(data) => Ok(data),
+        None => {
+            sql_new_bank(storage, account_id)?;
+            Ok(PGBank::default())
+        }
+    }
+}
+
+pub async fn sql_update_bank_money(conn: &mut PgConnection, uid: Uuid, money: i64) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE public.bank
+        SET money = $1
+        WHERE uid = $2;
+        "#,
+    )
+    .bind(money)
+    .bind(uid)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn sql_update_bank_slots(conn: &mut PgConnection, uid: Uuid, slots: i16) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE public.bank
+        SET slots = $1
+        WHERE uid = $2;
+        "#,
+    )
+    .bind(slots)
+    .bind(uid)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,448 @@
+use crate::{
+    containers::{Storage, UserAccess},
+    sql::{integers::Shifting, *},
+};
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::gametypes::*;
+
+use sqlx::{FromRow, PgConnection, PgExecutor, PgPool};
+use tokio::{runtime::Runtime, task};
+
+#[derive(Debug, FromRow)]
+pub struct PGCharacter {
+    pub cid: Uuid,
+    pub slot: i16,
+    pub name: String,
+}
+
+/// Character row along with what the character select screen shows.
+#[derive(Debug, FromRow)]
+pub struct PGCharacterSummary {
+    pub cid: Uuid,
+    pub slot: i16,
+    pub name: String,
+    pub level: Option<i32>,
+    pub sprite: Option<i16>,
+    pub deleted_on: Option<DateTime<Utc>>,
+}
+
+impl PGCharacterSummary {
+    pub fn sprite(&self) -> u16 {
+        self.sprite.map(|sprite| sprite.shift_signed()).unwrap_or(0)
+    }
+
+    /// Seconds left before a deleted character can no longer be restored.
+    pub fn restore_seconds_left(&self, restore_days: u32) -> i64 {
+        self.deleted_on
+            .map(|deleted_on| {
+                (deleted_on + Duration::days(restore_days as i64) - Utc::now())
+                    .num_seconds()
+                    .max(0)
+            })
+            .unwrap_or(0)
+    }
+}
+
+pub fn sql_new_character(storage: &Storage, uid: Uuid, slot: i16, name: &str) -> Result<Uuid> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    let result: (Uuid,) = local.block_on(
+        &rt,
+        sqlx::query_as(
+            r#"
+            INSERT INTO public.characters(uid, slot, name)
+            VALUES ($1, $2, $3) RETURNING cid;
+            "#,
+        )
+        .bind(uid)
+        .bind(slot)
+        .bind(name)
+        .fetch_one(&storage.pgconn),
+    )?;
+
+    Ok(result.0)
+}
+
+/// Accounts made before characters existed kept their data under the account uid.
+/// This gives them a character using the account uid and username so nothing is lost.
+fn sql_new_legacy_character(storage: &Storage, uid: Uuid) -> Result<()> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    local.block_on(
+        &rt,
+        sqlx::query(
+            r#"
+            INSERT INTO public.characters(cid, uid, slot, name)
+            SELECT a.uid, a.uid, 0, a.username
+            FROM public.account a
+            WHERE a.uid = $1
+                AND EXISTS(SELECT 1 FROM public.general g WHERE g.uid = a.uid)
+            ON CONFLICT DO NOTHING;
+            "#,
+        )
+        .bind(uid)
+        .execute(&storage.pgconn),
+    )?;
+
+    Ok(())
+}
+
+pub fn sql_load_characters(storage: &Storage, uid: Uuid) -> Result<Vec<PGCharacterSummary>> {
+    let query = r#"
+        SELECT c.cid, c.slot, c.name, cb.level, g.sprite, c.deleted_on
+        FROM public.characters c
+        LEFT JOIN public.combat cb ON cb.uid = c.cid
+        LEFT JOIN public.general g ON g.uid = c.cid
+        WHERE c.uid = $1
+        ORDER BY c.slot ASC;
+        "#;
+
+    let characters: Vec<PGCharacterSummary> = {
+        let rt = storage.rt.borrow_mut();
+        let local = storage.local.borrow();
+
+        local.block_on(
+            &rt,
+            sqlx::query_as(query).bind(uid).fetch_all(&storage.pgconn),
+        )?
+    };
+
+    if !characters.is_empty() {
+        return Ok(characters);
+    }
+
+    sql_new_legacy_character(storage, uid)?;
+
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    Ok(local.block_on(
+        &rt,
+        sqlx::query_as(query).bind(uid).fetch_all(&storage.pgconn),
+    )?)
+}
+
+pub fn sql_load_character(storage: &Storage, uid: Uuid, slot: i16) -> Result<Option<PGCharacter>> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    Ok(local.block_on(
+        &rt,
+        sqlx::query_as(
+            r#"
+            SELECT cid, slot, name
+            FROM public.characters
+            WHERE uid = $1 AND slot = $2 AND deleted_on IS NULL;
+            "#,
+        )
+        .bind(uid)
+        .bind(slot)
+        .fetch_optional(&storage.pgconn),
+    )?)
+}
+
+/// Checks if a name is used by any character or by another accounts username, ignoring case.
+pub fn sql_character_name_taken(storage: &Storage, uid: Uuid, name: &str) -> Result<bool> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    let check: Check = local.block_on(
+        &rt,
+        sqlx::query_as(
+            r#"
+            SELECT (
+                EXISTS(SELECT 1 FROM public.characters WHERE lower(name) = lower($1))
+                OR EXISTS(SELECT 1 FROM public.account WHERE lower(username) = lower($1) AND uid <> $2)
+            ) as check
+            "#,
+        )
+        .bind(name)
+        .bind(uid)
+        .fetch_one(&storage.pgconn),
+    )?;
+
+    Ok(check.check)
+}
+
+/// Renames the character if it has not been renamed within the cooldown.
+/// Returns false if the character is still on cooldown.
+pub fn sql_rename_character(
+    storage: &Storage,
+    cid: Uuid,
+    name: &str,
+    cooldown_days: u32,
+) -> Result<bool> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    let result = local.block_on(
+        &rt,
+        sqlx::query(
+            r#"
+            UPDATE public.characters
+            SET name = $2, renamed_on = now()
+            WHERE cid = $1
+                AND (renamed_on IS NULL OR renamed_on <= now() - make_interval(days => $3));
+            "#,
+        )
+        .bind(cid)
+        .bind(name)
+        .bind(cooldown_days as i32)
+        .execute(&storage.pgconn),
+    )?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Marks the character as deleted. It can be restored until the restore window passes.
+pub fn sql_soft_delete_character(storage: &Storage, cid: Uuid) -> Result<()> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    local.block_on(
+        &rt,
+        sqlx::query(
+            r#"
+            UPDATE public.characters
+            SET deleted_on = now()
+            WHERE cid = $1;
+            "#,
+        )
+        .bind(cid)
+        .execute(&storage.pgconn),
+    )?;
+
+    Ok(())
+}
+
+/// Restores a deleted character that is still within the restore window.
+/// Returns false if there was nothing to restore.
+pub fn sql_restore_character(
+    storage: &Storage,
+    uid: Uuid,
+    slot: i16,
+    restore_days: u32,
+) -> Result<bool> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    let result = local.block_on(
+        &rt,
+        sqlx::query(
+            r#"
+            UPDATE public.characters
+            SET deleted_on = NULL
+            WHERE uid = $1 AND slot = $2
+                AND deleted_on > now() - make_interval(days => $3);
+            "#,
+        )
+        .bind(uid)
+        .bind(slot)
+        .bind(restore_days as i32)
+        .execute(&storage.pgconn),
+    )?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Removes all characters whose restore window has passed along with all of their data
+/// within a single transaction. Returns how many characters were removed.
+pub fn sql_purge_deleted_characters(storage: &Storage, restore_days: u32) -> Result<usize> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    let purged = local.block_on(&rt, async {
+        let mut tx = storage.pgconn.begin().await?;
+
+        let cids: Vec<(Uuid,)> = sqlx::query_as(
+            r#"
+            SELECT cid FROM public.characters
+            WHERE deleted_on <= now() - make_interval(days => $1)
+            FOR UPDATE;
+            "#,
+        )
+        .bind(restore_days as i32)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let cids: Vec<Uuid> = cids.into_iter().map(|(cid,)| cid).collect();
+
+        if cids.is_empty() {
+            return Ok(0);
+        }
+
+        pg_delete_all::<PGGeneral>(&mut *tx, &cids).await?;
+        pg_delete_all::<PGLocation>(&mut *tx, &cids).await?;
+        pg_delete_all::<PGCombat>(&mut *tx, &cids).await?;
+        pg_delete_all::<PGEquipmentSlot>(&mut *tx, &cids).await?;
+        pg_delete_all::<PGInventorySlot>(&mut *tx, &cids).await?;
+        pg_delete_all::<PGStorageSlot>(&mut *tx, &cids).await?;
+        pg_delete_all::<PGCharacterSnapshot>(&mut *tx, &cids).await?;
+
+        sqlx::query("DELETE FROM public.characters WHERE cid = ANY($1);")
+            .bind(&cids)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok::<usize, AscendingError>(cids.len())
+    })?;
+
+    Ok(purged)
+}
+
+/// Marks the character as online on this server. This waits on an admin edit of the
+/// character that is still in progress, so the load that follows sees the edit.
+pub fn sql_set_character_online(storage: &Storage, cid: Uuid) -> Result<()> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    local.block_on(
+        &rt,
+        sqlx::query(
+            r#"
+            UPDATE public.characters
+            SET online_on = now(), online_server = $2
+            WHERE cid = $1;
+            "#,
+        )
+        .bind(cid)
+        .bind(storage.config.server_id)
+        .execute(&storage.pgconn),
+    )?;
+
+    Ok(())
+}
+
+pub async fn sql_set_character_offline(conn: &mut PgConnection, cid: Uuid) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE public.characters
+        SET online_on = NULL, online_server = NULL
+        WHERE cid = $1;
+        "#,
+    )
+    .bind(cid)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Clears the online marker of every character left online by an earlier run of this
+/// server that did not shut down cleanly. Returns how many were cleared.
+pub fn sql_clear_online_characters(
+    conn: &PgPool,
+    rt: &mut Runtime,
+    local: &task::LocalSet,
+    server_id: i16,
+) -> Result<u64> {
+    let result = local.block_on(
+        rt,
+        sqlx::query(
+            r#"
+            UPDATE public.characters
+            SET online_on = NULL, online_server = NULL
+            WHERE online_server = $1;
+            "#,
+        )
+        .bind(server_id)
+        .execute(conn),
+    )?;
+
+    Ok(result.rows_affected())
+}
+
+/// A Character along with its Account and online marker, as the admin tool sees it.
+#[derive(Debug, FromRow)]
+pub struct PGCharacterAdmin {
+    pub cid: Uuid,
+    pub uid: Uuid,
+    pub slot: i16,
+    pub name: String,
+    pub username: String,
+    pub useraccess: UserAccess,
+    pub deleted_on: Option<DateTime<Utc>>,
+    pub online_on: Option<DateTime<Utc>>,
+    pub online_server: Option<i16>,
+}
+
+impl PGCharacterAdmin {
+    /// Fails if the Character is marked online, as the server would write over the edit.
+    pub fn check_offline(&self) -> Result<()> {
+        match (self.online_on, self.online_server) {
+            (Some(_), server) => Err(AscendingError::CharacterOnline {
+                name: self.name.clone(),
+                server: server.unwrap_or_default(),
+            }),
+            (None, _) => Ok(()),
+        }
+    }
+}
+
+/// Finds a Character by name or id for the admin tool. With `lock` the Character row is
+/// locked until the transaction ends, so the Character can not log in during an edit.
+pub async fn sql_admin_character<'c>(
+    conn: impl PgExecutor<'c>,
+    character: &str,
+    lock: bool,
+) -> Result<Option<PGCharacterAdmin>> {
+    let query = if lock {
+        r#"
+        SELECT c.cid, c.uid, c.slot, c.name, a.username, a.useraccess, c.deleted_on,
+            c.online_on, c.online_server
+        FROM public.characters c
+        JOIN public.account a ON a.uid = c.uid
+        WHERE c.cid = $1 OR c.name = $2
+        FOR UPDATE OF c;
+        "#
+    } else {
+        r#"
+        SELECT c.cid, c.uid, c.slot, c.name, a.username, a.useraccess, c.deleted_on,
+            c.online_on, c.online_server
+        FROM public.characters c
+        JOIN public.account a ON a.uid = c.uid
+        WHERE c.cid = $1 OR c.name = $2;
+        "#
+    };
+
+    let character = sqlx::query_as(query)
+        .bind(Uuid::parse_str(character).ok())
+        .bind(character)
+        .fetch_optional(conn)
+        .await?;
+
+    Ok(character)
+}
+
+/// Locks every Character of the Account until the transaction ends. Fails if any of them
+/// is online, since each of them saves the Account on logout.
+pub async fn sql_lock_account_characters<'c>(conn: impl PgExecutor<'c>, uid: Uuid) -> Result<()> {
+    let characters: Vec<(String, Option<DateTime<Utc>>, Option<i16>)> = sqlx::query_as(
+        r#"
+        SELECT name, online_on, online_server
+        FROM public.characters
+        WHERE uid = $1
+        FOR UPDATE;
+        "#,
+    )
+    .bind(uid)
+    .fetch_all(conn)
+    .await?;
+
+    match characters
+        .into_iter()
+        .find(|(_, online_on, _)| online_on.is_some())
+    {
+        Some((name, _, server)) => Err(AscendingError::CharacterOnline {
+            name,
+            server: server.unwrap_or_default(),
+        }),
+        None => Ok(()),
+    }
+}
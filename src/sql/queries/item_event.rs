@@ -0,0 +1,161 @@
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgExecutor};
+use uuid::Uuid;
+
+use crate::{gametypes::*, items::Item, sql::*};
+
+pg_table! {
+    /// An item ledger entry. `uid` is the Character that caused it, or nil for the server.
+    /// Every entry of one trade shares a `correlation` id.
+    pub struct PGItemEvent in "public.item_events" {
+        created_on: DateTime<Utc>,
+        reason: ItemReason,
+        correlation: Option<Uuid>,
+        source: ItemPlace,
+        source_uid: Option<Uuid>,
+        destination: ItemPlace,
+        destination_uid: Option<Uuid>,
+        pos: Option<Position>,
+        num: i64,
+        val: i32,
+        level: i16,
+        data: [i16; 5],
+        instance: Option<Uuid>,
+        quantity: i32,
+    }
+}
+
+impl PGItemEvent {
+    /// `quantity` of `item` moving from the Void to the Void. Set the places with `from` and `to`.
+    pub fn new(reason: ItemReason, item: &Item, quantity: u16) -> Self {
+        Self {
+            created_on: Utc::now(),
+            reason,
+            correlation: None,
+            source: ItemPlace::Void,
+            source_uid: None,
+            destination: ItemPlace::Void,
+            destination_uid: None,
+            pos: None,
+            num: item.num as i64,
+            val: item.val as i32,
+            level: item.level as i16,
+            data: item.data,
+            instance: item.instance,
+            quantity: quantity as i32,
+        }
+    }
+
+    pub fn from(mut self, place: ItemPlace, uid: Option<Uuid>) -> Self {
+        self.source = place;
+        self.source_uid = uid;
+        self
+    }
+
+    pub fn to(mut self, place: ItemPlace, uid: Option<Uuid>) -> Self {
+        self.destination = place;
+        self.destination_uid = uid;
+        self
+    }
+
+    pub fn at(mut self, pos: Position) -> Self {
+        self.pos = Some(pos);
+        self
+    }
+
+    pub fn correlated(mut self, correlation: Option<Uuid>) -> Self {
+        self.correlation = correlation;
+        self
+    }
+}
+
+/// A ledger entry read back along with its id and who caused it.
+#[derive(Debug, FromRow)]
+pub struct PGItemEventRecord {
+    pub id: i64,
+    pub uid: Uuid,
+    #[sqlx(flatten)]
+    pub event: PGItemEvent,
+}
+
+/// The newest `limit` ledger entries for item `num`, newest first.
+pub async fn sql_item_history<'c>(
+    conn: impl PgExecutor<'c>,
+    num: u32,
+    limit: i64,
+) -> Result<Vec<PGItemEventRecord>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT id, uid, created_on, reason, correlation, source, source_uid, destination,
+            destination_uid, pos, num, val, level, data, instance, quantity
+        FROM public.item_events
+        WHERE num = $1
+        ORDER BY created_on DESC, id DESC
+        LIMIT $2;
+        "#,
+    )
+    .bind(num as i64)
+    .bind(limit)
+    .fetch_all(conn)
+    .await?)
+}
+
+/// The newest `limit` ledger entries a Character caused or had items moved to or from.
+pub async fn sql_character_item_history<'c>(
+    conn: impl PgExecutor<'c>,
+    cid: Uuid,
+    limit: i64,
+) -> Result<Vec<PGItemEventRecord>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT id, uid, created_on, reason, correlation, source, source_uid, destination,
+            destination_uid, pos, num, val, level, data, instance, quantity
+        FROM public.item_events
+        WHERE uid = $1 OR source_uid = $1 OR destination_uid = $1
+        ORDER BY created_on DESC, id DESC
+        LIMIT $2;
+        "#,
+    )
+    .bind(cid)
+    .bind(limit)
+    .fetch_all(conn)
+    .await?)
+}
+
+/// Every ledger entry of one trade, in the order they happened.
+pub async fn sql_correlated_item_events<'c>(
+    conn: impl PgExecutor<'c>,
+    correlation: Uuid,
+) -> Result<Vec<PGItemEventRecord>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT id, uid, created_on, reason, correlation, source, source_uid, destination,
+            destination_uid, pos, num, val, level, data, instance, quantity
+        FROM public.item_events
+        WHERE correlation = $1
+        ORDER BY created_on, id;
+        "#,
+    )
+    .bind(correlation)
+    .fetch_all(conn)
+    .await?)
+}
+
+/// Every ledger entry of one item instance, in the order they happened.
+pub async fn sql_item_instance_events<'c>(
+    conn: impl PgExecutor<'c>,
+    instance: Uuid,
+) -> Result<Vec<PGItemEventRecord>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT id, uid, created_on, reason, correlation, source, source_uid, destination,
+            destination_uid, pos, num, val, level, data, instance, quantity
+        FROM public.item_events
+        WHERE instance = $1
+        ORDER BY created_on, id;
+        "#,
+    )
+    .bind(instance)
+    .fetch_all(conn)
+    .await?)
+}
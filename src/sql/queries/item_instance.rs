@@ -0,0 +1,59 @@
+use sqlx::{FromRow, PgExecutor};
+use uuid::Uuid;
+
+use crate::{gametypes::*, sql::integers::Shifting};
+
+/// A saved slot holding an item instance that has more than one copy saved.
+#[derive(Debug, FromRow)]
+pub struct PGDuplicateItem {
+    pub instance: Uuid,
+    pub place: ItemPlace,
+    pub uid: Uuid,
+    pub id: i16,
+    pub num: i32,
+    /// Copies of the instance in this slot.
+    pub copies: i32,
+}
+
+impl PGDuplicateItem {
+    pub fn item_num(&self) -> u32 {
+        self.num.shift_signed()
+    }
+}
+
+/// Every saved slot holding an item instance that has more than one copy saved, grouped
+/// by instance. Copies are counted from each slot's `val`, so one slot holding two copies
+/// is found and an emptied slot that still has the id is not counted.
+pub async fn sql_duplicate_item_instances<'c>(
+    conn: impl PgExecutor<'c>,
+) -> Result<Vec<PGDuplicateItem>> {
+    Ok(sqlx::query_as(
+        r#"
+        WITH slots AS (
+            SELECT instance, 'Inventory'::item_place AS place, uid, id, num, val FROM public.inventory
+                WHERE instance IS NOT NULL
+            UNION ALL
+            SELECT instance, 'Equipment'::item_place, uid, id, num, val FROM public.equipment
+                WHERE instance IS NOT NULL
+            UNION ALL
+            SELECT instance, 'Storage'::item_place, uid, id, num, val FROM public.storage
+                WHERE instance IS NOT NULL
+            UNION ALL
+            SELECT instance, 'Stash'::item_place, uid, id, num, val FROM public.stash
+                WHERE instance IS NOT NULL
+        ),
+        -- val is stored shifted down by 32768 so the whole u16 range fits in a smallint.
+        copies AS (
+            SELECT instance, place, uid, id, num, val::integer + 32768 AS copies
+            FROM slots
+            WHERE val::integer + 32768 > 0
+        )
+        SELECT instance, place, uid, id, num, copies
+        FROM copies
+        WHERE instance IN (SELECT instance FROM copies GROUP BY instance HAVING SUM(copies) > 1)
+        ORDER BY instance, place, uid, id;
+        "#,
+    )
+    .fetch_all(conn)
+    .await?)
+}
@@ -0,0 +1,32 @@
+use crate::{
+    containers::Storage,
+    sql::{PGLog, SaveCommand},
+};
+use sqlx::PgConnection;
+
+use crate::gametypes::*;
+
+/// Queues the log with the persistence worker so it is kept through a database outage.
+pub fn sql_new_log(storage: &Storage, log: &PGLog) -> Result<()> {
+    storage.persistence.save(SaveCommand::Log {
+        data: Box::new(log.clone()),
+    })
+}
+
+pub async fn sql_insert_log(conn: &mut PgConnection, log: &PGLog) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO public.logs(serverid, userid, logtype, message, ipaddress)
+        VALUES ($1, $2, $3, $4, $5);
+        "#,
+    )
+    .bind(log.serverid)
+    .bind(log.userid)
+    .bind(log.logtype)
+    .bind(&log.message)
+    .bind(&log.ipaddress)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgExecutor, types::Json};
+use uuid::Uuid;
+
+use crate::{containers::*, gametypes::*, items::Item, sql::*};
+
+/// The saved state of a Character, as snapshots keep it and the admin tool prints it.
+/// Numbers are the values the game uses, not the shifted ones the database stores.
+/// Only slots holding an item are listed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CharacterState {
+    pub sprite: u16,
+    pub money: u64,
+    pub resetcount: i16,
+    pub level: i32,
+    pub levelexp: u64,
+    pub pk: bool,
+    pub indeath: bool,
+    pub vital: [i32; VITALS_MAX],
+    pub vital_max: [i32; VITALS_MAX],
+    pub pos: Position,
+    pub spawn: Position,
+    pub dir: u8,
+    pub inventory: BTreeMap<i16, Item>,
+    pub equipment: BTreeMap<i16, Item>,
+    pub storage: BTreeMap<i16, Item>,
+}
+
+fn slot_items(slots: impl Iterator<Item = (i16, Item)>) -> BTreeMap<i16, Item> {
+    slots.filter(|(_, item)| item.val > 0).collect()
+}
+
+impl CharacterState {
+    pub fn from_player(player: &PlayerEntity) -> Self {
+        let items = |items: &[Item]| {
+            slot_items(
+                items
+                    .iter()
+                    .copied()
+                    .enumerate()
+                    .map(|(id, item)| (id as i16, item)),
+            )
+        };
+
+        Self {
+            sprite: player.sprite.id,
+            money: player.money.vals,
+            resetcount: player.general.resetcount,
+            level: player.combat.level,
+            levelexp: player.general.levelexp,
+            pk: player.general.pk,
+            indeath: player.combat.death_type.is_dead(),
+            vital: player.combat.vitals.vital,
+            vital_max: player.combat.vitals.vitalmax,
+            pos: player.movement.pos,
+            spawn: player.movement.spawn.pos,
+            dir: player.movement.dir,
+            inventory: items(&player.inventory.items),
+            equipment: items(&player.equipment.items),
+            storage: items(&player.storage.items),
+        }
+    }
+
+    pub fn from_rows(
+        general: &PGGeneral,
+        combat: &PGCombat,
+        location: &PGLocation,
+        inventory: &[PGInventorySlot],
+        equipment: &[PGEquipmentSlot],
+        storage: &[PGStorageSlot],
+    ) -> Self {
+        Self {
+            sprite: general.sprite.shift_signed(),
+            money: general.money.shift_signed(),
+            resetcount: general.resetcount,
+            level: combat.level,
+            levelexp: combat.levelexp.shift_signed(),
+            pk: combat.pk,
+            indeath: combat.indeath,
+            vital: combat.vital,
+            vital_max: combat.vital_max,
+            pos: location.pos,
+            spawn: location.spawn,
+            dir: location.dir as u8,
+            inventory: slot_items(inventory.iter().map(|slot| (slot.id, slot.item()))),
+            equipment: slot_items(equipment.iter().map(|slot| (slot.id, slot.item()))),
+            storage: slot_items(storage.iter().map(|slot| (slot.id, slot.item()))),
+        }
+    }
+}
+
+pg_table! {
+    /// A copy of a Character's state. `uid` is the Character.
+    pub struct PGCharacterSnapshot in "public.character_snapshots" {
+        created_on: DateTime<Utc>,
+        reason: SnapshotReason,
+        data: Json<CharacterState>,
+    }
+}
+
+impl PGCharacterSnapshot {
+    pub fn new(reason: SnapshotReason, state: CharacterState) -> Self {
+        Self {
+            created_on: Utc::now(),
+            reason,
+            data: Json(state),
+        }
+    }
+}
+
+/// A snapshot read back along with its id and Character.
+#[derive(Debug, FromRow)]
+pub struct PGCharacterSnapshotRecord {
+    pub id: i64,
+    pub uid: Uuid,
+    #[sqlx(flatten)]
+    pub snapshot: PGCharacterSnapshot,
+}
+
+/// Queues a snapshot of the player's current state, unless `character_snapshots` is off.
+pub fn snapshot_player(
+    storage: &Storage,
+    player: &PlayerEntity,
+    reason: SnapshotReason,
+) -> Result<()> {
+    if !storage.config.character_snapshots {
+        return Ok(());
+    }
+
+    storage.persistence.save(SaveCommand::Snapshot {
+        cid: player.character.id,
+        data: Box::new(PGCharacterSnapshot::new(
+            reason,
+            CharacterState::from_player(player),
+        )),
+    })
+}
+
+/// The newest `limit` snapshots of a Character, newest first.
+pub async fn sql_character_snapshots<'c>(
+    conn: impl PgExecutor<'c>,
+    cid: Uuid,
+    limit: i64,
+) -> Result<Vec<PGCharacterSnapshotRecord>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT id, uid, created_on, reason, data
+        FROM public.character_snapshots
+        WHERE uid = $1
+        ORDER BY created_on DESC, id DESC
+        LIMIT $2;
+        "#,
+    )
+    .bind(cid)
+    .bind(limit)
+    .fetch_all(conn)
+    .await?)
+}
+
+/// One snapshot of a Character by its id.
+pub async fn sql_character_snapshot<'c>(
+    conn: impl PgExecutor<'c>,
+    cid: Uuid,
+    id: i64,
+) -> Result<Option<PGCharacterSnapshotRecord>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT id, uid, created_on, reason, data
+        FROM public.character_snapshots
+        WHERE uid = $1 AND id = $2;
+        "#,
+    )
+    .bind(cid)
+    .bind(id)
+    .fetch_optional(conn)
+    .await?)
+}
+
+/// Removes snapshots older than `keep_days` and all but the newest `keep_count` of each
+/// Character. A limit of 0 is not applied. Returns how many were removed.
+pub async fn sql_prune_character_snapshots<'c>(
+    conn: impl PgExecutor<'c>,
+    keep_days: u32,
+    keep_count: u32,
+) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM public.character_snapshots
+        WHERE ($1 > 0 AND created_on < now() - make_interval(days => $1))
+            OR ($2 > 0 AND id IN (
+                SELECT id FROM (
+                    SELECT id, row_number() OVER (
+                        PARTITION BY uid ORDER BY created_on DESC, id DESC
+                    ) AS newest
+                    FROM public.character_snapshots
+                ) AS ranked
+                WHERE newest > $2
+            ));
+        "#,
+    )
+    .bind(keep_days as i32)
+    .bind(keep_count as i64)
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected())
+}
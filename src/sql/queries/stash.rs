@@ -0,0 +1,49 @@
+use crate::{containers::Storage, sql::*};
+use uuid::Uuid;
+
+use crate::gametypes::*;
+
+use sqlx::{FromRow, PgConnection};
+
+pg_slot_table! {
+    pub struct PGStashSlot in "public.stash"
+}
+
+#[derive(Debug, FromRow)]
+pub struct PGStash {
+    pub slot: Vec<PGStashSlot>,
+}
+
+pub fn sql_new_stash(storage: &Storage, uid: Uuid) -> Result<()> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    local.block_on(
+        &rt,
+        pg_insert_range(&storage.pgconn, uid, 0..MAX_STASH, &PGStashSlot::empty()),
+    )
+}
+
+/// Loads the Accounts stash. Accounts made before the stash existed get one created.
+pub fn sql_load_stash(storage: &Storage, account_id: Uuid) -> Result<PGStash> {
+    let slot: Vec<PGStashSlot> = {
+        let rt = storage.rt.borrow_mut();
+        let local = storage.local.borrow();
+
+        local.block_on(&rt, pg_select_all(&storage.pgconn, account_id))?
+    };
+
+    if slot.is_empty() {
+        sql_new_stash(storage, account_id)?;
+    }
+
+    Ok(PGStash { slot })
+}
+
+pub async fn sql_update_stash_slot(
+    conn: &mut PgConnection,
+    uid: Uuid,
+    data: &PGStashSlot,
+) -> Result<()> {
+    pg_update(conn, uid, data).await
+}
@@ -0,0 +1,98 @@
+use crate::{containers::Storage, sql::Check};
+use uuid::Uuid;
+
+use crate::gametypes::*;
+
+pub fn sql_new_email_verification(storage: &Storage, uid: Uuid, token: &str) -> Result<()> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    local.block_on(
+        &rt,
+        sqlx::query(
+            r#"
+            INSERT INTO public.email_verification(uid, token)
+            VALUES ($1, $2)
+            ON CONFLICT (uid) DO UPDATE SET token = $2, created_on = now();
+            "#,
+        )
+        .bind(uid)
+        .bind(token)
+        .execute(&storage.pgconn),
+    )?;
+
+    Ok(())
+}
+
+/// Checks if the Account was sent a token within the last `secs` seconds.
+pub fn sql_email_verification_sent_within(storage: &Storage, uid: Uuid, secs: u32) -> Result<bool> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    let check: Check = local.block_on(
+        &rt,
+        sqlx::query_as(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM public.email_verification
+                WHERE uid = $1 AND created_on > now() - make_interval(secs => $2)
+            ) as check
+            "#,
+        )
+        .bind(uid)
+        .bind(secs as f64)
+        .fetch_one(&storage.pgconn),
+    )?;
+
+    Ok(check.check)
+}
+
+/// Accounts are verified once they no longer have a pending verification token.
+pub fn sql_is_email_verified(storage: &Storage, uid: Uuid) -> Result<bool> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    let check: Check = local.block_on(
+        &rt,
+        sqlx::query_as(
+            r#"
+            SELECT NOT EXISTS(SELECT 1 FROM public.email_verification WHERE uid = $1) as check
+            "#,
+        )
+        .bind(uid)
+        .fetch_one(&storage.pgconn),
+    )?;
+
+    Ok(check.check)
+}
+
+/// Removes the pending token if it matches the account found by username or email, ignoring case,
+/// and was sent within `ttl_mins`. Returns false if it did not match or has expired.
+pub fn sql_verify_email(
+    storage: &Storage,
+    email: &str,
+    token: &str,
+    ttl_mins: u32,
+) -> Result<bool> {
+    let rt = storage.rt.borrow_mut();
+    let local = storage.local.borrow();
+
+    let result = local.block_on(
+        &rt,
+        sqlx::query(
+            r#"
+            DELETE FROM public.email_verification v
+            USING public.account a
+            WHERE v.uid = a.uid AND v.token = $2
+                AND v.created_on > now() - make_interval(mins => $3)
+                AND (lower(a.username) = lower($1) OR lower(a.email) = lower($1));
+            "#,
+        )
+        .bind(email)
+        .bind(token)
+        .bind(ttl_mins as i32)
+        .execute(&storage.pgconn),
+    )?;
+
+    Ok(result.rows_affected() > 0)
+}
@@ -224,3 +224,336 @@ pub const STORAGE_SCHEMA_ALTER: &str = "
 ALTER TABLE IF EXISTS public.storage
     OWNER to postgres;
 ";
+
+#[rustfmt::skip]
+pub const BANK_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS public.bank
+(
+    uid uuid NOT NULL,
+    money bigint NOT NULL,
+    slots smallint NOT NULL,
+    CONSTRAINT bank_pkey PRIMARY KEY (uid)
+)
+
+WITH (
+    FILLFACTOR = 70
+)
+TABLESPACE pg_default;
+";
+
+#[rustfmt::skip]
+pub const BANK_SCHEMA_ALTER: &str = "
+ALTER TABLE IF EXISTS public.bank
+    OWNER to server;
+";
+
+#[rustfmt::skip]
+pub const CHARACTERS_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS public.characters
+(
+    cid uuid NOT NULL DEFAULT uuid_generate_v7(),
+    uid uuid NOT NULL,
+    slot smallint NOT NULL,
+    name text COLLATE pg_catalog.\"default\" NOT NULL,
+    created_on timestamp with time zone NOT NULL DEFAULT now(),
+    renamed_on timestamp with time zone,
+    deleted_on timestamp with time zone,
+    CONSTRAINT characters_pkey PRIMARY KEY (cid),
+    CONSTRAINT character_name UNIQUE (name),
+    CONSTRAINT character_slot UNIQUE (uid, slot)
+)
+
+WITH (
+    FILLFACTOR = 70
+)
+TABLESPACE pg_default;
+";
+
+#[rustfmt::skip]
+pub const CHARACTERS_SCHEMA_ALTER: &str = "
+ALTER TABLE IF EXISTS public.characters
+    OWNER to server;
+";
+
+#[rustfmt::skip]
+pub const STASH_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS public.stash
+(
+    uid uuid NOT NULL,
+    id smallint NOT NULL,
+    num integer NOT NULL,
+    val smallint NOT NULL,
+    level smallint NOT NULL,
+    data smallint[] NOT NULL
+)
+
+WITH (
+    FILLFACTOR = 70
+)
+TABLESPACE pg_default;
+";
+
+#[rustfmt::skip]
+pub const STASH_SCHEMA_ALTER: &str = "
+ALTER TABLE IF EXISTS public.stash
+    OWNER to server;
+";
+
+#[rustfmt::skip]
+pub const EMAIL_VERIFICATION_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS public.email_verification
+(
+    uid uuid NOT NULL,
+    token text COLLATE pg_catalog.\"default\" NOT NULL,
+    created_on timestamp with time zone NOT NULL DEFAULT now(),
+    CONSTRAINT email_verification_pkey PRIMARY KEY (uid)
+)
+
+TABLESPACE pg_default;
+";
+
+#[rustfmt::skip]
+pub const EMAIL_VERIFICATION_SCHEMA_ALTER: &str = "
+ALTER TABLE IF EXISTS public.email_verification
+    OWNER to server;
+";
+
+#[rustfmt::skip]
+pub const SCHEMA_VERSION_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS public.schema_version
+(
+    version integer NOT NULL,
+    name text COLLATE pg_catalog.\"default\" NOT NULL,
+    applied_on timestamp with time zone NOT NULL DEFAULT now(),
+    CONSTRAINT schema_version_pkey PRIMARY KEY (version)
+)
+
+TABLESPACE pg_default;
+";
+
+#[rustfmt::skip]
+pub const GENERAL_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS general_uid ON public.general (uid);
+";
+
+#[rustfmt::skip]
+pub const LOCATION_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS locations_uid ON public.locations (uid);
+";
+
+#[rustfmt::skip]
+pub const COMBAT_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS combat_uid ON public.combat (uid);
+";
+
+#[rustfmt::skip]
+pub const EQUIPMENT_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS equipment_uid ON public.equipment (uid, id);
+";
+
+#[rustfmt::skip]
+pub const INVENTORY_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS inventory_uid ON public.inventory (uid, id);
+";
+
+#[rustfmt::skip]
+pub const STORAGE_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS storage_uid ON public.storage (uid, id);
+";
+
+#[rustfmt::skip]
+pub const STASH_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS stash_uid ON public.stash (uid, id);
+";
+
+#[rustfmt::skip]
+pub const ITEM_EVENTS_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS public.item_events
+(
+    id bigserial NOT NULL,
+    uid uuid NOT NULL,
+    created_on timestamp with time zone NOT NULL DEFAULT now(),
+    reason \"item_reason\" NOT NULL,
+    correlation uuid,
+    source \"item_place\" NOT NULL,
+    source_uid uuid,
+    destination \"item_place\" NOT NULL,
+    destination_uid uuid,
+    pos \"location\",
+    num bigint NOT NULL,
+    val integer NOT NULL,
+    level smallint NOT NULL,
+    data smallint[] NOT NULL,
+    quantity integer NOT NULL,
+    CONSTRAINT item_events_pkey PRIMARY KEY (id)
+)
+
+TABLESPACE pg_default;
+";
+
+#[rustfmt::skip]
+pub const ITEM_EVENTS_SCHEMA_ALTER: &str = "
+ALTER TABLE IF EXISTS public.item_events
+    OWNER to server;
+";
+
+#[rustfmt::skip]
+pub const ITEM_EVENTS_NUM_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS item_events_num ON public.item_events (num, created_on);
+";
+
+#[rustfmt::skip]
+pub const ITEM_EVENTS_UID_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS item_events_uid ON public.item_events (uid, created_on);
+";
+
+#[rustfmt::skip]
+pub const ITEM_EVENTS_CORRELATION_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS item_events_correlation ON public.item_events (correlation)
+    WHERE correlation IS NOT NULL;
+";
+
+#[rustfmt::skip]
+pub const ITEM_EVENTS_SOURCE_UID_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS item_events_source_uid ON public.item_events (source_uid, created_on)
+    WHERE source_uid IS NOT NULL;
+";
+
+#[rustfmt::skip]
+pub const ITEM_EVENTS_DESTINATION_UID_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS item_events_destination_uid ON public.item_events (destination_uid, created_on)
+    WHERE destination_uid IS NOT NULL;
+";
+
+#[rustfmt::skip]
+pub const EQUIPMENT_INSTANCE: &str = "
+ALTER TABLE IF EXISTS public.equipment ADD COLUMN IF NOT EXISTS instance uuid;
+";
+
+#[rustfmt::skip]
+pub const INVENTORY_INSTANCE: &str = "
+ALTER TABLE IF EXISTS public.inventory ADD COLUMN IF NOT EXISTS instance uuid;
+";
+
+#[rustfmt::skip]
+pub const STORAGE_INSTANCE: &str = "
+ALTER TABLE IF EXISTS public.storage ADD COLUMN IF NOT EXISTS instance uuid;
+";
+
+#[rustfmt::skip]
+pub const STASH_INSTANCE: &str = "
+ALTER TABLE IF EXISTS public.stash ADD COLUMN IF NOT EXISTS instance uuid;
+";
+
+#[rustfmt::skip]
+pub const ITEM_EVENTS_INSTANCE: &str = "
+ALTER TABLE IF EXISTS public.item_events ADD COLUMN IF NOT EXISTS instance uuid;
+";
+
+#[rustfmt::skip]
+pub const ITEM_EVENTS_INSTANCE_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS item_events_instance ON public.item_events (instance, created_on)
+    WHERE instance IS NOT NULL;
+";
+
+#[rustfmt::skip]
+pub const CHARACTERS_ONLINE: &str = "
+ALTER TABLE IF EXISTS public.characters
+    ADD COLUMN IF NOT EXISTS online_on timestamp with time zone,
+    ADD COLUMN IF NOT EXISTS online_server smallint;
+";
+
+/// Fails the migration listing the accounts that only differ in case.
+#[rustfmt::skip]
+pub const ACCOUNT_CASE_COLLISIONS: &str = "
+DO $$
+DECLARE
+    collisions text;
+BEGIN
+    SELECT string_agg(names, '; ') INTO collisions FROM (
+        SELECT 'usernames ' || string_agg(username, ', ' ORDER BY username) AS names
+            FROM public.account GROUP BY lower(username) HAVING count(*) > 1
+        UNION ALL
+        SELECT 'emails ' || string_agg(email, ', ' ORDER BY email) AS names
+            FROM public.account GROUP BY lower(email) HAVING count(*) > 1
+    ) AS found;
+
+    IF collisions IS NOT NULL THEN
+        RAISE EXCEPTION 'Accounts differ only in case, rename them before migrating: %', collisions;
+    END IF;
+END
+$$;
+";
+
+#[rustfmt::skip]
+pub const ACCOUNT_USERNAME_LOWER_INDEX: &str = "
+CREATE UNIQUE INDEX IF NOT EXISTS account_username_lower ON public.account (lower(username));
+";
+
+#[rustfmt::skip]
+pub const ACCOUNT_EMAIL_LOWER_INDEX: &str = "
+CREATE UNIQUE INDEX IF NOT EXISTS account_email_lower ON public.account (lower(email));
+";
+
+#[rustfmt::skip]
+pub const ACCOUNT_DROP_CASE_SENSITIVE: &str = "
+ALTER TABLE IF EXISTS public.account
+    DROP CONSTRAINT IF EXISTS username,
+    DROP CONSTRAINT IF EXISTS email;
+";
+
+/// Fails the migration listing the characters that only differ in case.
+#[rustfmt::skip]
+pub const CHARACTERS_CASE_COLLISIONS: &str = "
+DO $$
+DECLARE
+    collisions text;
+BEGIN
+    SELECT string_agg(names, '; ') INTO collisions FROM (
+        SELECT string_agg(name, ', ' ORDER BY name) AS names
+            FROM public.characters GROUP BY lower(name) HAVING count(*) > 1
+    ) AS found;
+
+    IF collisions IS NOT NULL THEN
+        RAISE EXCEPTION 'Characters differ only in case, rename them before migrating: %', collisions;
+    END IF;
+END
+$$;
+";
+
+#[rustfmt::skip]
+pub const CHARACTERS_NAME_LOWER_INDEX: &str = "
+CREATE UNIQUE INDEX IF NOT EXISTS characters_name_lower ON public.characters (lower(name));
+";
+
+#[rustfmt::skip]
+pub const CHARACTERS_DROP_CASE_SENSITIVE: &str = "
+ALTER TABLE IF EXISTS public.characters
+    DROP CONSTRAINT IF EXISTS character_name;
+";
+
+#[rustfmt::skip]
+pub const CHARACTER_SNAPSHOTS_SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS public.character_snapshots
+(
+    id bigserial NOT NULL,
+    uid uuid NOT NULL,
+    created_on timestamp with time zone NOT NULL DEFAULT now(),
+    reason \"snapshot_reason\" NOT NULL,
+    data jsonb NOT NULL,
+    CONSTRAINT character_snapshots_pkey PRIMARY KEY (id)
+)
+
+TABLESPACE pg_default;
+";
+
+#[rustfmt::skip]
+pub const CHARACTER_SNAPSHOTS_SCHEMA_ALTER: &str = "
+ALTER TABLE IF EXISTS public.character_snapshots
+    OWNER to server;
+";
+
+#[rustfmt::skip]
+pub const CHARACTER_SNAPSHOTS_UID_INDEX: &str = "
+CREATE INDEX IF NOT EXISTS character_snapshots_uid ON public.character_snapshots (uid, created_on);
+";
@@ -29,3 +29,57 @@ pub const USERACCESS_SCHEMA_ALTER: &str = "
 ALTER TYPE public.\"user_access\"
     OWNER TO postgres;
 ";
+
+#[rustfmt::skip]
+pub const ITEM_PLACE_SCHEMA: &str = "
+DO $$ BEGIN
+    CREATE TYPE public.\"item_place\" AS ENUM
+        ('Inventory', 'Equipment', 'Storage', 'Stash', 'Trade', 'Map', 'Shop', 'Npc', 'Void');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+";
+
+#[rustfmt::skip]
+pub const ITEM_PLACE_SCHEMA_ALTER: &str = "
+ALTER TYPE public.\"item_place\"
+    OWNER TO postgres;
+";
+
+#[rustfmt::skip]
+pub const ITEM_REASON_SCHEMA: &str = "
+DO $$ BEGIN
+    CREATE TYPE public.\"item_reason\" AS ENUM
+        ('Drop', 'NpcDrop', 'Pickup', 'Trade', 'ShopBuy', 'ShopSell', 'Deposit', 'Withdraw',
+         'StashDeposit', 'StashWithdraw', 'Delete', 'Use', 'Equip', 'Unequip');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+";
+
+#[rustfmt::skip]
+pub const ITEM_REASON_ADMIN: &str = "
+ALTER TYPE public.\"item_reason\" ADD VALUE IF NOT EXISTS 'Admin';
+";
+
+#[rustfmt::skip]
+pub const ITEM_REASON_SCHEMA_ALTER: &str = "
+ALTER TYPE public.\"item_reason\"
+    OWNER TO postgres;
+";
+
+#[rustfmt::skip]
+pub const SNAPSHOT_REASON_SCHEMA: &str = "
+DO $$ BEGIN
+    CREATE TYPE public.\"snapshot_reason\" AS ENUM
+        ('Timer', 'Trade', 'LevelUp', 'Logout', 'Admin');
+EXCEPTION
+    WHEN duplicate_object THEN null;
+END $$;
+";
+
+#[rustfmt::skip]
+pub const SNAPSHOT_REASON_SCHEMA_ALTER: &str = "
+ALTER TYPE public.\"snapshot_reason\"
+    OWNER TO postgres;
+";
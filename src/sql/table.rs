@@ -0,0 +1,425 @@
+use crate::gametypes::*;
+use itertools::Itertools;
+use sqlx::{
+    FromRow, PgExecutor, Postgres,
+    postgres::{PgArguments, PgRow},
+    query::{Query, QueryAs},
+};
+use std::ops::Range;
+use uuid::Uuid;
+
+pub type PgQuery<'q> = Query<'q, Postgres, PgArguments>;
+pub type PgQueryAs<'q, T> = QueryAs<'q, Postgres, T, PgArguments>;
+
+/// The statements for a PgTable. They are built once from the column names and only
+/// ever take values as bound parameters, so sqlx keeps them prepared per connection.
+pub struct PgStatements {
+    pub select: String,
+    pub insert: String,
+    pub insert_range: String,
+    pub update: String,
+    pub delete: String,
+}
+
+impl PgStatements {
+    pub fn new(table: &str, keys: &[&str], columns: &[&str]) -> Self {
+        let fields = keys.iter().chain(columns).join(", ");
+        let key_filter: String = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| format!(" AND {} = ${}", key, i + 2))
+            .collect();
+        let order = if keys.is_empty() {
+            String::new()
+        } else {
+            format!(" ORDER BY {}", keys.join(", "))
+        };
+
+        Self {
+            select: format!("SELECT {} FROM {} WHERE uid = $1{}", fields, table, order),
+            insert: format!(
+                "INSERT INTO {}(uid, {}) VALUES ({})",
+                table,
+                fields,
+                (1..=keys.len() + columns.len() + 1)
+                    .map(|i| format!("${}", i))
+                    .join(", ")
+            ),
+            // Only used by tables with a single key, which is filled in from the range.
+            insert_range: format!(
+                "INSERT INTO {}(uid, {}) SELECT $1, generate_series($2::integer, $3::integer), {}",
+                table,
+                fields,
+                (4..columns.len() + 4).map(|i| format!("${}", i)).join(", ")
+            ),
+            update: format!(
+                "UPDATE {} SET {} WHERE uid = $1{}",
+                table,
+                columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, column)| format!("{} = ${}", column, i + keys.len() + 2))
+                    .join(", "),
+                key_filter
+            ),
+            delete: format!("DELETE FROM {} WHERE uid = ANY($1)", table),
+        }
+    }
+}
+
+/// A struct stored in a table whose rows belong to an Account or Character `uid`.
+/// Use `pg_table!` to declare one so the column lists match the struct fields.
+pub trait PgTable: for<'r> FromRow<'r, PgRow> + Send + Unpin {
+    const TABLE: &'static str;
+    /// Columns besides `uid` that pick out one row, like a slot id.
+    const KEYS: &'static [&'static str];
+    const COLUMNS: &'static [&'static str];
+
+    fn statements() -> &'static PgStatements;
+    fn bind_keys<'q>(&'q self, query: PgQuery<'q>) -> PgQuery<'q>;
+    fn bind_columns<'q>(&'q self, query: PgQuery<'q>) -> PgQuery<'q>;
+}
+
+/// Declares a struct stored in a PgTable. Fields after `by` are keys besides `uid`.
+///
+/// ```ignore
+/// pg_table! {
+///     pub struct PGGeneral in "public.general" {
+///         sprite: i16,
+///         money: i64,
+///     }
+/// }
+/// ```
+macro_rules! pg_table {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident in $table:literal {
+            $($column:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $crate::sql::pg_table!(@impl [$(#[$meta])*] $name, $table, [], [$($column: $ty),*]);
+    };
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident in $table:literal by $key:ident: $key_ty:ty {
+            $($column:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $crate::sql::pg_table!(@impl [$(#[$meta])*] $name, $table, [$key: $key_ty], [$($column: $ty),*]);
+    };
+    (
+        @impl [$(#[$meta:meta])*] $name:ident, $table:literal,
+        [$($key:ident: $key_ty:ty),*], [$($column:ident: $ty:ty),*]
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, sqlx::FromRow)]
+        pub struct $name {
+            $(pub $key: $key_ty,)*
+            $(pub $column: $ty,)*
+        }
+
+        impl $crate::sql::PgTable for $name {
+            const TABLE: &'static str = $table;
+            const KEYS: &'static [&'static str] = &[$(stringify!($key)),*];
+            const COLUMNS: &'static [&'static str] = &[$(stringify!($column)),*];
+
+            fn statements() -> &'static $crate::sql::PgStatements {
+                static STATEMENTS: std::sync::OnceLock<$crate::sql::PgStatements> =
+                    std::sync::OnceLock::new();
+
+                STATEMENTS.get_or_init(|| {
+                    $crate::sql::PgStatements::new(Self::TABLE, Self::KEYS, Self::COLUMNS)
+                })
+            }
+
+            fn bind_keys<'q>(&'q self, query: $crate::sql::PgQuery<'q>) -> $crate::sql::PgQuery<'q> {
+                query$(.bind(&self.$key))*
+            }
+
+            fn bind_columns<'q>(
+                &'q self,
+                query: $crate::sql::PgQuery<'q>,
+            ) -> $crate::sql::PgQuery<'q> {
+                query$(.bind(&self.$column))*
+            }
+        }
+    };
+}
+
+/// Declares a PgTable of item slots keyed by the slot `id`, like the inventory.
+macro_rules! pg_slot_table {
+    ($(#[$meta:meta])* pub struct $name:ident in $table:literal) => {
+        $crate::sql::pg_table! {
+            $(#[$meta])*
+            pub struct $name in $table by id: i16 {
+                num: i32,
+                val: i16,
+                level: i16,
+                data: [i16; 5],
+                instance: Option<uuid::Uuid>,
+            }
+        }
+
+        impl $name {
+            pub fn new(id: usize, item: &$crate::items::Item) -> Self {
+                use $crate::sql::integers::Shifting;
+
+                Self {
+                    id: id as i16,
+                    num: i32::unshift_signed(&item.num),
+                    val: i16::unshift_signed(&item.val),
+                    level: item.level as i16,
+                    data: item.data,
+                    // An empty slot can still hold the rest of what was taken out of it.
+                    instance: if item.val == 0 { None } else { item.instance },
+                }
+            }
+
+            pub fn item(&self) -> $crate::items::Item {
+                use $crate::sql::integers::Shifting;
+
+                $crate::items::Item {
+                    num: self.num.shift_signed(),
+                    val: self.val.shift_signed(),
+                    level: self.level as u8,
+                    data: self.data,
+                    instance: self.instance,
+                }
+            }
+
+            /// What a slot holds before anything is put in it.
+            pub fn empty() -> Self {
+                use $crate::sql::integers::Shifting;
+
+                Self {
+                    id: 0,
+                    num: i32::unshift_signed(&0),
+                    val: i16::unshift_signed(&0),
+                    level: 0,
+                    data: [0; 5],
+                    instance: None,
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use {pg_slot_table, pg_table};
+
+/// Loads the one row `uid` has in the table.
+pub async fn pg_select<'c, T: PgTable>(conn: impl PgExecutor<'c>, uid: Uuid) -> Result<T> {
+    let query: PgQueryAs<'_, T> = sqlx::query_as(&T::statements().select);
+
+    Ok(query.bind(uid).fetch_one(conn).await?)
+}
+
+pub async fn pg_select_optional<'c, T: PgTable>(
+    conn: impl PgExecutor<'c>,
+    uid: Uuid,
+) -> Result<Option<T>> {
+    let query: PgQueryAs<'_, T> = sqlx::query_as(&T::statements().select);
+
+    Ok(query.bind(uid).fetch_optional(conn).await?)
+}
+
+/// Loads every row `uid` has in the table, ordered by its keys.
+pub async fn pg_select_all<'c, T: PgTable>(conn: impl PgExecutor<'c>, uid: Uuid) -> Result<Vec<T>> {
+    let query: PgQueryAs<'_, T> = sqlx::query_as(&T::statements().select);
+
+    Ok(query.bind(uid).fetch_all(conn).await?)
+}
+
+pub async fn pg_insert<'c, T: PgTable>(
+    conn: impl PgExecutor<'c>,
+    uid: Uuid,
+    row: &T,
+) -> Result<()> {
+    let query = sqlx::query(&T::statements().insert).bind(uid);
+
+    row.bind_columns(row.bind_keys(query)).execute(conn).await?;
+    Ok(())
+}
+
+/// Inserts a copy of `row` for every key in `keys`. Only for tables with a single key.
+pub async fn pg_insert_range<'c, T: PgTable>(
+    conn: impl PgExecutor<'c>,
+    uid: Uuid,
+    keys: Range<usize>,
+    row: &T,
+) -> Result<()> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+
+    let query = sqlx::query(&T::statements().insert_range)
+        .bind(uid)
+        .bind(keys.start as i32)
+        .bind(keys.end as i32 - 1);
+
+    row.bind_columns(query).execute(conn).await?;
+    Ok(())
+}
+
+/// Writes every column of `row` to the row with the same `uid` and keys.
+pub async fn pg_update<'c, T: PgTable>(
+    conn: impl PgExecutor<'c>,
+    uid: Uuid,
+    row: &T,
+) -> Result<()> {
+    let query = sqlx::query(&T::statements().update).bind(uid);
+
+    row.bind_columns(row.bind_keys(query)).execute(conn).await?;
+    Ok(())
+}
+
+/// Removes every row owned by any of `uids`.
+pub async fn pg_delete_all<'c, T: PgTable>(conn: impl PgExecutor<'c>, uids: &[Uuid]) -> Result<()> {
+    sqlx::query(&T::statements().delete)
+        .bind(uids)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// These run against the database in `ASCENDING_TEST_DATABASE_URL` and are ignored unless
+/// run with `--ignored`. Use a disposable database; it is migrated to the latest schema and
+/// every test rolls back what it wrote.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{containers::UserAccess, items::Item, sql::*};
+    use sqlx::{PgPool, postgres::PgPoolOptions};
+    use std::sync::Mutex;
+    use tokio::{runtime::Runtime, task};
+
+    static MIGRATE: Mutex<()> = Mutex::new(());
+
+    fn test_database() -> Result<(Runtime, PgPool)> {
+        let url = std::env::var("ASCENDING_TEST_DATABASE_URL")
+            .expect("ASCENDING_TEST_DATABASE_URL is not set");
+
+        let mut rt = Runtime::new()?;
+        let local = task::LocalSet::new();
+        let pool = rt.block_on(PgPoolOptions::new().max_connections(1).connect(&url))?;
+
+        // Tests run in parallel and the migrations are not meant to race each other.
+        let _migrating = MIGRATE.lock().unwrap_or_else(|e| e.into_inner());
+        migrate(&pool, &mut rt, &local, false)?;
+
+        Ok((rt, pool))
+    }
+
+    #[test]
+    #[ignore = "needs ASCENDING_TEST_DATABASE_URL"]
+    fn table_rows_round_trip() -> Result<()> {
+        let (rt, pool) = test_database()?;
+
+        rt.block_on(async {
+            let mut tx = pool.begin().await?;
+            let uid = Uuid::now_v7();
+
+            pg_insert(&mut *tx, uid, &PGGeneral::new(12)).await?;
+            let mut general: PGGeneral = pg_select(&mut *tx, uid).await?;
+            assert_eq!(general.sprite.shift_signed(), 12u16);
+            assert_eq!(general.money.shift_signed(), 0u64);
+
+            general.money = i64::unshift_signed(&500);
+            general.deathtimer = 42;
+            pg_update(&mut *tx, uid, &general).await?;
+            let general: PGGeneral = pg_select(&mut *tx, uid).await?;
+            assert_eq!(general.money.shift_signed(), 500u64);
+            assert_eq!(general.deathtimer, 42);
+
+            pg_delete_all::<PGGeneral>(&mut *tx, &[uid]).await?;
+            assert!(
+                pg_select_optional::<PGGeneral>(&mut *tx, uid)
+                    .await?
+                    .is_none()
+            );
+
+            tx.rollback().await?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[ignore = "needs ASCENDING_TEST_DATABASE_URL"]
+    fn slot_rows_round_trip() -> Result<()> {
+        let (rt, pool) = test_database()?;
+
+        rt.block_on(async {
+            let mut tx = pool.begin().await?;
+            let uid = Uuid::now_v7();
+
+            let empty = PGInventorySlot::empty().item();
+
+            pg_insert_range(&mut *tx, uid, 0..MAX_INV, &PGInventorySlot::empty()).await?;
+            let slots: Vec<PGInventorySlot> = pg_select_all(&mut *tx, uid).await?;
+            assert_eq!(slots.len(), MAX_INV);
+            assert!(slots.iter().map(|slot| slot.id).eq(0..MAX_INV as i16));
+            assert!(slots.iter().all(|slot| slot.item() == empty));
+
+            let mut item = Item::new(7);
+            item.val = 1;
+            item.level = 3;
+            item.data = [1, -2, 3, -4, 5];
+            item.instance = Some(Uuid::now_v7());
+            pg_update(&mut *tx, uid, &PGInventorySlot::new(4, &item)).await?;
+
+            let slots: Vec<PGInventorySlot> = pg_select_all(&mut *tx, uid).await?;
+            assert_eq!(slots[4].item(), item);
+            assert_eq!(slots[3].item(), empty);
+
+            pg_insert(&mut *tx, uid, &PGInventorySlot::new(MAX_INV, &item)).await?;
+            let slots: Vec<PGInventorySlot> = pg_select_all(&mut *tx, uid).await?;
+            assert_eq!(slots.len(), MAX_INV + 1);
+
+            pg_delete_all::<PGInventorySlot>(&mut *tx, &[uid]).await?;
+            let slots: Vec<PGInventorySlot> = pg_select_all(&mut *tx, uid).await?;
+            assert!(slots.is_empty());
+
+            tx.rollback().await?;
+            Ok(())
+        })
+    }
+
+    #[test]
+    #[ignore = "needs ASCENDING_TEST_DATABASE_URL"]
+    fn bound_queries_round_trip() -> Result<()> {
+        let (rt, pool) = test_database()?;
+
+        rt.block_on(async {
+            let mut tx = pool.begin().await?;
+            // Quotes would have broken the old string built queries.
+            let username = format!("O'Test {}", Uuid::now_v7().simple());
+
+            let (account,): (Uuid,) = sqlx::query_as(&PGAccount::into_empty())
+                .bind(&username)
+                .bind("127.0.0.1")
+                .bind("hash")
+                .bind(format!("{}@test.invalid", Uuid::now_v7().simple()))
+                .fetch_one(&mut *tx)
+                .await?;
+            sql_update_account(&mut tx, account, UserAccess::Admin).await?;
+
+            let (name, access): (String, UserAccess) =
+                sqlx::query_as("SELECT username, useraccess FROM public.account WHERE uid = $1")
+                    .bind(account)
+                    .fetch_one(&mut *tx)
+                    .await?;
+            assert_eq!(name, username);
+            assert_eq!(access, UserAccess::Admin);
+
+            pg_insert(&mut *tx, account, &PGGeneral::new(1)).await?;
+            sql_update_money(&mut tx, account, i64::unshift_signed(&250)).await?;
+            sql_update_resetcount(&mut tx, account, 3).await?;
+
+            let general: PGGeneral = pg_select(&mut *tx, account).await?;
+            assert_eq!(general.money.shift_signed(), 250u64);
+            assert_eq!(general.resetcount, 3);
+
+            tx.rollback().await?;
+            Ok(())
+        })
+    }
+}
@@ -1,15 +1,15 @@
 use crate::{
     containers::{Entity, GlobalKey, Storage, World},
     gametypes::*,
+    items::Item,
     sql::integers::Shifting,
     time_ext::MyInstant,
 };
+use uuid::Uuid;
 
 use super::{
-    PGCombat, PGEquipmentSlot, PGGeneral, PGInventorySlot, PGLocation, PGStorageSlot,
-    sql_update_combat, sql_update_equipment_slot, sql_update_general, sql_update_inventory_slot,
-    sql_update_level, sql_update_location, sql_update_money, sql_update_resetcount,
-    sql_update_storage_slot,
+    PGCombat, PGEquipmentSlot, PGGeneral, PGInventorySlot, PGItemEvent, PGLocation, PGStashSlot,
+    PGStorageSlot, SaveCommand,
 };
 
 pub fn get_time_left(cur_time: MyInstant, system_time: MyInstant) -> i64 {
@@ -24,10 +24,9 @@ pub fn update_player(storage: &Storage, world: &mut World, entity: GlobalKey) ->
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let p_data = p_data.try_lock()?;
 
-        sql_update_combat(
-            storage,
-            p_data.account.id,
-            PGCombat {
+        storage.persistence.save(SaveCommand::Combat {
+            cid: p_data.character.id,
+            data: PGCombat {
                 level: p_data.combat.level,
                 levelexp: i64::unshift_signed(&p_data.general.levelexp),
                 vital: p_data.combat.vitals.vital,
@@ -35,29 +34,27 @@ pub fn update_player(storage: &Storage, world: &mut World, entity: GlobalKey) ->
                 indeath: false,
                 pk: p_data.general.pk,
             },
-        )?;
+        })?;
 
-        sql_update_general(
-            storage,
-            p_data.account.id,
-            PGGeneral {
+        storage.persistence.save(SaveCommand::General {
+            cid: p_data.character.id,
+            data: PGGeneral {
                 sprite: i16::unshift_signed(&p_data.sprite.id),
                 money: i64::unshift_signed(&p_data.money.vals),
                 resetcount: p_data.general.resetcount,
                 itemtimer: get_time_left(p_data.item_timer.itemtimer, tick),
                 deathtimer: get_time_left(p_data.combat.death_timer.0, tick),
             },
-        )?;
+        })?;
 
-        sql_update_location(
-            storage,
-            p_data.account.id,
-            PGLocation {
+        storage.persistence.save(SaveCommand::Location {
+            cid: p_data.character.id,
+            data: PGLocation {
                 spawn: p_data.movement.spawn.pos,
                 pos: p_data.movement.pos,
                 dir: p_data.movement.dir as i16,
             },
-        )?;
+        })?;
     }
 
     Ok(())
@@ -72,20 +69,13 @@ pub fn update_inv(
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let p_data = p_data.try_lock()?;
 
-        let uid = p_data.account.id;
+        let cid = p_data.character.id;
 
         if let Some(slot_data) = p_data.inventory.items.get(slot) {
-            sql_update_inventory_slot(
-                storage,
-                uid,
-                PGInventorySlot {
-                    id: slot as i16,
-                    num: i32::unshift_signed(&slot_data.num),
-                    val: i16::unshift_signed(&slot_data.val),
-                    level: slot_data.level as i16,
-                    data: slot_data.data,
-                },
-            )?;
+            storage.persistence.save(SaveCommand::InventorySlot {
+                cid,
+                data: PGInventorySlot::new(slot, slot_data),
+            })?;
         }
     }
 
@@ -101,20 +91,35 @@ pub fn update_storage(
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let p_data = p_data.try_lock()?;
 
-        let uid = p_data.account.id;
+        let cid = p_data.character.id;
 
         if let Some(slot_data) = p_data.storage.items.get(slot) {
-            sql_update_storage_slot(
-                storage,
+            storage.persistence.save(SaveCommand::StorageSlot {
+                cid,
+                data: PGStorageSlot::new(slot, slot_data),
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn update_stash(
+    storage: &Storage,
+    world: &mut World,
+    entity: GlobalKey,
+    slot: usize,
+) -> Result<()> {
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let p_data = p_data.try_lock()?;
+
+        let uid = p_data.account.id;
+
+        if let Some(slot_data) = p_data.stash.items.get(slot) {
+            storage.persistence.save(SaveCommand::StashSlot {
                 uid,
-                PGStorageSlot {
-                    id: slot as i16,
-                    num: i32::unshift_signed(&slot_data.num),
-                    val: i16::unshift_signed(&slot_data.val),
-                    level: slot_data.level as i16,
-                    data: slot_data.data,
-                },
-            )?;
+                data: PGStashSlot::new(slot, slot_data),
+            })?;
         }
     }
 
@@ -130,20 +135,13 @@ pub fn update_equipment(
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let p_data = p_data.try_lock()?;
 
-        let uid = p_data.account.id;
+        let cid = p_data.character.id;
 
         if let Some(slot_data) = p_data.equipment.items.get(slot) {
-            sql_update_equipment_slot(
-                storage,
-                uid,
-                PGEquipmentSlot {
-                    id: slot as i16,
-                    num: i32::unshift_signed(&slot_data.num),
-                    val: i16::unshift_signed(&slot_data.val),
-                    level: slot_data.level as i16,
-                    data: slot_data.data,
-                },
-            )?;
+            storage.persistence.save(SaveCommand::EquipmentSlot {
+                cid,
+                data: PGEquipmentSlot::new(slot, slot_data),
+            })?;
         }
     }
 
@@ -154,15 +152,14 @@ pub fn update_pos(storage: &Storage, world: &mut World, entity: GlobalKey) -> Re
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let p_data = p_data.try_lock()?;
 
-        sql_update_location(
-            storage,
-            p_data.account.id,
-            PGLocation {
+        storage.persistence.save(SaveCommand::Location {
+            cid: p_data.character.id,
+            data: PGLocation {
                 spawn: p_data.movement.spawn.pos,
                 pos: p_data.movement.pos,
                 dir: p_data.movement.dir as i16,
             },
-        )?;
+        })?;
     }
 
     Ok(())
@@ -172,11 +169,47 @@ pub fn update_currency(storage: &Storage, world: &mut World, entity: GlobalKey)
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let p_data = p_data.try_lock()?;
 
-        sql_update_money(
-            storage,
-            p_data.account.id,
-            i64::unshift_signed(&p_data.money.vals),
-        )?;
+        storage.persistence.save(SaveCommand::Money {
+            cid: p_data.character.id,
+            money: i64::unshift_signed(&p_data.money.vals),
+        })?;
+    }
+
+    Ok(())
+}
+
+pub fn update_bank_money(storage: &Storage, world: &mut World, entity: GlobalKey) -> Result<()> {
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let p_data = p_data.try_lock()?;
+
+        storage.persistence.save(SaveCommand::BankMoney {
+            uid: p_data.account.id,
+            money: i64::unshift_signed(&p_data.bank_money.vals),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Saves the storage slot count and creates the rows for any slots added since `old_slots`.
+pub fn update_storage_slots(
+    storage: &Storage,
+    world: &mut World,
+    entity: GlobalKey,
+    old_slots: usize,
+) -> Result<()> {
+    if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
+        let p_data = p_data.try_lock()?;
+        let slots = p_data.storage.items.len();
+
+        storage.persistence.save(SaveCommand::StorageRows {
+            cid: p_data.character.id,
+            slots: old_slots..slots,
+        })?;
+        storage.persistence.save(SaveCommand::BankSlots {
+            uid: p_data.account.id,
+            slots: slots as i16,
+        })?;
     }
 
     Ok(())
@@ -186,17 +219,16 @@ pub fn update_level(storage: &Storage, world: &mut World, entity: GlobalKey) ->
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let p_data = p_data.try_lock()?;
 
-        sql_update_level(
-            storage,
-            p_data.account.id,
-            PGCombat {
+        storage.persistence.save(SaveCommand::Level {
+            cid: p_data.character.id,
+            data: PGCombat {
                 level: p_data.combat.level,
                 levelexp: i64::unshift_signed(&p_data.general.levelexp),
                 vital: p_data.combat.vitals.vital,
                 vital_max: p_data.combat.vitals.vitalmax,
                 ..Default::default()
             },
-        )?;
+        })?;
     }
     Ok(())
 }
@@ -205,7 +237,111 @@ pub fn update_resetcount(storage: &Storage, world: &mut World, entity: GlobalKey
     if let Some(Entity::Player(p_data)) = world.get_opt_entity(entity) {
         let p_data = p_data.try_lock()?;
 
-        sql_update_resetcount(storage, p_data.account.id, p_data.general.resetcount)?;
+        storage.persistence.save(SaveCommand::ResetCount {
+            cid: p_data.character.id,
+            resetcount: p_data.general.resetcount,
+        })?;
     }
     Ok(())
 }
+
+/// The other side of items given to or taken from a players inventory, for the item ledger.
+#[derive(Copy, Clone, Debug)]
+pub struct ItemMove {
+    pub reason: ItemReason,
+    pub place: ItemPlace,
+    /// The Character or Account that owns `place`, if any.
+    pub uid: Option<Uuid>,
+    pub correlation: Option<Uuid>,
+}
+
+impl ItemMove {
+    pub fn new(reason: ItemReason, place: ItemPlace) -> Self {
+        Self {
+            reason,
+            place,
+            uid: None,
+            correlation: None,
+        }
+    }
+
+    pub fn owned_by(mut self, uid: Uuid) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    pub fn correlated(mut self, correlation: Uuid) -> Self {
+        self.correlation = Some(correlation);
+        self
+    }
+}
+
+/// Queues an item ledger entry caused by `uid`, which is nil when the server caused it.
+pub fn record_item_event(storage: &Storage, uid: Uuid, event: PGItemEvent) -> Result<()> {
+    if !storage.config.item_ledger || event.quantity == 0 {
+        return Ok(());
+    }
+
+    storage.persistence.save(SaveCommand::ItemEvent {
+        uid,
+        data: Box::new(event),
+    })
+}
+
+/// Records `quantity` of `item` going into the players inventory from `from`.
+pub fn record_inv_gain(
+    storage: &Storage,
+    world: &mut World,
+    entity: GlobalKey,
+    item: &Item,
+    quantity: u16,
+    from: ItemMove,
+) -> Result<()> {
+    record_inv_move(storage, world, entity, item, quantity, from, true)
+}
+
+/// Records `quantity` of `item` leaving the players inventory for `to`.
+pub fn record_inv_loss(
+    storage: &Storage,
+    world: &mut World,
+    entity: GlobalKey,
+    item: &Item,
+    quantity: u16,
+    to: ItemMove,
+) -> Result<()> {
+    record_inv_move(storage, world, entity, item, quantity, to, false)
+}
+
+fn record_inv_move(
+    storage: &Storage,
+    world: &mut World,
+    entity: GlobalKey,
+    item: &Item,
+    quantity: u16,
+    other: ItemMove,
+    gained: bool,
+) -> Result<()> {
+    let (cid, pos) = match world.get_opt_entity(entity) {
+        Some(Entity::Player(p_data)) => {
+            let p_data = p_data.try_lock()?;
+
+            (p_data.character.id, p_data.movement.pos)
+        }
+        _ => return Ok(()),
+    };
+
+    let event = PGItemEvent::new(other.reason, item, quantity)
+        .at(pos)
+        .correlated(other.correlation);
+    let event = if gained {
+        event
+            .from(other.place, other.uid)
+            .to(ItemPlace::Inventory, Some(cid))
+    } else {
+        event
+            .from(ItemPlace::Inventory, Some(cid))
+            .to(other.place, other.uid)
+    };
+
+    record_item_event(storage, cid, event)
+}
@@ -84,7 +84,7 @@ pub fn player_spawn_packet(
 
         let mut buffer = MByteBuffer::new()?;
         buffer
-            .write(&p_data.account.username)?
+            .write(&p_data.character.name)?
             .write(p_data.movement.dir)?
             .write(entity)?
             .write(p_data.combat.level)?
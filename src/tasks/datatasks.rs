@@ -59,7 +59,8 @@ impl DataTaskToken {
                         .back_mut()
                         .ok_or(AscendingError::PacketCacheNotFound(self))?;
 
-                    if data.length() + buffer.length() > BUFFER_SIZE {
+                    // Room is left for a WebSocket frame header in front of the packet.
+                    if data.length() + buffer.length() > BUFFER_SIZE - BINARY_FRAME_HEADER_MAX {
                         *is_finished = true;
                         finish_cache(buffer, *count, false)?;
 